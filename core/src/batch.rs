@@ -0,0 +1,450 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use futures::future;
+use futures::sync::oneshot;
+use futures::{Async, Future, Poll};
+use jsonrpc_core::types::{Failure, Id, MethodCall, Output};
+use serde_json::{self, Value as JsonValue};
+use std::collections::HashMap;
+use std::error;
+use std::sync::{Arc, Mutex};
+use {Error, ErrorKind, Result, ResultExt, Transport};
+
+/// Collects RPC calls and sends them to the server in a single JSON-RPC 2.0 batch request.
+///
+/// Calls are added to the batch by making them on a client created with a
+/// [`BatchTransport`](struct.BatchTransport.html) from [`transport`](#method.transport). Each
+/// call returns an `RpcRequest` like usual, but nothing is sent until [`send`](#method.send) is
+/// called. The `RpcRequest`s then complete with their own result when the returned
+/// `BatchResponse` is driven to completion, so a failing call does not affect the other calls in
+/// the batch. Waiting for an `RpcRequest` before the `BatchResponse` blocks forever.
+///
+/// Notifications can be added to the batch the same way. They complete when the batch response
+/// arrives.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut batch = BatchRequest::new(transport_handle);
+/// let mut client = FizzBuzzClient::new(batch.transport());
+/// let fizz = client.fizz_buzz(3);
+/// let buzz = client.fizz_buzz(5);
+///
+/// let (_, (fizz, buzz)) = batch.send().join(fizz.join(buzz)).wait().unwrap();
+/// ```
+pub struct BatchRequest<T: Transport> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+struct Shared<T> {
+    transport: T,
+    /// The calls added so far, or `None` once the batch is sent.
+    calls: Option<Vec<QueuedCall>>,
+}
+
+struct QueuedCall {
+    /// The id of the call, or `None` for notifications.
+    id: Option<Id>,
+    request_raw: Vec<u8>,
+    response_tx: oneshot::Sender<Result<Vec<u8>>>,
+}
+
+impl<T: Transport> BatchRequest<T> {
+    /// Creates an empty batch that is sent over the given transport.
+    pub fn new(transport: T) -> Self {
+        BatchRequest {
+            shared: Arc::new(Mutex::new(Shared {
+                transport,
+                calls: Some(Vec::new()),
+            })),
+        }
+    }
+
+    /// Returns a transport adding all calls made through it to this batch. Request ids are taken
+    /// from the transport the batch is sent over, so they stay unique.
+    pub fn transport(&self) -> BatchTransport<T> {
+        BatchTransport {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Sends all calls added to the batch as one request. The returned future completes when the
+    /// response has been received and handed out to the individual calls. It fails if the
+    /// transport fails or the response is not valid, in which case all calls fail as well.
+    ///
+    /// Calls made after this fail right away. An empty batch is not sent at all.
+    pub fn send(self) -> BatchResponse<T::Future> {
+        with_shared(&self.shared, |shared| {
+            let calls = shared.calls.take().unwrap_or_default();
+            if calls.is_empty() {
+                return BatchResponse {
+                    transport_future: None,
+                    calls,
+                };
+            }
+            let mut batch_raw = vec![b'['];
+            for (i, call) in calls.iter().enumerate() {
+                if i > 0 {
+                    batch_raw.push(b',');
+                }
+                batch_raw.extend_from_slice(&call.request_raw);
+            }
+            batch_raw.push(b']');
+            trace!("Sending batch of {} calls", calls.len());
+            // The server does not respond to a batch of only notifications.
+            let transport_future = if calls.iter().all(|call| call.id.is_none()) {
+                shared.transport.send_notification(batch_raw)
+            } else {
+                shared.transport.send(batch_raw)
+            };
+            BatchResponse {
+                transport_future: Some(transport_future),
+                calls,
+            }
+        })
+    }
+}
+
+/// Runs `f` with the shared batch state locked.
+fn with_shared<T, R, F: FnOnce(&mut Shared<T>) -> R>(shared: &Mutex<Shared<T>>, f: F) -> R {
+    // The state is never left inconsistent, so a poisoned lock is still safe to use.
+    let mut shared = shared.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut shared)
+}
+
+/// A `Transport` adding calls to a `BatchRequest` instead of sending them. Created with
+/// `BatchRequest::transport`.
+pub struct BatchTransport<T: Transport> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T: Transport> Clone for BatchTransport<T> {
+    fn clone(&self) -> Self {
+        BatchTransport {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Transport> Transport for BatchTransport<T> {
+    type Future = Box<Future<Item = Vec<u8>, Error = Error> + Send>;
+    type Error = Error;
+
+    fn get_next_id(&mut self) -> u64 {
+        with_shared(&self.shared, |shared| shared.transport.get_next_id())
+    }
+
+    fn next_request_id(&mut self) -> Id {
+        with_shared(&self.shared, |shared| shared.transport.next_request_id())
+    }
+
+    fn send(&self, json_data: Vec<u8>) -> Self::Future {
+        match serde_json::from_slice::<MethodCall>(&json_data) {
+            Ok(method_call) => self.queue(Some(method_call.id), json_data),
+            Err(e) => Box::new(future::err(Error::with_chain(e, ErrorKind::SerializeError))),
+        }
+    }
+
+    fn send_notification(&self, json_data: Vec<u8>) -> Self::Future {
+        self.queue(None, json_data)
+    }
+
+    fn use_content_type(&mut self, content_type: &str) {
+        with_shared(&self.shared, |shared| {
+            shared.transport.use_content_type(content_type)
+        })
+    }
+
+    fn accept_null_response_id(&self) -> bool {
+        with_shared(&self.shared, |shared| {
+            shared.transport.accept_null_response_id()
+        })
+    }
+}
+
+impl<T: Transport> BatchTransport<T> {
+    /// Adds a call, or a notification if `id` is `None`, to the batch.
+    fn queue(
+        &self,
+        id: Option<Id>,
+        json_data: Vec<u8>,
+    ) -> Box<Future<Item = Vec<u8>, Error = Error> + Send> {
+        let (response_tx, response_rx) = oneshot::channel();
+        let queued = with_shared(&self.shared, |shared| match shared.calls {
+            Some(ref mut calls) => {
+                calls.push(QueuedCall {
+                    id,
+                    request_raw: json_data,
+                    response_tx,
+                });
+                true
+            }
+            None => false,
+        });
+        if !queued {
+            return Box::new(future::err(
+                ErrorKind::BatchError("Batch already sent").into(),
+            ));
+        }
+        Box::new(
+            response_rx
+                .map_err(|_| Error::from(ErrorKind::BatchError("Batch dropped without being sent")))
+                .and_then(|result| result),
+        )
+    }
+}
+
+/// Future returned from `BatchRequest::send`, handing out the responses to the calls in the
+/// batch when the response arrives.
+pub struct BatchResponse<F> {
+    transport_future: Option<F>,
+    calls: Vec<QueuedCall>,
+}
+
+impl<E, F> Future for BatchResponse<F>
+where
+    E: error::Error + Send + 'static,
+    F: Future<Item = Vec<u8>, Error = E>,
+{
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<(), Error> {
+        let response_raw = match self.transport_future {
+            Some(ref mut future) => match future.poll() {
+                Ok(Async::Ready(response_raw)) => response_raw,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => {
+                    self.fail_calls(|| ErrorKind::TransportError.into());
+                    return Err(Error::with_chain(e, ErrorKind::TransportError));
+                }
+            },
+            None => return Ok(Async::Ready(())),
+        };
+        self.transport_future = None;
+        if self.calls.iter().all(|call| call.id.is_none()) {
+            self.respond(Vec::new());
+            return Ok(Async::Ready(()));
+        }
+        match parse_outputs(&response_raw) {
+            Ok(outputs) => {
+                self.respond(outputs);
+                Ok(Async::Ready(()))
+            }
+            Err(e) => {
+                self.fail_calls(|| ErrorKind::ResponseError("Not a valid batch response").into());
+                Err(e)
+            }
+        }
+    }
+}
+
+impl<F> BatchResponse<F> {
+    /// Hands out the outputs to the calls they belong to. An error without an id, returned
+    /// instead of a batch, is handed out to all calls. Notifications get an empty response.
+    fn respond(&mut self, mut outputs: Vec<Output>) {
+        let mut calls = Vec::with_capacity(self.calls.len());
+        for call in self.calls.drain(..) {
+            match call.id.clone() {
+                Some(id) => calls.push((id, call)),
+                None => {
+                    let _ = call.response_tx.send(Ok(Vec::new()));
+                }
+            }
+        }
+
+        if outputs.len() == 1 && *outputs[0].id() == Id::Null {
+            if let Output::Failure(failure) = outputs.remove(0) {
+                for (id, call) in calls {
+                    let output = Output::Failure(Failure {
+                        id,
+                        ..failure.clone()
+                    });
+                    respond(call, Ok(output));
+                }
+                return;
+            }
+        }
+
+        let mut outputs_by_id = HashMap::new();
+        for output in outputs {
+            outputs_by_id.entry(output.id().clone()).or_insert(output);
+        }
+        for (id, call) in calls {
+            let output = match outputs_by_id.get(&id) {
+                Some(output) => Ok(output.clone()),
+                None => Err(ErrorKind::BatchError("No response to call in batch").into()),
+            };
+            respond(call, output);
+        }
+    }
+
+    fn fail_calls<G: Fn() -> Error>(&mut self, error: G) {
+        for call in self.calls.drain(..) {
+            let _ = call.response_tx.send(Err(error()));
+        }
+    }
+}
+
+fn respond(call: QueuedCall, output: Result<Output>) {
+    let response_raw = output.and_then(|output| {
+        serde_json::to_vec(&output).chain_err(|| ErrorKind::ResponseError("Not valid json"))
+    });
+    let _ = call.response_tx.send(response_raw);
+}
+
+/// Parses a batch response into its outputs. Entries that are not valid outputs are skipped, so
+/// the calls they belong to fail as having no response. A single output is also accepted, since
+/// servers reply with a single error to batches they can not parse.
+fn parse_outputs(response_raw: &[u8]) -> Result<Vec<Output>> {
+    let response: JsonValue = serde_json::from_slice(response_raw)
+        .chain_err(|| ErrorKind::ResponseError("Not valid json"))?;
+    match response {
+        JsonValue::Array(values) => Ok(values
+            .into_iter()
+            .filter_map(|value| serde_json::from_value(value).ok())
+            .collect()),
+        value => serde_json::from_value(value)
+            .map(|output| vec![output])
+            .chain_err(|| ErrorKind::ResponseError("Not a valid batch response")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    /// A transport responding to all requests with the same data, and recording the requests.
+    struct FixedTransport {
+        next_id: u64,
+        response: JsonValue,
+        requests: Arc<Mutex<Vec<JsonValue>>>,
+    }
+
+    impl FixedTransport {
+        fn new(response: JsonValue) -> Self {
+            FixedTransport {
+                next_id: 1,
+                response,
+                requests: Arc::new(Mutex::new(Vec::new())),
+            }
+        }
+    }
+
+    impl Transport for FixedTransport {
+        type Future = Box<Future<Item = Vec<u8>, Error = io::Error> + Send>;
+        type Error = io::Error;
+
+        fn get_next_id(&mut self) -> u64 {
+            self.next_id += 1;
+            self.next_id - 1
+        }
+
+        fn send(&self, json_data: Vec<u8>) -> Self::Future {
+            let request = serde_json::from_slice(&json_data).unwrap();
+            self.requests.lock().unwrap().push(request);
+            Box::new(future::ok(serde_json::to_vec(&self.response).unwrap()))
+        }
+    }
+
+    jsonrpc_client!(pub struct TestRpcClient {
+        pub fn double(&mut self, arg0: u64) -> RpcRequest<u64>;
+        pub fn log(&mut self, arg0: &str) -> RpcNotification;
+    });
+
+    #[test]
+    fn calls_are_sent_as_one_batch() {
+        let transport = FixedTransport::new(json!([
+            {"jsonrpc": "2.0", "id": 2, "result": 4},
+            {"jsonrpc": "2.0", "id": 1, "result": 2},
+        ]));
+        let requests = transport.requests.clone();
+        let batch = BatchRequest::new(transport);
+        let mut client = TestRpcClient::new(batch.transport());
+        let calls = vec![client.double(1), client.double(2)];
+
+        let (_, results) = batch.send().join(future::join_all(calls)).wait().unwrap();
+        assert_eq!(results, vec![2, 4]);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0][0]["id"], json!(1));
+        assert_eq!(requests[0][1]["params"], json!([2]));
+    }
+
+    #[test]
+    fn notifications_are_sent_in_batch() {
+        let transport = FixedTransport::new(json!([
+            {"jsonrpc": "2.0", "id": 1, "result": 2},
+        ]));
+        let requests = transport.requests.clone();
+        let batch = BatchRequest::new(transport);
+        let mut client = TestRpcClient::new(batch.transport());
+        let notification = client.log("doubling");
+        let call = client.double(1);
+
+        let (_, (_, result)) = batch.send().join(notification.join(call)).wait().unwrap();
+        assert_eq!(result, 2);
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[0][0],
+            json!({"jsonrpc": "2.0", "method": "log", "params": ["doubling"]})
+        );
+    }
+
+    #[test]
+    fn calls_fail_individually() {
+        let transport = FixedTransport::new(json!([
+            {"jsonrpc": "2.0", "id": 1, "result": 2},
+            {"jsonrpc": "2.0", "id": 2, "error": {"code": -32602, "message": "Invalid params"}},
+        ]));
+        let batch = BatchRequest::new(transport);
+        let mut client = TestRpcClient::new(batch.transport());
+        let ok_call = client.double(1);
+        let error_call = client.double(2);
+        let missing_call = client.double(3);
+
+        batch.send().wait().unwrap();
+        assert_eq!(ok_call.wait().unwrap(), 2);
+        match *error_call.wait().unwrap_err().kind() {
+            ErrorKind::JsonRpcError(ref error) => assert_eq!(error.message, "Invalid params"),
+            ref kind => panic!("Wrong error kind: {:?}", kind),
+        }
+        let error = missing_call.wait().unwrap_err();
+        assert!(error
+            .iter()
+            .any(|e| e.to_string().contains("No response to call in batch")));
+    }
+
+    #[test]
+    fn single_error_fails_all_calls() {
+        let transport = FixedTransport::new(json!(
+            {"jsonrpc": "2.0", "id": null, "error": {"code": -32600, "message": "Invalid Request"}}
+        ));
+        let batch = BatchRequest::new(transport);
+        let mut client = TestRpcClient::new(batch.transport());
+        let calls = vec![client.double(1), client.double(2)];
+
+        batch.send().wait().unwrap();
+        for call in calls {
+            match *call.wait().unwrap_err().kind() {
+                ErrorKind::JsonRpcError(ref error) => assert_eq!(error.message, "Invalid Request"),
+                ref kind => panic!("Wrong error kind: {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn calls_after_send_fail() {
+        let batch = BatchRequest::new(FixedTransport::new(json!([])));
+        let mut client = TestRpcClient::new(batch.transport());
+        batch.send().wait().unwrap();
+        assert!(client.double(1).wait().is_err());
+    }
+}
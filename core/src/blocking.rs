@@ -0,0 +1,201 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Blocking interface for code that does not use futures.
+//!
+//! [`BlockingClient`](struct.BlockingClient.html) calls methods by name directly on a transport,
+//! and [`wait`](fn.wait.html) runs the requests of clients generated by the `jsonrpc_client`
+//! macro. Both give up when a deadline is reached, instead of blocking forever if the transport
+//! never completes the request, for example because its event loop has died.
+//!
+//! ```rust,ignore
+//! let mut client = BlockingClient::new(transport_handle, Duration::from_secs(10));
+//! let fizz: String = client.call_method("fizz_buzz", [3])?;
+//!
+//! let mut client = FizzBuzzClient::new(transport_handle);
+//! let buzz = blocking::wait(client.fizz_buzz(5), Duration::from_secs(10))?;
+//! ```
+
+use futures::executor::{self, Notify};
+use futures::{Async, Future};
+use serde;
+use std::sync::Arc;
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+use {call_method, send_notification, Error, ErrorKind, Result, Transport};
+
+/// Calls RPC methods by name on a transport, blocking the calling thread until the response
+/// arrives or the timeout is reached.
+///
+/// Each call takes the `&mut self` of the client, so to call methods from several threads at the
+/// same time, give each thread its own client. With a cloneable transport, like the handles of
+/// `jsonrpc-client-http`, the client can simply be cloned.
+#[derive(Debug, Clone)]
+pub struct BlockingClient<T: Transport> {
+    transport: T,
+    timeout: Duration,
+}
+
+impl<T: Transport> BlockingClient<T> {
+    /// Creates a client sending requests over the given transport, failing them with a
+    /// `TimeoutError` if they take longer than `timeout`.
+    pub fn new(transport: T, timeout: Duration) -> Self {
+        BlockingClient { transport, timeout }
+    }
+
+    /// Calls the method `method` with the parameters `params`, and returns the result
+    /// deserialized into `R`. The parameters must serialize to an array or an object, or to a
+    /// single value, which is sent as an array with one element.
+    pub fn call_method<R, P>(&mut self, method: &str, params: P) -> Result<R>
+    where
+        R: serde::de::DeserializeOwned + Send + 'static,
+        P: serde::Serialize,
+    {
+        let request = call_method(&mut self.transport, method.to_owned(), params);
+        wait(request, self.timeout)
+    }
+
+    /// Sends a notification to the method `method` with the parameters `params`, and blocks
+    /// until the transport has sent it.
+    pub fn notify<P>(&mut self, method: &str, params: P) -> Result<()>
+    where
+        P: serde::Serialize,
+    {
+        let notification = send_notification(&mut self.transport, method.to_owned(), params);
+        wait(notification, self.timeout)
+    }
+
+    /// Returns the underlying transport.
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+/// Runs `future` to completion on the calling thread, like `Future::wait` does, but fails with a
+/// `TimeoutError` if it takes longer than `timeout`. The future is dropped on timeout.
+pub fn wait<F>(future: F, timeout: Duration) -> Result<F::Item>
+where
+    F: Future<Error = Error>,
+{
+    let deadline = Instant::now() + timeout;
+    let notify = Arc::new(ThreadNotify(thread::current()));
+    let mut spawn = executor::spawn(future);
+    loop {
+        if let Async::Ready(item) = spawn.poll_future_notify(&notify, 0)? {
+            return Ok(item);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            bail!(ErrorKind::TimeoutError(timeout));
+        }
+        // Spurious wakeups only cause another poll.
+        thread::park_timeout(deadline - now);
+    }
+}
+
+/// Wakes up the thread waiting for a future.
+struct ThreadNotify(Thread);
+
+impl Notify for ThreadNotify {
+    fn notify(&self, _id: usize) {
+        self.0.unpark();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use futures::sync::oneshot;
+    use serde_json::{self, Value as JsonValue};
+    use std::io;
+
+    /// A transport responding with the method name and parameters of each request, after
+    /// `delay` on another thread.
+    #[derive(Clone)]
+    struct DelayedEchoTransport {
+        delay: Duration,
+    }
+
+    impl Transport for DelayedEchoTransport {
+        type Future = Box<Future<Item = Vec<u8>, Error = io::Error> + Send>;
+        type Error = io::Error;
+
+        fn get_next_id(&mut self) -> u64 {
+            1
+        }
+
+        fn send(&self, json_data: Vec<u8>) -> Self::Future {
+            let request: JsonValue = serde_json::from_slice(&json_data).unwrap();
+            let response = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": [request["method"], request["params"]],
+            });
+            let (response_tx, response_rx) = oneshot::channel();
+            let delay = self.delay;
+            thread::spawn(move || {
+                thread::sleep(delay);
+                let _ = response_tx.send(serde_json::to_vec(&response).unwrap());
+            });
+            Box::new(response_rx.map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e)))
+        }
+    }
+
+    #[test]
+    fn call_method() {
+        let transport = DelayedEchoTransport {
+            delay: Duration::from_millis(10),
+        };
+        let mut client = BlockingClient::new(transport, Duration::from_secs(5));
+        let result: JsonValue = client.call_method("echo", ("first", 2)).unwrap();
+        assert_eq!(result, json!(["echo", ["first", 2]]));
+    }
+
+    #[test]
+    fn concurrent_calls() {
+        let transport = DelayedEchoTransport {
+            delay: Duration::from_millis(10),
+        };
+        let client = BlockingClient::new(transport, Duration::from_secs(5));
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let mut client = client.clone();
+                thread::spawn(move || client.call_method::<JsonValue, _>("echo", [i]).unwrap())
+            })
+            .collect();
+        for (i, thread) in threads.into_iter().enumerate() {
+            assert_eq!(thread.join().unwrap(), json!(["echo", [i]]));
+        }
+    }
+
+    #[test]
+    fn times_out() {
+        let transport = DelayedEchoTransport {
+            delay: Duration::from_secs(5),
+        };
+        let mut client = BlockingClient::new(transport, Duration::from_millis(50));
+        let start = Instant::now();
+        let error = client.call_method::<JsonValue, _>("echo", ()).unwrap_err();
+        match *error.kind() {
+            ErrorKind::TimeoutError(timeout) => assert_eq!(timeout, Duration::from_millis(50)),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn never_completing_future_times_out() {
+        let future = future::empty::<(), Error>();
+        assert!(wait(future, Duration::from_millis(10)).is_err());
+        assert_eq!(
+            wait(future::ok::<_, Error>(5), Duration::from_secs(0)).unwrap(),
+            5
+        );
+    }
+}
@@ -0,0 +1,554 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Transports for recording the exchanges with a real server to a cassette file, and replaying
+//! them later without the server, for example in CI.
+//!
+//! A [`RecordingTransport`](struct.RecordingTransport.html) wraps another transport and appends
+//! every request with its response or error to the cassette. A
+//! [`ReplayTransport`](struct.ReplayTransport.html) loads a cassette and answers requests with the
+//! recorded responses. Requests are matched by method and parameters, ignoring the ids, and the
+//! ids in the replayed responses are replaced with the ids of the new requests. Requests that
+//! were not recorded fail with a `CassetteError` showing the nearest recorded request.
+//!
+//! ```rust,ignore
+//! // Recording, against the real server.
+//! let transport = RecordingTransport::create(http_handle, "tests/fixtures/fizz_buzz.jsonl")?;
+//! let mut client = FizzBuzzClient::new(transport);
+//! assert_eq!(client.fizz_buzz(3).wait().unwrap(), "fizz");
+//!
+//! // Replaying, offline.
+//! let transport = ReplayTransport::load("tests/fixtures/fizz_buzz.jsonl")?;
+//! let mut client = FizzBuzzClient::new(transport);
+//! assert_eq!(client.fizz_buzz(3).wait().unwrap(), "fizz");
+//! ```
+//!
+//! A cassette has one JSON object per line, with the `request` as sent, the `response` body as a
+//! string or the `error` message of the transport, and the `timestamp` of the response in seconds
+//! since the Unix epoch. Bodies that are not UTF-8, like the MessagePack sent by a
+//! `CodecTransport`, are kept as `request_hex` or `response_hex` instead, with their bytes in
+//! hexadecimal. Only JSON requests can be replayed. Both transports fail with `CassetteError`s, carrying the messages of
+//! the transport errors, so a client sees the same errors when recording and replaying.
+
+use futures::{future, Future};
+use serde_json::{self, Value as JsonValue};
+use std::cmp;
+use std::error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use {Id, Transport};
+
+/// A transport sending requests over another transport, and recording them with their responses
+/// to a cassette file. See the [`cassette`](index.html) module.
+///
+/// Clones share the cassette.
+#[derive(Debug, Clone)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    cassette: Arc<Mutex<File>>,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// Records the exchanges over `inner` to a new cassette at `path`. An existing file is
+    /// emptied first.
+    pub fn create<P: AsRef<Path>>(inner: T, path: P) -> io::Result<Self> {
+        Ok(Self::new(inner, File::create(path)?))
+    }
+
+    /// Records the exchanges over `inner` to the end of the cassette at `path`, which is created
+    /// if it does not exist.
+    pub fn append<P: AsRef<Path>>(inner: T, path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(inner, file))
+    }
+
+    /// Returns the transport the requests are sent over.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    fn new(inner: T, file: File) -> Self {
+        RecordingTransport {
+            inner,
+            cassette: Arc::new(Mutex::new(file)),
+        }
+    }
+
+    /// Returns `future` recording the result of `request` when it completes. Failing to write
+    /// the cassette fails the request.
+    fn record(&self, request: Vec<u8>, future: T::Future) -> <Self as Transport>::Future {
+        let cassette = self.cassette.clone();
+        Box::new(future.then(move |result| {
+            let response = match result {
+                Ok(ref body) => Ok(&body[..]),
+                Err(ref error) => Err(error.to_string()),
+            };
+            let mut line = serde_json::to_vec(&interaction(&request, &response)).unwrap();
+            line.push(b'\n');
+            let mut file = cassette.lock().unwrap_or_else(|e| e.into_inner());
+            file.write_all(&line).map_err(|error| {
+                CassetteError(format!("Unable to write to the cassette: {}", error))
+            })?;
+            result.map_err(|error| CassetteError(error.to_string()))
+        }))
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    type Future = Box<Future<Item = Vec<u8>, Error = CassetteError> + Send>;
+    type Error = CassetteError;
+
+    fn get_next_id(&mut self) -> u64 {
+        self.inner.get_next_id()
+    }
+
+    fn next_request_id(&mut self) -> Id {
+        self.inner.next_request_id()
+    }
+
+    fn send(&self, json_data: Vec<u8>) -> Self::Future {
+        let future = self.inner.send(json_data.clone());
+        self.record(json_data, future)
+    }
+
+    fn send_notification(&self, json_data: Vec<u8>) -> Self::Future {
+        let future = self.inner.send_notification(json_data.clone());
+        self.record(json_data, future)
+    }
+
+    fn use_content_type(&mut self, content_type: &str) {
+        self.inner.use_content_type(content_type)
+    }
+
+    fn accept_null_response_id(&self) -> bool {
+        self.inner.accept_null_response_id()
+    }
+}
+
+/// Returns the cassette entry of `request` and its response.
+fn interaction(request: &[u8], response: &Result<&[u8], String>) -> JsonValue {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0);
+    let mut entry = json!({ "timestamp": timestamp });
+    match serde_json::from_slice(request) {
+        Ok(request) => entry["request"] = request,
+        Err(_) => set_body(&mut entry, "request", request),
+    }
+    match *response {
+        Ok(body) => set_body(&mut entry, "response", body),
+        Err(ref error) => entry["error"] = JsonValue::from(&error[..]),
+    }
+    entry
+}
+
+/// Sets `body` as a string on `entry`, or in hexadecimal with a `_hex` suffix on `key` if it is
+/// not UTF-8.
+fn set_body(entry: &mut JsonValue, key: &str, body: &[u8]) {
+    match ::std::str::from_utf8(body) {
+        Ok(body) => entry[key] = JsonValue::from(body),
+        Err(_) => {
+            let hex: String = body.iter().map(|byte| format!("{:02x}", byte)).collect();
+            entry[format!("{}_hex", key)] = JsonValue::from(hex);
+        }
+    }
+}
+
+/// Returns the body set on `entry` with `set_body`.
+fn get_body(entry: &JsonValue, key: &str) -> Option<Vec<u8>> {
+    if let Some(body) = entry.get(key).and_then(JsonValue::as_str) {
+        return Some(body.as_bytes().to_vec());
+    }
+    let hex = entry.get(format!("{}_hex", key))?.as_str()?;
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = ::std::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+/// A transport answering requests with the responses recorded on a cassette by a
+/// `RecordingTransport`. See the [`cassette`](index.html) module.
+///
+/// When several recorded requests match a request, the one replayed the fewest times is used,
+/// the first of them if there is a tie. So identical requests get the recorded responses in
+/// order, and requests made more often than recorded get them over again. Clones share the
+/// cassette, how often its entries were replayed and the request ids.
+#[derive(Debug, Clone)]
+pub struct ReplayTransport {
+    interactions: Arc<Vec<Interaction>>,
+    replayed: Arc<Mutex<Vec<usize>>>,
+    next_id: Arc<AtomicUsize>,
+}
+
+#[derive(Debug)]
+struct Interaction {
+    request: JsonValue,
+    /// The request without its ids, for matching.
+    normalized: JsonValue,
+    /// The response body, or the message of the transport error.
+    response: Result<Vec<u8>, String>,
+}
+
+impl ReplayTransport {
+    /// Loads the cassette at `path`. Fails with an `InvalidData` error if it is not a valid
+    /// cassette.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut interactions = Vec::new();
+        for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let interaction = Interaction::parse(&line).ok_or_else(|| {
+                let message = format!("Invalid cassette entry on line {}", index + 1);
+                io::Error::new(io::ErrorKind::InvalidData, message)
+            })?;
+            interactions.push(interaction);
+        }
+        Ok(ReplayTransport {
+            replayed: Arc::new(Mutex::new(vec![0; interactions.len()])),
+            interactions: Arc::new(interactions),
+            next_id: Arc::new(AtomicUsize::new(1)),
+        })
+    }
+
+    fn replay(&self, json_data: &[u8]) -> Result<Vec<u8>, CassetteError> {
+        let request: JsonValue = serde_json::from_slice(json_data)
+            .map_err(|error| CassetteError(format!("Unable to parse the request: {}", error)))?;
+        let normalized = without_ids(&request);
+        let interaction = {
+            let mut replayed = self.replayed.lock().unwrap_or_else(|e| e.into_inner());
+            let index = (0..self.interactions.len())
+                .filter(|&index| self.interactions[index].normalized == normalized)
+                .min_by_key(|&index| replayed[index])
+                .ok_or_else(|| self.mismatch(&request, &normalized))?;
+            replayed[index] += 1;
+            &self.interactions[index]
+        };
+        match interaction.response {
+            Ok(ref body) => Ok(with_ids_of(body, &interaction.request, &request)),
+            Err(ref error) => Err(CassetteError(error.clone())),
+        }
+    }
+
+    /// Returns the error for a request without a recorded match.
+    fn mismatch(&self, request: &JsonValue, normalized: &JsonValue) -> CassetteError {
+        let nearest = self
+            .interactions
+            .iter()
+            .min_by_key(|interaction| distance(&interaction.normalized, normalized));
+        CassetteError(match nearest {
+            Some(nearest) => format!(
+                "No recorded request matches {}, the nearest is {}",
+                request, nearest.request
+            ),
+            None => format!(
+                "No recorded request matches {}, the cassette is empty",
+                request
+            ),
+        })
+    }
+}
+
+impl Transport for ReplayTransport {
+    type Future = Box<Future<Item = Vec<u8>, Error = CassetteError> + Send>;
+    type Error = CassetteError;
+
+    fn get_next_id(&mut self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::SeqCst) as u64
+    }
+
+    fn send(&self, json_data: Vec<u8>) -> Self::Future {
+        Box::new(future::result(self.replay(&json_data)))
+    }
+}
+
+impl Interaction {
+    /// Parses a line of a cassette.
+    fn parse(line: &str) -> Option<Self> {
+        let entry: JsonValue = serde_json::from_str(line).ok()?;
+        // A request kept in hexadecimal never matches, since requests are replayed as JSON.
+        let request = entry
+            .get("request")
+            .or_else(|| entry.get("request_hex"))?
+            .clone();
+        let body = get_body(&entry, "response");
+        let error = entry.get("error").and_then(JsonValue::as_str);
+        let response = match (body, error) {
+            (Some(body), _) => Ok(body),
+            (_, Some(error)) => Err(error.to_owned()),
+            _ => return None,
+        };
+        Some(Interaction {
+            normalized: without_ids(&request),
+            request,
+            response,
+        })
+    }
+}
+
+/// Returns the calls of a request or batch.
+fn calls(request: &JsonValue) -> Vec<&JsonValue> {
+    match *request {
+        JsonValue::Array(ref calls) => calls.iter().collect(),
+        ref call => vec![call],
+    }
+}
+
+/// Returns a request or batch with the ids of its calls removed.
+fn without_ids(request: &JsonValue) -> JsonValue {
+    match *request {
+        JsonValue::Array(ref calls) => JsonValue::Array(calls.iter().map(without_ids).collect()),
+        JsonValue::Object(ref call) => JsonValue::Object(
+            call.iter()
+                .filter(|&(key, _)| key != "id")
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        ),
+        ref other => other.clone(),
+    }
+}
+
+/// Returns the recorded response `body` to `recorded`, with the ids replaced by the ids of the
+/// matching calls in `request`. Bodies that are not JSON are returned as they are.
+fn with_ids_of(body: &[u8], recorded: &JsonValue, request: &JsonValue) -> Vec<u8> {
+    let mut response: JsonValue = match serde_json::from_slice(body) {
+        Ok(response) => response,
+        Err(_) => return body.to_vec(),
+    };
+    let ids: Vec<(&JsonValue, &JsonValue)> = calls(recorded)
+        .into_iter()
+        .zip(calls(request))
+        .filter_map(|(recorded, call)| Some((recorded.get("id")?, call.get("id")?)))
+        .collect();
+    {
+        let outputs: Vec<&mut JsonValue> = match response {
+            JsonValue::Array(ref mut outputs) => outputs.iter_mut().collect(),
+            ref mut output => vec![output],
+        };
+        for output in outputs {
+            if let Some(id) = output.get_mut("id") {
+                if let Some(&(_, new_id)) = ids.iter().find(|&&(old_id, _)| *old_id == *id) {
+                    *id = new_id.clone();
+                }
+            }
+        }
+    }
+    serde_json::to_vec(&response).unwrap()
+}
+
+/// Returns how different two requests are, for finding the recorded request nearest to one
+/// that has no match. A different method weighs more than any single different parameter.
+fn distance(a: &JsonValue, b: &JsonValue) -> usize {
+    if let (Some(a), Some(b)) = (a.as_array(), b.as_array()) {
+        let common: usize = a.iter().zip(b).map(|(a, b)| distance(a, b)).sum();
+        common + cmp::max(a.len(), b.len()) - cmp::min(a.len(), b.len())
+    } else if let (Some(a), Some(b)) = (a.as_object(), b.as_object()) {
+        let weight = |key: &str| if key == "method" { 10 } else { 1 };
+        let in_a: usize = a
+            .iter()
+            .map(|(key, value)| match b.get(key) {
+                Some(other) if key == "method" && other != value => weight(key),
+                Some(other) => distance(value, other),
+                None => weight(key),
+            })
+            .sum();
+        let only_in_b: usize = b
+            .keys()
+            .filter(|key| !a.contains_key(*key))
+            .map(|key| weight(key))
+            .sum();
+        in_a + only_in_b
+    } else {
+        (a != b) as usize
+    }
+}
+
+/// The error of requests to a `RecordingTransport` or a `ReplayTransport`. Carries the message
+/// of the error of the transport that was recorded, or tells why a request could not be recorded
+/// or replayed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CassetteError(String);
+
+impl fmt::Display for CassetteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl error::Error for CassetteError {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{MockResponse, MockTransport};
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use ErrorKind;
+    #[cfg(feature = "msgpack")]
+    use {Codec, MsgPackCodec};
+
+    jsonrpc_client!(pub struct TestClient {
+        pub fn add(&mut self, a: u64, b: u64) -> RpcRequest<u64>;
+        pub fn name(&mut self) -> RpcRequest<String>;
+        pub fn log(&mut self, message: &str) -> RpcNotification;
+    });
+
+    /// A cassette in the temporary directory, removed when dropped.
+    struct TempCassette(PathBuf);
+
+    impl TempCassette {
+        fn new(name: &str) -> Self {
+            let file_name = format!("jsonrpc-cassette-{}-{}.jsonl", process::id(), name);
+            TempCassette(env::temp_dir().join(file_name))
+        }
+    }
+
+    impl Drop for TempCassette {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn record(cassette: &TempCassette) {
+        let mock = MockTransport::new();
+        mock.on_request(|r| r["params"] == json!([1, 2]), MockResponse::result(3))
+            .on_method("add", MockResponse::result(5))
+            .on_method("name", MockResponse::transport_error("Connection refused"))
+            .on_method("log", MockResponse::result(()));
+        let transport = RecordingTransport::create(mock, &cassette.0).unwrap();
+        let mut client = TestClient::new(transport);
+        assert_eq!(client.add(1, 2).wait().unwrap(), 3);
+        assert_eq!(client.add(2, 3).wait().unwrap(), 5);
+        assert!(client.name().wait().is_err());
+        client.log("hello").wait().unwrap();
+    }
+
+    #[test]
+    fn replays_recorded_responses() {
+        let cassette = TempCassette::new("replay");
+        record(&cassette);
+        let lines = fs::read_to_string(&cassette.0).unwrap();
+        assert_eq!(lines.lines().count(), 4);
+
+        let mut client = TestClient::new(ReplayTransport::load(&cassette.0).unwrap());
+        // Asked in another order, so the ids differ from the recorded ones.
+        assert_eq!(client.add(2, 3).wait().unwrap(), 5);
+        assert_eq!(client.add(1, 2).wait().unwrap(), 3);
+        assert_eq!(client.add(1, 2).wait().unwrap(), 3);
+        client.log("hello").wait().unwrap();
+        let error = client.name().wait().unwrap_err();
+        match *error.kind() {
+            ErrorKind::TransportError => {
+                assert_eq!(
+                    error.iter().nth(1).unwrap().to_string(),
+                    "Connection refused"
+                )
+            }
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn mismatch_shows_nearest_request() {
+        let cassette = TempCassette::new("mismatch");
+        record(&cassette);
+
+        let mut client = TestClient::new(ReplayTransport::load(&cassette.0).unwrap());
+        let error = client.add(2, 4).wait().unwrap_err();
+        let message = error.iter().nth(1).unwrap().to_string();
+        assert!(
+            message.starts_with("No recorded request matches"),
+            "{}",
+            message
+        );
+        assert!(message.contains("the nearest is"), "{}", message);
+        assert!(message.ends_with(r#""params":[2,3]}"#), "{}", message);
+    }
+
+    /// A transport answering every request with `response`, keeping the content type it is told
+    /// to use.
+    #[cfg(feature = "msgpack")]
+    #[derive(Clone)]
+    struct FixedTransport {
+        response: Vec<u8>,
+        content_type: Arc<Mutex<Option<String>>>,
+    }
+
+    #[cfg(feature = "msgpack")]
+    impl Transport for FixedTransport {
+        type Future = Box<Future<Item = Vec<u8>, Error = io::Error> + Send>;
+        type Error = io::Error;
+
+        fn get_next_id(&mut self) -> u64 {
+            1
+        }
+
+        fn send(&self, _: Vec<u8>) -> Self::Future {
+            Box::new(future::ok(self.response.clone()))
+        }
+
+        fn use_content_type(&mut self, content_type: &str) {
+            *self.content_type.lock().unwrap() = Some(content_type.to_owned());
+        }
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn records_codec_bodies_unchanged() {
+        let response = json!({"jsonrpc": "2.0", "result": "fixed", "id": 1});
+        let encoded = MsgPackCodec.serialize_request(&response).unwrap();
+        let inner = FixedTransport {
+            response: encoded.clone(),
+            content_type: Arc::default(),
+        };
+        let cassette = TempCassette::new("codec");
+        let transport = RecordingTransport::create(inner.clone(), &cassette.0).unwrap();
+        let mut client = TestClient::with_codec(transport, MsgPackCodec);
+        assert_eq!(client.name().wait().unwrap(), "fixed");
+        assert_eq!(
+            *inner.content_type.lock().unwrap(),
+            Some("application/msgpack".to_owned())
+        );
+
+        let line = fs::read_to_string(&cassette.0).unwrap();
+        let entry: JsonValue = serde_json::from_str(&line).unwrap();
+        assert!(entry.get("response").is_none());
+        assert_eq!(get_body(&entry, "response"), Some(encoded));
+        let request = MsgPackCodec
+            .deserialize_response(&get_body(&entry, "request").unwrap())
+            .unwrap();
+        assert_eq!(request["method"], "name");
+    }
+
+    #[test]
+    fn invalid_cassette() {
+        let cassette = TempCassette::new("invalid");
+        fs::write(&cassette.0, "{\"request\": {}}\n").unwrap();
+        let error = ReplayTransport::load(&cassette.0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use futures::{future, Future};
+#[cfg(feature = "msgpack")]
+use rmp_serde;
+use serde_json::{self, Value as JsonValue};
+use std::sync::Arc;
+use {Error, ErrorKind, Id, Result, ResultExt, Transport};
+
+/// The format JSON-RPC 2.0 messages are encoded in on the wire, for servers keeping the
+/// semantics of JSON-RPC 2.0 but encoding the messages in another format than JSON. Used by a
+/// [`CodecTransport`](struct.CodecTransport.html), which the `with_codec` constructor of the
+/// clients generated by `jsonrpc_client` creates. Clients created with `new` send JSON, like
+/// with the [`JsonCodec`](struct.JsonCodec.html).
+///
+/// The messages are handed over as JSON values, what the clients build them as. So a codec works
+/// for any format that serde can turn JSON values into and back without changing them.
+pub trait Codec: Send + Sync + 'static {
+    /// Returns the media type of the encoded messages, like `application/msgpack`. Transports
+    /// announcing it, like the HTTP transport in its `Content-Type` header, are told about it
+    /// with `Transport::use_content_type`.
+    fn content_type(&self) -> &str;
+
+    /// Encodes a request, a notification or a batch of them.
+    fn serialize_request(&self, request: &JsonValue) -> Result<Vec<u8>>;
+
+    /// Decodes the response to a request, or the responses to a batch.
+    fn deserialize_response(&self, response: &[u8]) -> Result<JsonValue>;
+}
+
+/// The JSON encoding of JSON-RPC 2.0, what clients created with `new` send.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn content_type(&self) -> &str {
+        "application/json"
+    }
+
+    fn serialize_request(&self, request: &JsonValue) -> Result<Vec<u8>> {
+        serde_json::to_vec(request).chain_err(|| ErrorKind::SerializeError)
+    }
+
+    fn deserialize_response(&self, response: &[u8]) -> Result<JsonValue> {
+        serde_json::from_slice(response).chain_err(|| ErrorKind::ResponseError("Not valid json"))
+    }
+}
+
+/// Encodes the messages in [MessagePack](https://msgpack.org), with the media type
+/// `application/msgpack`, like MessagePack-RPC. Requires the `msgpack` feature.
+///
+/// MessagePack binary data can not be turned into JSON values, so responses containing it fail
+/// with a `ResponseError`.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPackCodec;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MsgPackCodec {
+    fn content_type(&self) -> &str {
+        "application/msgpack"
+    }
+
+    fn serialize_request(&self, request: &JsonValue) -> Result<Vec<u8>> {
+        rmp_serde::to_vec(request).chain_err(|| ErrorKind::SerializeError)
+    }
+
+    fn deserialize_response(&self, response: &[u8]) -> Result<JsonValue> {
+        rmp_serde::from_slice(response)
+            .chain_err(|| ErrorKind::ResponseError("Not valid MessagePack"))
+    }
+}
+
+/// A transport sending the requests of a client over another transport, encoded with a
+/// [`Codec`](trait.Codec.html) instead of as JSON. Responses are decoded with the codec before
+/// the client parses them, except empty ones, which clients take as a `null` result.
+///
+/// Created by the `with_codec` constructor of the clients generated by `jsonrpc_client`:
+///
+/// ```rust,ignore
+/// let mut client = FizzBuzzClient::with_codec(http_handle, MsgPackCodec);
+/// assert_eq!(client.fizz_buzz(3).call()?, "fizz");
+/// ```
+///
+/// Errors of the inner transport are chained to a `TransportError`.
+#[derive(Debug)]
+pub struct CodecTransport<T, C> {
+    inner: T,
+    codec: Arc<C>,
+}
+
+impl<T: Transport, C: Codec> CodecTransport<T, C> {
+    /// Sends the requests over `inner`, encoded with `codec`. Tells `inner` the content type of
+    /// the codec.
+    pub fn new(mut inner: T, codec: C) -> Self {
+        inner.use_content_type(codec.content_type());
+        CodecTransport {
+            inner,
+            codec: Arc::new(codec),
+        }
+    }
+
+    /// Returns the transport the requests are sent over.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the codec the requests are encoded with.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    fn encode(&self, json_data: &[u8]) -> Result<Vec<u8>> {
+        let message: JsonValue =
+            serde_json::from_slice(json_data).chain_err(|| ErrorKind::SerializeError)?;
+        self.codec.serialize_request(&message)
+    }
+}
+
+impl<T: Clone, C> Clone for CodecTransport<T, C> {
+    fn clone(&self) -> Self {
+        CodecTransport {
+            inner: self.inner.clone(),
+            codec: self.codec.clone(),
+        }
+    }
+}
+
+impl<T: Transport, C: Codec> Transport for CodecTransport<T, C> {
+    type Future = Box<Future<Item = Vec<u8>, Error = Error> + Send>;
+    type Error = Error;
+
+    fn get_next_id(&mut self) -> u64 {
+        self.inner.get_next_id()
+    }
+
+    fn next_request_id(&mut self) -> Id {
+        self.inner.next_request_id()
+    }
+
+    fn send(&self, json_data: Vec<u8>) -> Self::Future {
+        let request = match self.encode(&json_data) {
+            Ok(request) => request,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        let codec = self.codec.clone();
+        Box::new(self.inner.send(request).then(move |result| {
+            let response = result.chain_err(|| ErrorKind::TransportError)?;
+            if response.is_empty() {
+                return Ok(response);
+            }
+            let response = codec.deserialize_response(&response)?;
+            serde_json::to_vec(&response).chain_err(|| ErrorKind::ResponseError("Not valid json"))
+        }))
+    }
+
+    fn send_notification(&self, json_data: Vec<u8>) -> Self::Future {
+        let notification = match self.encode(&json_data) {
+            Ok(notification) => notification,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        // Responses to notifications are ignored, so they are not decoded.
+        Box::new(
+            self.inner
+                .send_notification(notification)
+                .then(|result| result.chain_err(|| ErrorKind::TransportError)),
+        )
+    }
+
+    fn accept_null_response_id(&self) -> bool {
+        self.inner.accept_null_response_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{MockResponse, MockTransport};
+
+    jsonrpc_client!(pub struct EchoClient {
+        pub fn echo(&mut self, text: &str) -> RpcRequest<String>;
+        pub fn log(&mut self, text: &str) -> RpcNotification;
+    });
+
+    #[test]
+    fn json_codec() {
+        let transport = MockTransport::new();
+        transport
+            .on_method("echo", MockResponse::result("hello"))
+            .on_method("log", MockResponse::result(()));
+        let mut client = EchoClient::with_codec(transport.clone(), JsonCodec);
+
+        assert_eq!(client.echo("hello").call().unwrap(), "hello");
+        client.log("hello").call().unwrap();
+        assert_eq!(
+            transport.requests_for("echo")[0].params,
+            Some(json!(["hello"]))
+        );
+        assert_eq!(transport.requests_for("log").len(), 1);
+    }
+
+    #[test]
+    fn undecodable_response() {
+        match *JsonCodec
+            .deserialize_response(b"<html>")
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::ResponseError(_) => (),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_round_trip() {
+        let message = json!({"jsonrpc": "2.0", "method": "echo", "params": [1, "a"], "id": 1});
+        let encoded = MsgPackCodec.serialize_request(&message).unwrap();
+        assert!(serde_json::from_slice::<JsonValue>(&encoded).is_err());
+        assert_eq!(
+            MsgPackCodec.deserialize_response(&encoded).unwrap(),
+            message
+        );
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Interoperability with `std::future` and async/await, compiled with the "compat" feature.
+//!
+//! The requests of clients generated by the `jsonrpc_client` macro, and the futures of
+//! transports, are futures 0.1 futures. [`RpcRequest::call_async`] and
+//! [`RpcNotification::call_async`] turn them into `std::future::Future`s, so they can be awaited
+//! in async code without depending on futures 0.1. [`to_std`](fn.to_std.html) does the same for
+//! any futures 0.1 future, like the ones returned by transports.
+//!
+//! ```rust,ignore
+//! let mut client = FizzBuzzClient::new(transport_handle);
+//! let fizz = client.fizz_buzz(3).call_async().await?;
+//! ```
+//!
+//! The futures can be polled by any executor. The transport still has to drive the requests
+//! itself, like the standalone `HttpTransport` of `jsonrpc-client-http` does on its own thread.
+//!
+//! [`RpcRequest::call_async`]: ../struct.RpcRequest.html#method.call_async
+//! [`RpcNotification::call_async`]: ../struct.RpcNotification.html#method.call_async
+
+use futures::Future;
+use futures03::compat::{Compat01As03, Future01CompatExt};
+use serde;
+use std::future::Future as StdFuture;
+use {Result, RpcNotification, RpcRequest};
+
+/// Returns a `std::future::Future` completing with the result of the futures 0.1 `future`.
+pub fn to_std<F: Future>(
+    future: F,
+) -> impl StdFuture<Output = ::std::result::Result<F::Item, F::Error>> {
+    future.compat()
+}
+
+impl<T, E, F> RpcRequest<T, F>
+where
+    T: serde::de::DeserializeOwned + Send + 'static,
+    E: ::std::error::Error + Send + 'static,
+    F: Future<Item = Vec<u8>, Error = E> + Send + 'static,
+{
+    /// Consume this RPC request and return a `std::future::Future` performing it, for awaiting
+    /// the result in async code. See the [`compat`](compat/index.html) module.
+    pub fn call_async(self) -> impl StdFuture<Output = Result<T>> {
+        Compat01As03::new(self)
+    }
+}
+
+impl<E, F> RpcNotification<F>
+where
+    E: ::std::error::Error + Send + 'static,
+    F: Future<Item = Vec<u8>, Error = E> + Send + 'static,
+{
+    /// Consume this RPC notification and return a `std::future::Future` sending it, for awaiting
+    /// it in async code. See the [`compat`](compat/index.html) module.
+    pub fn call_async(self) -> impl StdFuture<Output = Result<()>> {
+        Compat01As03::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use futures03::executor::block_on;
+    use mock::{MockResponse, MockTransport};
+
+    jsonrpc_client!(pub struct CompatClient {
+        pub fn ping(&mut self, arg0: &str) -> RpcRequest<String>;
+        pub fn log(&mut self, arg0: &str) -> RpcNotification;
+    });
+
+    #[test]
+    fn awaits_requests() {
+        let transport = MockTransport::new();
+        transport
+            .on_method("ping", MockResponse::result("pong"))
+            .on_method("log", MockResponse::result(()));
+        let mut client = CompatClient::new(transport);
+        assert_eq!(block_on(client.ping("hello").call_async()).unwrap(), "pong");
+        block_on(client.log("note").call_async()).unwrap();
+    }
+
+    #[test]
+    fn converts_any_future() {
+        let result = block_on(to_std(future::ok::<_, ()>(7)));
+        assert_eq!(result, Ok(7));
+    }
+}
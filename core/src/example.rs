@@ -21,5 +21,9 @@ jsonrpc_client!(
         /// returns a `String`. From the name one could guess it will concatenate the two
         /// arguments. But that of course depends on the server where this call is sent.
         pub fn concat(&mut self, arg0: String, arg1: u64) -> RpcRequest<String>;
+
+        /// Example RPC notification named "log". Notifications get no response, so this only
+        /// tells the server to log the message, without knowing if it did.
+        pub fn log(&mut self, message: &str) -> RpcNotification;
     }
 );
@@ -0,0 +1,125 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use jsonrpc_core::types::Id;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Generates the ids of JSON-RPC requests, for transports that let the ids be customized.
+///
+/// The ids only have to be unique among the requests in flight at the same time, but servers
+/// and logs are easier to follow if they are never reused. Closures returning an `Id` implement
+/// this trait too.
+pub trait IdGenerator: Send + Sync {
+    /// Returns the id of the next request. It should be a number or a string, since
+    /// `Id::Null` is meant for responses to requests whose id could not be read.
+    fn next_id(&self) -> Id;
+}
+
+impl<F: Fn() -> Id + Send + Sync> IdGenerator for F {
+    fn next_id(&self) -> Id {
+        self()
+    }
+}
+
+/// Generates numeric ids counting up from an initial value, or string ids with a fixed prefix
+/// followed by the count.
+#[derive(Debug)]
+pub struct CounterIdGenerator {
+    next: AtomicUsize,
+    prefix: Option<String>,
+}
+
+impl CounterIdGenerator {
+    /// Creates a generator of numeric ids, starting with `initial`. Wraps around on overflow.
+    pub fn new(initial: u64) -> Self {
+        CounterIdGenerator {
+            next: AtomicUsize::new(initial as usize),
+            prefix: None,
+        }
+    }
+
+    /// Creates a generator of string ids made of `prefix` followed by a count starting at 1,
+    /// like `"billing-1"`. Gives the requests of a client ids of their own, which tell them
+    /// apart in logs shared with other clients.
+    pub fn with_prefix(prefix: &str) -> Self {
+        CounterIdGenerator {
+            next: AtomicUsize::new(1),
+            prefix: Some(prefix.to_owned()),
+        }
+    }
+}
+
+impl Default for CounterIdGenerator {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl IdGenerator for CounterIdGenerator {
+    fn next_id(&self) -> Id {
+        let count = self.next.fetch_add(1, Ordering::SeqCst) as u64;
+        match self.prefix {
+            Some(ref prefix) => Id::Str(format!("{}{}", prefix, count)),
+            None => Id::Num(count),
+        }
+    }
+}
+
+/// Generates string ids that are random version 4 UUIDs, for servers requiring them.
+#[cfg(feature = "uuid")]
+#[derive(Debug, Default)]
+pub struct UuidIdGenerator;
+
+#[cfg(feature = "uuid")]
+impl IdGenerator for UuidIdGenerator {
+    fn next_id(&self) -> Id {
+        let mut bytes: [u8; 16] = ::rand::random();
+        // The version and the variant defined by RFC 4122.
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        Id::Str(format!(
+            "{}-{}-{}-{}-{}",
+            hex[0..4].concat(),
+            hex[4..6].concat(),
+            hex[6..8].concat(),
+            hex[8..10].concat(),
+            hex[10..16].concat()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters() {
+        let numbers = CounterIdGenerator::new(5);
+        assert_eq!(numbers.next_id(), Id::Num(5));
+        assert_eq!(numbers.next_id(), Id::Num(6));
+        let strings = CounterIdGenerator::with_prefix("billing-");
+        assert_eq!(strings.next_id(), Id::Str("billing-1".to_owned()));
+        assert_eq!(strings.next_id(), Id::Str("billing-2".to_owned()));
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuids() {
+        let (first, second) = (UuidIdGenerator.next_id(), UuidIdGenerator.next_id());
+        assert_ne!(first, second);
+        match first {
+            Id::Str(uuid) => {
+                assert_eq!(uuid.len(), 36);
+                assert_eq!(&uuid[14..15], "4");
+                assert!("89ab".contains(&uuid[19..20]));
+            }
+            id => panic!("not a string id: {:?}", id),
+        }
+    }
+}
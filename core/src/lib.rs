@@ -20,8 +20,36 @@
 //! The `jsonrpc-client-core` crate itself and the structs generated by the `jsonrpc_client` macro
 //! are transport agnostic. They can use any type implementing the `Transport` trait.
 //!
-//! The main (and so far only) transport implementation is the Hyper based HTTP implementation
-//! in the [`jsonrpc-client-http`](../jsonrpc_client_http/index.html) crate.
+//! The main transport implementation is the Hyper based HTTP implementation in the
+//! [`jsonrpc-client-http`](../jsonrpc_client_http/index.html) crate. Requests can also be sent over
+//! a WebSocket connection with the [`jsonrpc-client-ws`](../jsonrpc_client_ws/index.html) crate.
+//!
+//! # Batches
+//!
+//! Several calls can be sent in one JSON-RPC 2.0 batch request with a
+//! [`BatchRequest`](struct.BatchRequest.html). The calls are made on a client using the
+//! transport returned from `BatchRequest::transport`, and each call still gets its own result.
+//!
+//! # Subscriptions
+//!
+//! Events can be subscribed to over transports without push support, like HTTP, with methods
+//! returning an [`RpcSubscription`](struct.RpcSubscription.html). It is a `Stream` of the events,
+//! fetched by polling the server with the subscription id.
+//!
+//! # Blocking calls
+//!
+//! Code not using futures can call methods by name with a
+//! [`BlockingClient`](blocking/struct.BlockingClient.html), or wait for the requests of a
+//! generated client with [`blocking::wait`](blocking/fn.wait.html). Both fail with a
+//! `TimeoutError` instead of blocking forever.
+//!
+//! # Testing
+//!
+//! Code using a generated client can be unit tested without a server by giving the client a
+//! [`MockTransport`](mock/struct.MockTransport.html), which answers with canned responses and
+//! records the requests it receives. Integration tests can record the exchanges with a real
+//! server once and replay them offline with the transports of the [`cassette`](cassette/index.html)
+//! module.
 //!
 //! # Example
 //!
@@ -59,16 +87,22 @@
 pub extern crate error_chain;
 #[macro_use]
 extern crate futures;
+#[cfg(feature = "compat")]
+extern crate futures03;
 extern crate jsonrpc_core;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "uuid")]
+extern crate rand;
+#[cfg(feature = "msgpack")]
+extern crate rmp_serde;
 extern crate serde;
-#[cfg_attr(test, macro_use)]
+#[macro_use]
 extern crate serde_json;
 
 use futures::future::Future;
 use futures::Async;
-use jsonrpc_core::types::{Id, MethodCall, Params, Version};
+use jsonrpc_core::types::{MethodCall, Notification, Params, Version};
 use serde_json::Value as JsonValue;
 
 /// Contains the main macro of this crate, `jsonrpc_client`.
@@ -77,6 +111,54 @@ mod macros;
 
 /// Module for functions parsing the response to a RPC method call.
 mod response;
+use response::ExpectedId;
+
+/// Module for sending several RPC method calls in one JSON-RPC 2.0 batch request.
+mod batch;
+pub use batch::{BatchRequest, BatchResponse, BatchTransport};
+
+/// Module for calls with parameters streamed to the transport instead of serialized up front.
+mod streaming;
+pub use streaming::{call_method_streaming, BodyStream, StreamingTransport};
+
+/// Module for the named parameters of methods generated by `jsonrpc_client`.
+mod params;
+pub use params::NamedParams;
+
+/// Module for calls returning the raw JSON of their result.
+mod raw;
+pub use raw::{call_method_raw, RpcRawRequest};
+pub use serde_json::value::RawValue;
+
+/// Module for encoding the messages in other formats than JSON.
+mod codec;
+#[cfg(feature = "msgpack")]
+pub use codec::MsgPackCodec;
+pub use codec::{Codec, CodecTransport, JsonCodec};
+
+/// Module for subscriptions to events that are polled for over the transport.
+mod subscription;
+pub use subscription::{subscribe, RpcSubscription, UnsubscribeFuture};
+
+/// Module for generating the ids of requests.
+mod id;
+#[cfg(feature = "uuid")]
+pub use id::UuidIdGenerator;
+pub use id::{CounterIdGenerator, IdGenerator};
+pub use jsonrpc_core::types::Id;
+
+/// Module for the error objects of JSON-RPC 2.0 error responses.
+mod rpc_error;
+pub use rpc_error::{RpcError, StandardErrorCode};
+
+pub mod blocking;
+
+#[cfg(feature = "compat")]
+pub mod compat;
+
+pub mod mock;
+
+pub mod cassette;
 
 /// Module containing an example client. To show in the docs what a generated struct look like.
 pub mod example;
@@ -97,9 +179,31 @@ error_chain! {
             display("Unable to deserialize the response: {}", msg)
         }
         /// The request was replied to, but with a JSON-RPC 2.0 error.
-        JsonRpcError(error: jsonrpc_core::Error) {
+        JsonRpcError(error: RpcError) {
             description("Method call returned JSON-RPC 2.0 error")
-            display("JSON-RPC 2.0 Error: {} ({})", error.code.description(), error.message)
+            display("JSON-RPC 2.0 Error: {}", error)
+        }
+        /// The response is to another request than the one sent, since its id is not the id of
+        /// the request. Contains the id of the request, the id of the response and the raw
+        /// response body, for reporting the misbehaving server. A `null` id is accepted instead
+        /// if the transport is configured to, see `Transport::accept_null_response_id`.
+        ResponseIdMismatch(expected: Id, got: Id, body: Vec<u8>) {
+            description("The response id is not equal to the request id")
+            display(
+                "Response id {} not equal to request id {}",
+                response::id_string(got),
+                response::id_string(expected)
+            )
+        }
+        /// Error specific to calls sent in a `BatchRequest`.
+        BatchError(msg: &'static str) {
+            description("Error in a JSON-RPC 2.0 batch request")
+            display("Error in a JSON-RPC 2.0 batch request: {}", msg)
+        }
+        /// A call made with the `blocking` module did not complete within the given timeout.
+        TimeoutError(timeout: ::std::time::Duration) {
+            description("Timeout while waiting for the response")
+            display("No response within the timeout of {:?}", timeout)
         }
     }
 }
@@ -121,6 +225,18 @@ where
     pub fn call(self) -> Result<T> {
         self.wait()
     }
+
+    /// Turns this RPC request into one completing with the raw JSON of the result, without
+    /// deserializing it into `T`. See [`RpcRawRequest`](struct.RpcRawRequest.html).
+    pub fn raw(self) -> RpcRawRequest<F> {
+        RpcRawRequest::new(self.0.map(|inner| (inner.transport_future, inner.id)))
+    }
+
+    /// Consume this RPC request and run it synchronously like `call`, but return the raw JSON of
+    /// the result instead of deserializing it.
+    pub fn call_raw(self) -> Result<Box<RawValue>> {
+        self.raw().call()
+    }
 }
 
 impl<T, E, F> Future for RpcRequest<T, F>
@@ -144,12 +260,12 @@ where
 
 struct InnerRpcRequest<T, F> {
     transport_future: F,
-    id: Id,
+    id: ExpectedId,
     _marker: ::std::marker::PhantomData<T>,
 }
 
 impl<T, F> InnerRpcRequest<T, F> {
-    fn new(transport_future: F, id: Id) -> Self {
+    fn new(transport_future: F, id: ExpectedId) -> Self {
         Self {
             transport_future,
             id,
@@ -176,12 +292,58 @@ where
         trace!(
             "Deserializing {} byte response to request with id {:?}",
             response_raw.len(),
-            self.id
+            self.id.id
         );
         response::parse(&response_raw, &self.id).map(|t| Async::Ready(t))
     }
 }
 
+/// A lazy RPC notification `Future`. Works like `RpcRequest`, but notifications get no response,
+/// so whatever the transport returns is ignored. Completes when the transport has sent the
+/// notification.
+pub struct RpcNotification<F>(::std::result::Result<F, Option<Error>>);
+
+impl<E, F> RpcNotification<F>
+where
+    E: ::std::error::Error + Send + 'static,
+    F: Future<Item = Vec<u8>, Error = E> + Send + 'static,
+{
+    /// Consume this RPC notification and send it synchronously. This blocks until the transport
+    /// is done sending it.
+    pub fn call(self) -> Result<()> {
+        self.wait()
+    }
+}
+
+impl<E, F> Future for RpcNotification<F>
+where
+    E: ::std::error::Error + Send + 'static,
+    F: Future<Item = Vec<u8>, Error = E> + Send + 'static,
+{
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> futures::Poll<Self::Item, Self::Error> {
+        match self.0 {
+            Ok(ref mut transport_future) => {
+                let response_raw = try_ready!(
+                    transport_future
+                        .poll()
+                        .chain_err(|| ErrorKind::TransportError)
+                );
+                trace!(
+                    "Ignoring {} byte response to notification",
+                    response_raw.len()
+                );
+                Ok(Async::Ready(()))
+            }
+            Err(ref mut error_option) => Err(error_option
+                .take()
+                .expect("Cannot call RpcNotification poll twice when in error state")),
+        }
+    }
+}
+
 
 /// Trait for types acting as a transport layer for the JSON-RPC 2.0 clients generated by the
 /// `jsonrpc_client` macro.
@@ -196,9 +358,37 @@ pub trait Transport {
     /// to fill in the "id" field of a request.
     fn get_next_id(&mut self) -> u64;
 
+    /// Returns the id of the next request, which can also be a string. Used by the RPC clients
+    /// instead of `get_next_id`, so transports with string ids or configurable ids override
+    /// this. The default implementation returns the number from `get_next_id`.
+    fn next_request_id(&mut self) -> Id {
+        Id::Num(self.get_next_id())
+    }
+
     /// Sends the given data over the transport and returns a future that will complete with the
     /// response to the request, or the transport specific error if something went wrong.
     fn send(&self, json_data: Vec<u8>) -> Self::Future;
+
+    /// Sends the given notification data over the transport. Notifications get no response, so
+    /// anything the returned future completes with is ignored by the RPC clients. The default
+    /// implementation sends the notification just like a request.
+    fn send_notification(&self, json_data: Vec<u8>) -> Self::Future {
+        self.send(json_data)
+    }
+
+    /// Tells the transport the media type of the data it sends, when it is not JSON. Called by
+    /// `CodecTransport::new` with the content type of its codec, for transports announcing it,
+    /// like the HTTP transport in its `Content-Type` header. The default implementation ignores
+    /// it.
+    fn use_content_type(&mut self, _content_type: &str) {}
+
+    /// Returns if responses with a `null` id are accepted as responses to single requests, for
+    /// servers that do not echo the id of the request back. Such responses are logged with a
+    /// warning. Responses with any other id than the request id fail with a
+    /// `ResponseIdMismatch` error. The default implementation returns `false`.
+    fn accept_null_response_id(&self) -> bool {
+        false
+    }
 }
 
 
@@ -219,7 +409,7 @@ where
     P: serde::Serialize,
     R: serde::de::DeserializeOwned + Send + 'static,
 {
-    let id = Id::Num(transport.get_next_id());
+    let id = transport.next_request_id();
     trace!("Serializing call to method \"{}\" with id {:?}", method, id);
     let request_serialization_result =
         serialize_request(id.clone(), method, params).chain_err(|| ErrorKind::SerializeError);
@@ -227,11 +417,36 @@ where
         Err(e) => RpcRequest(Err(Some(e))),
         Ok(request_raw) => {
             let transport_future = transport.send(request_raw);
+            let id = ExpectedId::new(id, transport);
             RpcRequest(Ok(InnerRpcRequest::new(transport_future, id)))
         }
     }
 }
 
+/// Prepares a lazy `RpcNotification` with a given transport, method and parameters. Works like
+/// `call_method`, but the notification has no id and gets no response.
+///
+/// # Not intended for direct use
+/// This is being called from the client structs generated by the `jsonrpc_client` macro. This
+/// function is not intended to be used directly, only the generated structs should call this.
+pub fn send_notification<T, P>(
+    transport: &mut T,
+    method: String,
+    params: P,
+) -> RpcNotification<T::Future>
+where
+    T: Transport,
+    P: serde::Serialize,
+{
+    trace!("Serializing notification to method \"{}\"", method);
+    let notification_serialization_result =
+        serialize_notification(method, params).chain_err(|| ErrorKind::SerializeError);
+    match notification_serialization_result {
+        Err(e) => RpcNotification(Err(Some(e))),
+        Ok(notification_raw) => RpcNotification(Ok(transport.send_notification(notification_raw))),
+    }
+}
+
 
 /// Creates a JSON-RPC 2.0 request to the given method with the given parameters.
 fn serialize_request<P>(
@@ -242,21 +457,43 @@ fn serialize_request<P>(
 where
     P: serde::Serialize,
 {
-    let serialized_params = match serde_json::to_value(params)? {
-        JsonValue::Null => None,
-        JsonValue::Array(vec) => Some(Params::Array(vec)),
-        JsonValue::Object(obj) => Some(Params::Map(obj)),
-        value => Some(Params::Array(vec![value])),
-    };
     let method_call = MethodCall {
         jsonrpc: Some(Version::V2),
         method,
-        params: serialized_params,
+        params: serialize_params(params)?,
         id,
     };
     serde_json::to_vec(&method_call)
 }
 
+/// Creates a JSON-RPC 2.0 notification to the given method with the given parameters.
+fn serialize_notification<P>(
+    method: String,
+    params: P,
+) -> ::std::result::Result<Vec<u8>, serde_json::error::Error>
+where
+    P: serde::Serialize,
+{
+    let notification = Notification {
+        jsonrpc: Some(Version::V2),
+        method,
+        params: serialize_params(params)?,
+    };
+    serde_json::to_vec(&notification)
+}
+
+fn serialize_params<P>(params: P) -> ::std::result::Result<Option<Params>, serde_json::error::Error>
+where
+    P: serde::Serialize,
+{
+    Ok(match serde_json::to_value(params)? {
+        JsonValue::Null => None,
+        JsonValue::Array(vec) => Some(Params::Array(vec)),
+        JsonValue::Object(obj) => Some(Params::Map(obj)),
+        value => Some(Params::Array(vec![value])),
+    })
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -334,8 +571,71 @@ mod tests {
         }
     }
 
+    /// A transport that records the notifications sent and responds to them with an empty body.
+    #[derive(Clone, Default)]
+    struct NotificationTransport {
+        next_id: u64,
+        sent: ::std::sync::Arc<::std::sync::Mutex<Vec<JsonValue>>>,
+    }
+
+    impl Transport for NotificationTransport {
+        type Future = BoxFuture<Vec<u8>, io::Error>;
+        type Error = io::Error;
+
+        fn get_next_id(&mut self) -> u64 {
+            self.next_id += 1;
+            self.next_id
+        }
+
+        fn send(&self, _json_data: Vec<u8>) -> Self::Future {
+            panic!("Notification sent as a request");
+        }
+
+        fn send_notification(&self, json_data: Vec<u8>) -> Self::Future {
+            let json = serde_json::from_slice(&json_data).unwrap();
+            self.sent.lock().unwrap().push(json);
+            Box::new(futures::future::ok(Vec::new()))
+        }
+    }
+
+    /// A transport with string ids that answers with the id of the request, or with
+    /// `response_id` if set.
+    #[derive(Clone, Default)]
+    struct StringIdTransport {
+        response_id: Option<JsonValue>,
+        accept_null_id: bool,
+    }
+
+    impl Transport for StringIdTransport {
+        type Future = BoxFuture<Vec<u8>, io::Error>;
+        type Error = io::Error;
+
+        fn get_next_id(&mut self) -> u64 {
+            1
+        }
+
+        fn next_request_id(&mut self) -> Id {
+            Id::Str("req-1".to_owned())
+        }
+
+        fn send(&self, json_data: Vec<u8>) -> Self::Future {
+            let request: JsonValue = serde_json::from_slice(&json_data).unwrap();
+            let id = self
+                .response_id
+                .clone()
+                .unwrap_or_else(|| request["id"].clone());
+            let json = json!({"jsonrpc": "2.0", "id": id, "result": request["id"]});
+            Box::new(futures::future::ok(serde_json::to_vec(&json).unwrap()))
+        }
+
+        fn accept_null_response_id(&self) -> bool {
+            self.accept_null_id
+        }
+    }
+
     jsonrpc_client!(pub struct TestRpcClient {
         pub fn ping(&mut self, arg0: &str) -> RpcRequest<JsonValue>;
+        pub fn log(&mut self, arg0: &str) -> RpcNotification;
     });
 
     #[test]
@@ -353,13 +653,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn string_ids() {
+        let mut client = TestRpcClient::new(StringIdTransport::default());
+        assert_eq!(client.ping("").call().unwrap(), json!("req-1"));
+        let mut client = TestRpcClient::new(StringIdTransport {
+            response_id: Some(json!("other")),
+            ..StringIdTransport::default()
+        });
+        let error = client.ping("").call().unwrap_err();
+        match *error.kind() {
+            ErrorKind::ResponseIdMismatch(ref expected, ref got, ref body) => {
+                assert_eq!(*expected, Id::Str("req-1".to_owned()));
+                assert_eq!(*got, Id::Str("other".to_owned()));
+                let body: JsonValue = serde_json::from_slice(body).unwrap();
+                assert_eq!(body["result"], json!("req-1"));
+            }
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+        assert_eq!(
+            error.to_string(),
+            r#"Response id "other" not equal to request id "req-1""#
+        );
+    }
+
+    #[test]
+    fn null_response_id() {
+        let mut transport = StringIdTransport {
+            response_id: Some(JsonValue::Null),
+            ..StringIdTransport::default()
+        };
+        let mut client = TestRpcClient::new(transport.clone());
+        match *client.ping("").call().unwrap_err().kind() {
+            ErrorKind::ResponseIdMismatch(_, Id::Null, _) => (),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+
+        transport.accept_null_id = true;
+        let mut client = TestRpcClient::new(transport);
+        assert_eq!(client.ping("").call().unwrap(), json!("req-1"));
+        assert_eq!(client.ping("").raw().call().unwrap().get(), r#""req-1""#);
+    }
+
     #[test]
     fn invalid_request() {
         let mut client = TestRpcClient::new(InvalidRequestTransport);
         let error = client.ping("").call().unwrap_err();
         if let &ErrorKind::JsonRpcError(ref json_error) = error.kind() {
-            use jsonrpc_core::ErrorCode;
-            assert_eq!(ErrorCode::InvalidRequest, json_error.code);
+            assert_eq!(-32600, json_error.code);
+            assert_eq!(
+                Some(StandardErrorCode::InvalidRequest),
+                json_error.standard_code()
+            );
             assert_eq!("This was an invalid request", json_error.message);
             assert_eq!(Some(json!{[1, 2, 3]}), json_error.data);
         } else {
@@ -374,5 +719,23 @@ mod tests {
             &ErrorKind::TransportError => (),
             _ => panic!("Wrong error kind"),
         }
+        match client.log("").call().unwrap_err().kind() {
+            &ErrorKind::TransportError => (),
+            _ => panic!("Wrong error kind"),
+        }
+    }
+
+    #[test]
+    fn notification() {
+        let transport = NotificationTransport::default();
+        let mut client = TestRpcClient::new(transport.clone());
+        client.log("Hello").call().unwrap();
+
+        let sent = transport.sent.lock().unwrap();
+        assert_eq!(
+            *sent,
+            vec![json!({"jsonrpc": "2.0", "method": "log", "params": ["Hello"]})]
+        );
+        assert_eq!(client.transport.next_id, 0);
     }
 }
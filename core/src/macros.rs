@@ -8,14 +8,39 @@
 
 /// The main macro of this crate. Generates JSON-RPC 2.0 client structs with automatic serialization
 /// and deserialization. Method calls get correct types automatically.
+///
+/// Methods returning `RpcRequest<T>` are calls, expecting a response of type `T`. Methods
+/// returning `RpcRawRequest` are calls completing with the raw JSON of the result, see
+/// [`RpcRawRequest`](struct.RpcRawRequest.html). Methods returning `RpcNotification` are
+/// notifications, which have no id and get no response. Methods returning `RpcSubscription<T>`
+/// subscribe to events of type `T` that are polled for, see
+/// [`RpcSubscription`](struct.RpcSubscription.html). They are only available with transports
+/// implementing `Clone`.
+///
+/// Clients created with `with_codec` send the messages encoded with a `Codec`, like the
+/// `MsgPackCodec` of the `msgpack` feature, instead of as JSON. See
+/// [`CodecTransport`](struct.CodecTransport.html).
+///
+/// The arguments are sent as positional parameters, a JSON array. Methods marked with `#[named]`
+/// send them as named parameters instead, a JSON object with the argument names as keys.
+/// Arguments serializing to `null`, like `None` options, are left out of the object, so trailing
+/// optional parameters can be omitted:
+///
+/// ```rust,ignore
+/// jsonrpc_client!(pub struct ChainClient {
+///     /// Sends `{"hash": "..."}` when `verbose` is `None`.
+///     #[named]
+///     pub fn get_block(&mut self, hash: String, verbose: Option<bool>) -> RpcRequest<Block>;
+/// });
+/// ```
 #[macro_export]
 macro_rules! jsonrpc_client {
     (
         $(#[$struct_attr:meta])*
         pub struct $struct_name:ident {$(
-            $(#[$attr:meta])*
+            $(#[$($attr:tt)*])*
             pub fn $method:ident(&mut $selff:ident $(, $arg_name:ident: $arg_ty:ty)*)
-                -> RpcRequest<$return_ty:ty>;
+                -> $kind:ident $(<$return_ty:ty>)*;
         )*}
     ) => (
         $(#[$struct_attr])*
@@ -29,20 +54,112 @@ macro_rules! jsonrpc_client {
                 $struct_name { transport }
             }
 
+            /// Creates a new RPC client backed by the given transport implementation, encoding
+            /// the messages with `codec` instead of as JSON. See `Codec`.
+            #[allow(dead_code)]
+            pub fn with_codec<C: $crate::Codec>(
+                transport: T,
+                codec: C,
+            ) -> $struct_name<$crate::CodecTransport<T, C>> {
+                $struct_name::new($crate::CodecTransport::new(transport, codec))
+            }
+
             $(
-                $(#[$attr])*
-                pub fn $method(&mut $selff $(, $arg_name: $arg_ty)*)
-                    -> $crate::RpcRequest<$return_ty, T::Future>
-                {
-                    let method = String::from(stringify!($method));
-                    let params = expand_params!($($arg_name,)*);
-                    $crate::call_method(&mut $selff.transport, method, params)
-                }
+                jsonrpc_client_method!(
+                    ($(#[$($attr)*])*) $method $selff ($($arg_name: $arg_ty),*)
+                        $kind $(<$return_ty>)*
+                );
             )*
         }
     )
 }
 
+/// Generates one method of a client struct generated by `jsonrpc_client`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! jsonrpc_client_method {
+    // Sorts the `#[named]` attribute out of the attributes of the method.
+    (@attrs $params:ident [$($attrs:tt)*] (#[named] $($rest:tt)*) $($method:tt)*) => (
+        jsonrpc_client_method!(@attrs named [$($attrs)*] ($($rest)*) $($method)*);
+    );
+    (@attrs $params:ident [$($attrs:tt)*] (#[$($attr:tt)*] $($rest:tt)*) $($method:tt)*) => (
+        jsonrpc_client_method!(@attrs $params [$($attrs)* #[$($attr)*]] ($($rest)*) $($method)*);
+    );
+    (@attrs $params:ident [$($attrs:tt)*] () $($method:tt)*) => (
+        jsonrpc_client_method!(@method $params ($($attrs)*) $($method)*);
+    );
+    (
+        @method $params:ident ($(#[$attr:meta])*) $method:ident $selff:ident
+            ($($arg_name:ident: $arg_ty:ty),*) RpcRequest<$return_ty:ty>
+    ) => (
+        $(#[$attr])*
+        pub fn $method(&mut $selff $(, $arg_name: $arg_ty)*)
+            -> $crate::RpcRequest<$return_ty, T::Future>
+        {
+            let method = String::from(stringify!($method));
+            let params = jsonrpc_client_params!($params $($arg_name),*);
+            $crate::call_method(&mut $selff.transport, method, params)
+        }
+    );
+    (
+        @method $params:ident ($(#[$attr:meta])*) $method:ident $selff:ident
+            ($($arg_name:ident: $arg_ty:ty),*) RpcRawRequest
+    ) => (
+        $(#[$attr])*
+        pub fn $method(&mut $selff $(, $arg_name: $arg_ty)*)
+            -> $crate::RpcRawRequest<T::Future>
+        {
+            let method = String::from(stringify!($method));
+            let params = jsonrpc_client_params!($params $($arg_name),*);
+            $crate::call_method_raw(&mut $selff.transport, method, params)
+        }
+    );
+    (
+        @method $params:ident ($(#[$attr:meta])*) $method:ident $selff:ident
+            ($($arg_name:ident: $arg_ty:ty),*) RpcNotification
+    ) => (
+        $(#[$attr])*
+        pub fn $method(&mut $selff $(, $arg_name: $arg_ty)*)
+            -> $crate::RpcNotification<T::Future>
+        {
+            let method = String::from(stringify!($method));
+            let params = jsonrpc_client_params!($params $($arg_name),*);
+            $crate::send_notification(&mut $selff.transport, method, params)
+        }
+    );
+    (
+        @method $params:ident ($(#[$attr:meta])*) $method:ident $selff:ident
+            ($($arg_name:ident: $arg_ty:ty),*) RpcSubscription<$event_ty:ty>
+    ) => (
+        $(#[$attr])*
+        pub fn $method(&mut $selff $(, $arg_name: $arg_ty)*)
+            -> $crate::RpcSubscription<$event_ty, T>
+        where
+            T: Clone,
+        {
+            let name = String::from(stringify!($method));
+            let params = jsonrpc_client_params!($params $($arg_name),*);
+            $crate::subscribe($selff.transport.clone(), name, params)
+        }
+    );
+    (($($attrs:tt)*) $($method:tt)*) => (
+        jsonrpc_client_method!(@attrs positional [] ($($attrs)*) $($method)*);
+    );
+}
+
+/// Expands the arguments of a method into its parameters, positional or named depending on the
+/// `#[named]` attribute of the method.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! jsonrpc_client_params {
+    (positional $($arg_name:ident),*) => (expand_params!($($arg_name,)*));
+    (named $($arg_name:ident),*) => ({
+        let mut params = $crate::NamedParams::new();
+        $(params.insert(stringify!($arg_name), &$arg_name);)*
+        params
+    });
+}
+
 /// Expands a variable list of parameters into its serializable form. Is needed to make the params
 /// of a nullary method equal to `[]` instead of `()` and thus make sure it serializes to `[]`
 /// instead of `null`.
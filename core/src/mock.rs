@@ -0,0 +1,451 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Transport for unit testing code that uses RPC clients, without a server.
+//!
+//! A [`MockTransport`](struct.MockTransport.html) answers requests with canned responses,
+//! registered for a method name or for requests matching a predicate, and records every request
+//! it receives. Responses can also be JSON-RPC errors, transport errors or arrive after a delay.
+//! Requests for which no response is registered fail with a `MockError` naming the method.
+//!
+//! ```rust,ignore
+//! let transport = MockTransport::new();
+//! transport.on_method("fizz_buzz", MockResponse::result("fizz"));
+//!
+//! let mut client = FizzBuzzClient::new(transport.clone());
+//! assert_eq!(client.fizz_buzz(3).wait().unwrap(), "fizz");
+//! assert_eq!(transport.requests()[0].params, Some(json!([3])));
+//! ```
+
+use futures::sync::oneshot;
+use futures::{future, Future};
+use serde;
+use serde_json::{self, Value as JsonValue};
+use std::error;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use {RpcError, Transport};
+
+/// A transport answering requests with canned responses instead of sending them anywhere.
+///
+/// Clones share the registered responses, the recorded requests and the request ids, so a test
+/// can keep a clone to register responses and inspect requests after giving one to a client.
+///
+/// Every request is checked to be a structurally valid JSON-RPC 2.0 request or notification, and
+/// the transport panics if it is not, since that is a bug in the client rather than something a
+/// test would expect.
+#[derive(Clone)]
+pub struct MockTransport {
+    state: Arc<Mutex<State>>,
+    next_id: Arc<AtomicUsize>,
+}
+
+struct State {
+    expectations: Vec<Expectation>,
+    requests: Vec<MockRequest>,
+}
+
+struct Expectation {
+    matcher: Box<Fn(&JsonValue) -> bool + Send>,
+    response: MockResponse,
+}
+
+impl MockTransport {
+    /// Creates a transport without any registered responses.
+    pub fn new() -> Self {
+        MockTransport {
+            state: Arc::new(Mutex::new(State {
+                expectations: Vec::new(),
+                requests: Vec::new(),
+            })),
+            next_id: Arc::new(AtomicUsize::new(1)),
+        }
+    }
+
+    /// Answers all requests for the method `method` with `response`.
+    pub fn on_method(&self, method: &str, response: MockResponse) -> &Self {
+        let method = method.to_owned();
+        self.on_request(move |request| request["method"] == *method, response)
+    }
+
+    /// Answers all requests for which `matcher` returns true with `response`. The matcher is
+    /// given the whole request object, so it can look at the parameters as well as the method.
+    ///
+    /// When several registered responses match a request, the one registered first is used.
+    pub fn on_request<F>(&self, matcher: F, response: MockResponse) -> &Self
+    where
+        F: Fn(&JsonValue) -> bool + Send + 'static,
+    {
+        self.with_state(|state| {
+            state.expectations.push(Expectation {
+                matcher: Box::new(matcher),
+                response,
+            })
+        });
+        self
+    }
+
+    /// Returns all requests and notifications received so far, in the order they were sent. The
+    /// calls of a batch request are recorded one by one.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.with_state(|state| state.requests.clone())
+    }
+
+    /// Returns the requests and notifications received so far for the method `method`.
+    pub fn requests_for(&self, method: &str) -> Vec<MockRequest> {
+        self.with_state(|state| {
+            state
+                .requests
+                .iter()
+                .filter(|request| request.method == method)
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// Validates and records the request or batch in `json_data`, and returns the response to
+    /// it, if any, the longest delay of the matching responses and the first transport error.
+    fn handle(&self, json_data: &[u8]) -> ::std::result::Result<(Vec<u8>, Duration), MockError> {
+        let request: JsonValue = serde_json::from_slice(json_data)
+            .unwrap_or_else(|e| panic!("MockTransport received invalid JSON: {}", e));
+        let (calls, is_batch) = match request {
+            JsonValue::Array(calls) => {
+                assert!(!calls.is_empty(), "MockTransport received an empty batch");
+                (calls, true)
+            }
+            call => (vec![call], false),
+        };
+
+        let mut outputs = Vec::new();
+        let mut delay = Duration::from_secs(0);
+        for call in calls {
+            let recorded = MockRequest::parse(&call);
+            let response = self.with_state(|state| {
+                state.requests.push(recorded.clone());
+                state
+                    .expectations
+                    .iter()
+                    .find(|expectation| (expectation.matcher)(&call))
+                    .map(|expectation| expectation.response.clone())
+            });
+            let response = response.ok_or_else(|| {
+                MockError(format!("Unexpected call to method \"{}\"", recorded.method))
+            })?;
+            delay = delay.max(response.delay);
+            let mut output = match response.reply {
+                Reply::Result(result) => json!({"result": result}),
+                Reply::Error(error) => json!({"error": {
+                    "code": error.code,
+                    "message": error.message,
+                    "data": error.data,
+                }}),
+                Reply::TransportError(message) => return Err(MockError(message)),
+            };
+            if let Some(id) = recorded.id {
+                output["jsonrpc"] = json!("2.0");
+                output["id"] = id;
+                outputs.push(output);
+            }
+        }
+
+        let response = if is_batch {
+            serde_json::to_vec(&outputs).unwrap()
+        } else {
+            outputs
+                .pop()
+                .map(|output| serde_json::to_vec(&output).unwrap())
+                .unwrap_or_else(Vec::new)
+        };
+        Ok((response, delay))
+    }
+
+    /// Runs `f` with the state locked.
+    fn with_state<T, F: FnOnce(&mut State) -> T>(&self, f: F) -> T {
+        // A panicking matcher poisons the lock, but the state is never left inconsistent.
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut state)
+    }
+}
+
+impl Default for MockTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MockTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MockTransport")
+            .field("requests", &self.requests())
+            .finish()
+    }
+}
+
+impl Transport for MockTransport {
+    type Future = Box<Future<Item = Vec<u8>, Error = MockError> + Send>;
+    type Error = MockError;
+
+    fn get_next_id(&mut self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::SeqCst) as u64
+    }
+
+    fn send(&self, json_data: Vec<u8>) -> Self::Future {
+        let (response, delay) = match self.handle(&json_data) {
+            Ok(response) => response,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        if delay == Duration::from_secs(0) {
+            return Box::new(future::ok(response));
+        }
+        let (response_tx, response_rx) = oneshot::channel();
+        thread::spawn(move || {
+            thread::sleep(delay);
+            let _ = response_tx.send(response);
+        });
+        Box::new(response_rx.map_err(|_| MockError("Delayed response was dropped".to_owned())))
+    }
+}
+
+/// A request or notification received by a `MockTransport`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    /// The method called.
+    pub method: String,
+    /// The parameters of the call, or `None` if it had none.
+    pub params: Option<JsonValue>,
+    /// The id of the request, or `None` for notifications.
+    pub id: Option<JsonValue>,
+}
+
+impl MockRequest {
+    /// Parses a single request object, panicking if it is not valid JSON-RPC 2.0.
+    fn parse(call: &JsonValue) -> Self {
+        let object = call
+            .as_object()
+            .unwrap_or_else(|| invalid_request("not an object", call));
+        if object.get("jsonrpc") != Some(&json!("2.0")) {
+            invalid_request("\"jsonrpc\" is not \"2.0\"", call);
+        }
+        let method = match object.get("method").and_then(JsonValue::as_str) {
+            Some(method) => method.to_owned(),
+            None => invalid_request("\"method\" is not a string", call),
+        };
+        let params = match object.get("params") {
+            Some(params @ &JsonValue::Array(_)) | Some(params @ &JsonValue::Object(_)) => {
+                Some(params.clone())
+            }
+            None => None,
+            Some(_) => invalid_request("\"params\" is not an array or an object", call),
+        };
+        let id = match object.get("id") {
+            Some(id @ &JsonValue::Number(_)) | Some(id @ &JsonValue::String(_)) => Some(id.clone()),
+            None => None,
+            Some(_) => invalid_request("\"id\" is not a number or a string", call),
+        };
+        if let Some(key) = object
+            .keys()
+            .find(|key| !["jsonrpc", "method", "params", "id"].contains(&key.as_str()))
+        {
+            invalid_request(&format!("unknown member \"{}\"", key), call);
+        }
+        MockRequest { method, params, id }
+    }
+
+    /// Returns if this was a notification, which gets no response.
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+fn invalid_request(reason: &str, call: &JsonValue) -> ! {
+    panic!(
+        "MockTransport received an invalid JSON-RPC 2.0 request ({}): {}",
+        reason, call
+    )
+}
+
+/// A canned response of a `MockTransport`.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    reply: Reply,
+    delay: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum Reply {
+    Result(JsonValue),
+    Error(RpcError),
+    TransportError(String),
+}
+
+impl MockResponse {
+    /// A successful response with `result` as its result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `result` can not be serialized to JSON.
+    pub fn result<R: serde::Serialize>(result: R) -> Self {
+        let result = serde_json::to_value(result).expect("Unable to serialize mock result");
+        Self::new(Reply::Result(result))
+    }
+
+    /// A JSON-RPC error response with the given error object. The call fails with a
+    /// `JsonRpcError`.
+    pub fn error(error: RpcError) -> Self {
+        Self::new(Reply::Error(error))
+    }
+
+    /// Fails the request in the transport with a `MockError` with the given message, as if the
+    /// server could not be reached. The call fails with a `TransportError`.
+    pub fn transport_error(message: &str) -> Self {
+        Self::new(Reply::TransportError(message.to_owned()))
+    }
+
+    /// Delays the response by `delay`. Transport errors are returned right away.
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    fn new(reply: Reply) -> Self {
+        MockResponse {
+            reply,
+            delay: Duration::from_secs(0),
+        }
+    }
+}
+
+/// The error of requests to a `MockTransport` that have no registered response, or that are
+/// answered with `MockResponse::transport_error`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockError(String);
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl error::Error for MockError {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use {BatchRequest, Error, ErrorKind};
+
+    jsonrpc_client!(pub struct TestClient {
+        pub fn add(&mut self, a: u64, b: u64) -> RpcRequest<u64>;
+        pub fn name(&mut self) -> RpcRequest<String>;
+        pub fn log(&mut self, message: &str) -> RpcNotification;
+    });
+
+    fn transport_error(error: Error) -> String {
+        match *error.kind() {
+            ErrorKind::TransportError => error.iter().nth(1).unwrap().to_string(),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn responses_by_method_and_matcher() {
+        let transport = MockTransport::new();
+        transport
+            .on_request(|r| r["params"][0] == 0, MockResponse::result(0))
+            .on_method("add", MockResponse::result(5))
+            .on_method("name", MockResponse::result("mock"));
+        let mut client = TestClient::new(transport.clone());
+
+        assert_eq!(client.add(0, 1).wait().unwrap(), 0);
+        assert_eq!(client.add(2, 3).wait().unwrap(), 5);
+        assert_eq!(client.name().wait().unwrap(), "mock");
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].method, "add");
+        assert_eq!(requests[1].params, Some(json!([2, 3])));
+        assert_ne!(requests[0].id, requests[1].id);
+        assert_eq!(transport.requests_for("name").len(), 1);
+    }
+
+    #[test]
+    fn unexpected_method() {
+        let transport = MockTransport::new();
+        let mut client = TestClient::new(transport.clone());
+        let error = client.name().wait().unwrap_err();
+        assert_eq!(transport_error(error), "Unexpected call to method \"name\"");
+        assert_eq!(transport.requests_for("name").len(), 1);
+    }
+
+    #[test]
+    fn errors() {
+        let transport = MockTransport::new();
+        let rpc_error = RpcError {
+            code: -32602,
+            message: "Invalid params".to_owned(),
+            data: Some(json!("b")),
+        };
+        transport
+            .on_method("add", MockResponse::error(rpc_error.clone()))
+            .on_method("name", MockResponse::transport_error("Connection refused"));
+        let mut client = TestClient::new(transport);
+
+        match *client.add(1, 2).wait().unwrap_err().kind() {
+            ErrorKind::JsonRpcError(ref error) => assert_eq!(*error, rpc_error),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+        let error = client.name().wait().unwrap_err();
+        assert_eq!(transport_error(error), "Connection refused");
+    }
+
+    #[test]
+    fn delayed_response() {
+        let transport = MockTransport::new();
+        let delay = Duration::from_millis(50);
+        transport.on_method("name", MockResponse::result("slow").delayed(delay));
+        let mut client = TestClient::new(transport);
+
+        let start = Instant::now();
+        assert_eq!(client.name().wait().unwrap(), "slow");
+        assert!(start.elapsed() >= delay);
+    }
+
+    #[test]
+    fn notifications_and_batches() {
+        let transport = MockTransport::new();
+        transport
+            .on_method("add", MockResponse::result(3))
+            .on_method("log", MockResponse::result(()));
+        let batch = BatchRequest::new(transport.clone());
+        let mut client = TestClient::new(batch.transport());
+        let sum = client.add(1, 2);
+        let log = client.log("added");
+
+        let (_, (sum, _)) = batch.send().join(sum.join(log)).wait().unwrap();
+        assert_eq!(sum, 3);
+        let requests = transport.requests();
+        assert!(!requests[0].is_notification());
+        assert!(requests[1].is_notification());
+        assert_eq!(requests[1].params, Some(json!(["added"])));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid JSON-RPC 2.0 request (\"jsonrpc\" is not \"2.0\")")]
+    fn invalid_request() {
+        let transport = MockTransport::new();
+        transport.on_method("add", MockResponse::result(3));
+        let request = json!({"jsonrpc": "1.0", "id": 1, "method": "add", "params": [1, 2]});
+        let _ = transport.send(serde_json::to_vec(&request).unwrap());
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use serde::ser::{self, Serialize, SerializeMap, Serializer};
+use serde_json::{self, Value as JsonValue};
+
+/// The parameters of a method marked with `#[named]` in the `jsonrpc_client` macro, serialized
+/// as a JSON object with the argument names as keys. Arguments serializing to `null`, like `None`
+/// options, are left out of the object.
+///
+/// # Not intended for direct use
+/// This is being created by the client structs generated by the `jsonrpc_client` macro. It is
+/// not intended to be used directly, only the generated structs should use it.
+#[derive(Debug, Default)]
+pub struct NamedParams {
+    params: Vec<(&'static str, JsonValue)>,
+    /// The first error from serializing an argument, returned when serializing the parameters.
+    error: Option<String>,
+}
+
+impl NamedParams {
+    /// Creates an empty set of named parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the argument `name` with the given value, unless it serializes to `null`.
+    pub fn insert<T: Serialize>(&mut self, name: &'static str, value: &T) {
+        match serde_json::to_value(value) {
+            Ok(JsonValue::Null) => (),
+            Ok(value) => self.params.push((name, value)),
+            Err(error) => {
+                if self.error.is_none() {
+                    self.error = Some(format!("Unable to serialize \"{}\": {}", name, error));
+                }
+            }
+        }
+    }
+}
+
+impl Serialize for NamedParams {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let Some(ref error) = self.error {
+            return Err(ser::Error::custom(error));
+        }
+        let mut map = serializer.serialize_map(Some(self.params.len()))?;
+        for &(name, ref value) in &self.params {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Future;
+    use mock::{MockResponse, MockTransport};
+    use std::collections::HashMap;
+
+    jsonrpc_client!(pub struct NamedClient {
+        #[named]
+        pub fn get_block(&mut self, hash: String, verbose: Option<bool>) -> RpcRequest<u64>;
+        /// Positional parameters are still the default.
+        pub fn get_height(&mut self, hash: String, verbose: Option<bool>) -> RpcRequest<u64>;
+        #[named]
+        pub fn log(&mut self, message: &str) -> RpcNotification;
+    });
+
+    fn transport() -> MockTransport {
+        let transport = MockTransport::new();
+        transport
+            .on_method("get_block", MockResponse::result(1))
+            .on_method("get_height", MockResponse::result(2))
+            .on_method("log", MockResponse::result(()));
+        transport
+    }
+
+    #[test]
+    fn named_params_skip_none() {
+        let transport = transport();
+        let mut client = NamedClient::new(transport.clone());
+        client.get_block("abc".to_owned(), None).wait().unwrap();
+        client
+            .get_block("def".to_owned(), Some(true))
+            .wait()
+            .unwrap();
+
+        let requests = transport.requests_for("get_block");
+        assert_eq!(requests[0].params, Some(json!({"hash": "abc"})));
+        assert_eq!(
+            requests[1].params,
+            Some(json!({"hash": "def", "verbose": true}))
+        );
+    }
+
+    #[test]
+    fn positional_params_by_default() {
+        let transport = transport();
+        let mut client = NamedClient::new(transport.clone());
+        client.get_height("abc".to_owned(), None).wait().unwrap();
+        client.log("hello").wait().unwrap();
+
+        let requests = transport.requests_for("get_height");
+        assert_eq!(requests[0].params, Some(json!(["abc", null])));
+        let requests = transport.requests_for("log");
+        assert_eq!(requests[0].params, Some(json!({"message": "hello"})));
+    }
+
+    #[test]
+    fn unserializable_argument_fails() {
+        let mut map = HashMap::new();
+        map.insert(vec![1], 2);
+        let mut params = NamedParams::new();
+        params.insert("map", &map);
+        assert!(serde_json::to_value(&params).is_err());
+    }
+}
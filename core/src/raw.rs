@@ -0,0 +1,144 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use futures::{Async, Future, Poll};
+use response::{self, ExpectedId};
+use serde;
+use serde_json::value::RawValue;
+use serde_json::Value as JsonValue;
+use {call_method, Error, ErrorKind, Result, ResultExt, Transport};
+
+/// A lazy RPC call `Future` like `RpcRequest`, that completes with the "result" field of the
+/// response as the raw JSON it was sent as, instead of deserializing it. For passing large
+/// results on without decoding and encoding them again, or deserializing them later into types
+/// borrowing from the JSON.
+///
+/// The response is still checked like for an `RpcRequest`: it must be a JSON-RPC 2.0 response
+/// with the id of the request, and an error response fails with a `JsonRpcError`.
+///
+/// Returned by methods declared to return `RpcRawRequest` in the `jsonrpc_client` macro, and by
+/// [`RpcRequest::raw`](struct.RpcRequest.html#method.raw).
+///
+/// ```rust,ignore
+/// jsonrpc_client!(pub struct ChainClient {
+///     pub fn get_block_data(&mut self, hash: String) -> RpcRawRequest;
+/// });
+///
+/// let data: Box<RawValue> = client.get_block_data(hash).call()?;
+/// ```
+pub struct RpcRawRequest<F>(::std::result::Result<(F, ExpectedId), Option<Error>>);
+
+impl<F> RpcRawRequest<F> {
+    pub(crate) fn new(request: ::std::result::Result<(F, ExpectedId), Option<Error>>) -> Self {
+        RpcRawRequest(request)
+    }
+}
+
+impl<E, F> RpcRawRequest<F>
+where
+    E: ::std::error::Error + Send + 'static,
+    F: Future<Item = Vec<u8>, Error = E> + Send + 'static,
+{
+    /// Consume this RPC request and run it synchronously. This blocks until the RPC call is done,
+    /// then the raw result of the call is returned.
+    pub fn call(self) -> Result<Box<RawValue>> {
+        self.wait()
+    }
+}
+
+impl<E, F> Future for RpcRawRequest<F>
+where
+    E: ::std::error::Error + Send + 'static,
+    F: Future<Item = Vec<u8>, Error = E> + Send + 'static,
+{
+    type Item = Box<RawValue>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.0 {
+            Ok((ref mut transport_future, ref id)) => {
+                let response_raw = try_ready!(transport_future
+                    .poll()
+                    .chain_err(|| ErrorKind::TransportError));
+                trace!(
+                    "Extracting raw result from {} byte response to request with id {:?}",
+                    response_raw.len(),
+                    id.id
+                );
+                response::parse_raw(&response_raw, id).map(Async::Ready)
+            }
+            Err(ref mut error_option) => Err(error_option
+                .take()
+                .expect("Cannot call RpcRawRequest poll twice when in error state")),
+        }
+    }
+}
+
+/// Prepares a lazy `RpcRawRequest` with a given transport, method and parameters. Works like
+/// `call_method`, but the result is not deserialized.
+///
+/// # Not intended for direct use
+/// This is being called from the client structs generated by the `jsonrpc_client` macro. This
+/// function is not intended to be used directly, only the generated structs should call this.
+pub fn call_method_raw<T, P>(
+    transport: &mut T,
+    method: String,
+    params: P,
+) -> RpcRawRequest<T::Future>
+where
+    T: Transport,
+    P: serde::Serialize,
+{
+    call_method::<T, P, JsonValue>(transport, method, params).raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{MockResponse, MockTransport};
+    use RpcError;
+
+    jsonrpc_client!(pub struct BlobClient {
+        pub fn get_blob(&mut self, name: &str) -> RpcRawRequest;
+        pub fn get_size(&mut self, name: &str) -> RpcRequest<u64>;
+    });
+
+    #[test]
+    fn raw_results() {
+        let transport = MockTransport::new();
+        transport
+            .on_method("get_blob", MockResponse::result(json!({"data": [1, 2, 3]})))
+            .on_method("get_size", MockResponse::result(3));
+        let mut client = BlobClient::new(transport.clone());
+
+        let blob = client.get_blob("a").call().unwrap();
+        assert_eq!(blob.get(), r#"{"data":[1,2,3]}"#);
+        let size = client.get_size("a").raw().call().unwrap();
+        assert_eq!(size.get(), "3");
+        assert_eq!(
+            transport.requests_for("get_blob")[0].params,
+            Some(json!(["a"]))
+        );
+    }
+
+    #[test]
+    fn raw_error_response() {
+        let transport = MockTransport::new();
+        let error = RpcError {
+            code: -32000,
+            message: "No such blob".to_owned(),
+            data: None,
+        };
+        transport.on_method("get_blob", MockResponse::error(error));
+        let mut client = BlobClient::new(transport);
+        match *client.get_blob("a").call().unwrap_err().kind() {
+            ErrorKind::JsonRpcError(ref error) => assert_eq!(error.code, -32000),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+}
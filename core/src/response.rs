@@ -8,15 +8,49 @@
 
 use jsonrpc_core::types::{Id, Output, Version};
 use serde;
-use serde_json;
-use {ErrorKind, Result, ResultExt};
+use serde_json::value::RawValue;
+use serde_json::{self, Value as JsonValue};
+use std::collections::HashMap;
+use {ErrorKind, Result, ResultExt, Transport};
+
+/// The id a response must have, the id of the request, and if `null` is accepted instead.
+#[derive(Debug, Clone)]
+pub struct ExpectedId {
+    pub id: Id,
+    pub accept_null: bool,
+}
+
+impl ExpectedId {
+    /// The id of a request sent over `transport`, accepting `null` if the transport does.
+    pub fn new<T: Transport>(id: Id, transport: &T) -> Self {
+        ExpectedId {
+            id,
+            accept_null: transport.accept_null_response_id(),
+        }
+    }
+}
+
+impl From<Id> for ExpectedId {
+    fn from(id: Id) -> Self {
+        ExpectedId {
+            id,
+            accept_null: false,
+        }
+    }
+}
 
 /// Parses a binary response into json, extracts the "result" field and tries to deserialize that
-/// to the desired type.
-pub fn parse<R>(response_raw: &[u8], expected_id: &Id) -> Result<R>
+/// to the desired type. An empty response, as some servers send with 204 No Content, counts as a
+/// `null` result. That is fine for methods returning `()` or an `Option`, but fails for others.
+pub fn parse<R>(response_raw: &[u8], expected_id: &ExpectedId) -> Result<R>
 where
     R: serde::de::DeserializeOwned,
 {
+    if response_raw.is_empty() {
+        trace!("Received empty response, treating it as a null result");
+        return serde_json::from_value(JsonValue::Null)
+            .chain_err(|| ErrorKind::ResponseError("Server returned no response body"));
+    }
     let response: Output = serde_json::from_slice(response_raw)
         .chain_err(|| ErrorKind::ResponseError("Not valid json"))?;
     #[cfg(not(feature = "disable_version_check"))]
@@ -24,16 +58,202 @@ where
         response.version() == Some(Version::V2),
         ErrorKind::ResponseError("Not JSON-RPC 2.0 compatible")
     );
-    ensure!(
-        response.id() == expected_id,
-        ErrorKind::ResponseError("Response id not equal to request id")
-    );
+    check_id(response.id(), expected_id, response_raw)?;
     match response {
         Output::Success(success) => {
             trace!("Received json result: {}", success.result);
             serde_json::from_value(success.result)
                 .chain_err(|| ErrorKind::ResponseError("Not valid for target type"))
         }
-        Output::Failure(failure) => bail!(ErrorKind::JsonRpcError(failure.error)),
+        Output::Failure(failure) => bail!(ErrorKind::JsonRpcError(failure.error.into())),
+    }
+}
+
+/// Like `parse`, but returns the "result" field as the raw JSON it was in the response, without
+/// deserializing it. The rest of the response is still checked like `parse` does.
+pub fn parse_raw(response_raw: &[u8], expected_id: &ExpectedId) -> Result<Box<RawValue>> {
+    if response_raw.is_empty() {
+        trace!("Received empty response, treating it as a null result");
+        return RawValue::from_string("null".to_owned())
+            .chain_err(|| ErrorKind::ResponseError("Server returned no response body"));
+    }
+    // Only the top level fields are split out, the result is copied but never parsed into values.
+    let mut response: HashMap<String, Box<RawValue>> = serde_json::from_slice(response_raw)
+        .chain_err(|| ErrorKind::ResponseError("Not valid json"))?;
+    #[cfg(not(feature = "disable_version_check"))]
+    ensure!(
+        parse_field::<Version>(&response, "jsonrpc") == Some(Version::V2),
+        ErrorKind::ResponseError("Not JSON-RPC 2.0 compatible")
+    );
+    let id = parse_field::<Id>(&response, "id")
+        .ok_or(ErrorKind::ResponseError("Response has no valid id"))?;
+    check_id(&id, expected_id, response_raw)?;
+    match (response.remove("result"), response.remove("error")) {
+        (Some(result), None) => {
+            trace!("Received {} byte raw json result", result.get().len());
+            Ok(result)
+        }
+        (None, Some(error)) => {
+            let error: ::jsonrpc_core::Error = serde_json::from_str(error.get())
+                .chain_err(|| ErrorKind::ResponseError("Not valid json"))?;
+            bail!(ErrorKind::JsonRpcError(error.into()))
+        }
+        _ => bail!(ErrorKind::ResponseError("Not valid json")),
+    }
+}
+
+/// Fails with a `ResponseIdMismatch` error unless `id` is the expected id, or `null` if that is
+/// accepted.
+fn check_id(id: &Id, expected_id: &ExpectedId, response_raw: &[u8]) -> Result<()> {
+    if *id == expected_id.id {
+        return Ok(());
+    }
+    if *id == Id::Null && expected_id.accept_null {
+        warn!(
+            "Accepting response with null id to request with id {}",
+            id_string(&expected_id.id)
+        );
+        return Ok(());
+    }
+    bail!(ErrorKind::ResponseIdMismatch(
+        expected_id.id.clone(),
+        id.clone(),
+        response_raw.to_vec()
+    ))
+}
+
+/// Formats an id like it is written in JSON.
+pub fn id_string(id: &Id) -> String {
+    serde_json::to_string(id).unwrap_or_else(|_| format!("{:?}", id))
+}
+
+/// Deserializes the top level field `name` of a response split up by `parse_raw`.
+fn parse_field<T>(response: &HashMap<String, Box<RawValue>>, name: &str) -> Option<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    serde_json::from_str(response.get(name)?.get()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StandardErrorCode;
+
+    fn parse_error(error: JsonValue) -> ::Error {
+        let response = json!({"jsonrpc": "2.0", "id": 1, "error": error});
+        let response = serde_json::to_vec(&response).unwrap();
+        parse::<JsonValue>(&response, &Id::Num(1).into()).unwrap_err()
+    }
+
+    #[test]
+    fn error_with_data() {
+        let error = parse_error(json!({
+            "code": -32602,
+            "message": "Invalid params",
+            "data": {"missing": ["name"]},
+        }));
+        match *error.kind() {
+            ErrorKind::JsonRpcError(ref error) => {
+                assert_eq!(error.code, -32602);
+                assert_eq!(
+                    error.standard_code(),
+                    Some(StandardErrorCode::InvalidParams)
+                );
+                assert_eq!(error.message, "Invalid params");
+                let data: JsonValue = error.data_as().unwrap().unwrap();
+                assert_eq!(data, json!({"missing": ["name"]}));
+            }
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn error_without_data() {
+        let error = parse_error(json!({"code": 42, "message": "Out of fizz"}));
+        match *error.kind() {
+            ErrorKind::JsonRpcError(ref error) => {
+                assert_eq!(error.code, 42);
+                assert_eq!(error.standard_code(), None);
+                assert_eq!(error.data, None);
+                assert!(error.data_as::<JsonValue>().is_none());
+            }
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn empty_response() {
+        parse::<()>(b"", &Id::Num(1).into()).unwrap();
+        assert_eq!(parse::<Option<u64>>(b"", &Id::Num(1).into()).unwrap(), None);
+        match *parse::<u64>(b"", &Id::Num(1).into()).unwrap_err().kind() {
+            ErrorKind::ResponseError(message) => {
+                assert_eq!(message, "Server returned no response body")
+            }
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+        match *parse::<()>(b" ", &Id::Num(1).into()).unwrap_err().kind() {
+            ErrorKind::ResponseError(message) => assert_eq!(message, "Not valid json"),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn raw_result_is_kept_as_sent() {
+        let response = br#"{"jsonrpc": "2.0", "result": {"b": [1,  2], "a": "\u0041"}, "id": 1}"#;
+        let result = parse_raw(response, &Id::Num(1).into()).unwrap();
+        assert_eq!(result.get(), r#"{"b": [1,  2], "a": "\u0041"}"#);
+        assert_eq!(parse_raw(b"", &Id::Num(1).into()).unwrap().get(), "null");
+    }
+
+    #[test]
+    fn raw_result_checks_response() {
+        let response = br#"{"jsonrpc": "2.0", "result": 1, "id": 2}"#;
+        match *parse_raw(response, &Id::Num(1).into()).unwrap_err().kind() {
+            ErrorKind::ResponseIdMismatch(ref expected, ref got, ref body) => {
+                assert_eq!((expected, got), (&Id::Num(1), &Id::Num(2)));
+                assert_eq!(&body[..], &response[..]);
+            }
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+        let response = br#"{"jsonrpc": "2.0", "error": {"code": 42, "message": "No"}, "id": 1}"#;
+        match *parse_raw(response, &Id::Num(1).into()).unwrap_err().kind() {
+            ErrorKind::JsonRpcError(ref error) => assert_eq!(error.code, 42),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+        let response = br#"{"jsonrpc": "2.0", "id": 1}"#;
+        assert!(parse_raw(response, &Id::Num(1).into()).is_err());
+    }
+
+    #[test]
+    fn null_id() {
+        let response = br#"{"jsonrpc": "2.0", "result": 7, "id": null}"#;
+        match *parse::<u64>(response, &Id::Num(1).into())
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::ResponseIdMismatch(_, Id::Null, _) => (),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+        let accept_null = ExpectedId {
+            id: Id::Num(1),
+            accept_null: true,
+        };
+        assert_eq!(parse::<u64>(response, &accept_null).unwrap(), 7);
+        assert_eq!(parse_raw(response, &accept_null).unwrap().get(), "7");
+
+        let response = br#"{"jsonrpc": "2.0", "result": 7, "id": 2}"#;
+        assert!(parse::<u64>(response, &accept_null).is_err());
+    }
+
+    #[test]
+    fn non_integer_error_code() {
+        for code in &[json!(1.5), json!("-32601"), json!(null)] {
+            let error = parse_error(json!({"code": code, "message": "Broken"}));
+            match *error.kind() {
+                ErrorKind::ResponseError(_) => (),
+                ref kind => panic!("unexpected error for code {}: {}", code, kind),
+            }
+        }
     }
 }
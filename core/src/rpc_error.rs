@@ -0,0 +1,141 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use jsonrpc_core;
+use serde;
+use serde_json::Value as JsonValue;
+use std::fmt;
+use {ErrorKind, Result, ResultExt};
+
+/// The error object of a JSON-RPC 2.0 error response, returned in a `JsonRpcError`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    /// The error code sent by the server.
+    pub code: i64,
+    /// The short description of the error sent by the server.
+    pub message: String,
+    /// Additional information about the error, if the server sent any.
+    pub data: Option<JsonValue>,
+}
+
+impl RpcError {
+    /// Returns the well-known error code defined by the JSON-RPC 2.0 specification that `code`
+    /// is, or `None` if it is a code defined by the application.
+    pub fn standard_code(&self) -> Option<StandardErrorCode> {
+        StandardErrorCode::from_code(self.code)
+    }
+
+    /// Deserializes the `data` of the error into `T`. Returns `None` if the error has no data, and
+    /// a `ResponseError` if the data is not valid for `T`.
+    pub fn data_as<T>(&self) -> Option<Result<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.data.as_ref().map(|data| {
+            T::deserialize(data).chain_err(|| ErrorKind::ResponseError("Not valid for target type"))
+        })
+    }
+}
+
+impl From<jsonrpc_core::Error> for RpcError {
+    fn from(error: jsonrpc_core::Error) -> Self {
+        RpcError {
+            code: error.code.code(),
+            message: error.message,
+            data: error.data,
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+/// The error codes reserved by the JSON-RPC 2.0 specification, in the range -32768 to -32000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StandardErrorCode {
+    /// -32700, the server could not parse the request as JSON.
+    ParseError,
+    /// -32600, the request is not a valid request object.
+    InvalidRequest,
+    /// -32601, the method does not exist or is not available.
+    MethodNotFound,
+    /// -32602, the parameters are not valid for the method.
+    InvalidParams,
+    /// -32603, an internal error in the server.
+    InternalError,
+    /// -32099 to -32000, reserved for errors defined by the server implementation.
+    ServerError(i64),
+}
+
+impl StandardErrorCode {
+    /// Returns the well-known error code `code` is, or `None` if it is outside of the reserved
+    /// range or not defined by the specification.
+    pub fn from_code(code: i64) -> Option<Self> {
+        match code {
+            -32700 => Some(StandardErrorCode::ParseError),
+            -32600 => Some(StandardErrorCode::InvalidRequest),
+            -32601 => Some(StandardErrorCode::MethodNotFound),
+            -32602 => Some(StandardErrorCode::InvalidParams),
+            -32603 => Some(StandardErrorCode::InternalError),
+            // Division rounds toward zero, so these are exactly the codes -32099 to -32000.
+            code if code / 100 == -320 => Some(StandardErrorCode::ServerError(code)),
+            _ => None,
+        }
+    }
+
+    /// Returns the numeric value of the error code.
+    pub fn code(&self) -> i64 {
+        match *self {
+            StandardErrorCode::ParseError => -32700,
+            StandardErrorCode::InvalidRequest => -32600,
+            StandardErrorCode::MethodNotFound => -32601,
+            StandardErrorCode::InvalidParams => -32602,
+            StandardErrorCode::InternalError => -32603,
+            StandardErrorCode::ServerError(code) => code,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn standard_codes() {
+        for code in &[-32700, -32600, -32601, -32602, -32603, -32000, -32099] {
+            assert_eq!(StandardErrorCode::from_code(*code).unwrap().code(), *code);
+        }
+        assert_eq!(
+            StandardErrorCode::from_code(-32601),
+            Some(StandardErrorCode::MethodNotFound)
+        );
+        assert_eq!(StandardErrorCode::from_code(-32100), None);
+        assert_eq!(StandardErrorCode::from_code(-31999), None);
+        assert_eq!(StandardErrorCode::from_code(1), None);
+    }
+
+    #[test]
+    fn data_as() {
+        let mut error = RpcError {
+            code: 10,
+            message: "Validation failed".to_owned(),
+            data: Some(json!([{"field": "name", "reason": "empty"}])),
+        };
+        let violations: Vec<HashMap<String, String>> = error.data_as().unwrap().unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0]["field"], "name");
+        assert_eq!(violations[0]["reason"], "empty");
+        assert!(error.data_as::<String>().unwrap().is_err());
+        error.data = None;
+        assert!(error.data_as::<JsonValue>().is_none());
+    }
+}
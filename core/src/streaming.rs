@@ -0,0 +1,133 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use futures::{stream, Stream};
+use jsonrpc_core::types::Id;
+use response::ExpectedId;
+use serde;
+use serde_json;
+use std::io;
+use {ErrorKind, InnerRpcRequest, ResultExt, RpcRequest, Transport};
+
+/// A request body produced in chunks, for sending bodies too large to hold in memory.
+pub type BodyStream = Box<Stream<Item = Vec<u8>, Error = io::Error> + Send>;
+
+/// A transport that can send request bodies as they are produced, instead of only complete
+/// bodies.
+pub trait StreamingTransport: Transport {
+    /// Sends the request body produced by `body`, reading it only as fast as the transport can
+    /// send it. Works like `Transport::send` otherwise. An error from the stream fails the
+    /// request.
+    fn send_stream(&self, body: BodyStream) -> Self::Future;
+}
+
+/// Calls the method `method` with parameters produced by the stream `params`, and returns the
+/// result deserialized into `R`. Only the parameters are streamed, the request around them and
+/// the response work like for other calls. Together the chunks of `params` must be a JSON array
+/// or object.
+///
+/// ```rust,ignore
+/// let chunks = vec![b"[[1,2],".to_vec(), b"[3,4]]".to_vec()];
+/// let params = Box::new(stream::iter_ok(chunks));
+/// let imported: u64 = call_method_streaming(&mut transport, "import", params).wait()?;
+/// ```
+pub fn call_method_streaming<T, R>(
+    transport: &mut T,
+    method: &str,
+    params: BodyStream,
+) -> RpcRequest<R, T::Future>
+where
+    T: StreamingTransport,
+    R: serde::de::DeserializeOwned + Send + 'static,
+{
+    let id = transport.next_request_id();
+    trace!("Streaming call to method \"{}\" with id {:?}", method, id);
+    let prefix = serialize_prefix(&id, method).chain_err(|| ErrorKind::SerializeError);
+    match prefix {
+        Err(e) => RpcRequest(Err(Some(e))),
+        Ok(prefix) => {
+            let body = stream::once(Ok(prefix))
+                .chain(params)
+                .chain(stream::once(Ok(b"}".to_vec())));
+            let transport_future = transport.send_stream(Box::new(body));
+            let id = ExpectedId::new(id, transport);
+            RpcRequest(Ok(InnerRpcRequest::new(transport_future, id)))
+        }
+    }
+}
+
+/// Serializes the start of a request object, up to where its parameters begin.
+fn serialize_prefix(
+    id: &Id,
+    method: &str,
+) -> ::std::result::Result<Vec<u8>, serde_json::error::Error> {
+    let mut prefix = b"{\"jsonrpc\":\"2.0\",\"id\":".to_vec();
+    serde_json::to_writer(&mut prefix, id)?;
+    prefix.extend_from_slice(b",\"method\":");
+    serde_json::to_writer(&mut prefix, method)?;
+    prefix.extend_from_slice(b",\"params\":");
+    Ok(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, Future};
+    use serde_json::Value as JsonValue;
+
+    /// Collects the streamed body and answers with it as the result.
+    struct CollectingTransport;
+
+    impl Transport for CollectingTransport {
+        type Future = Box<Future<Item = Vec<u8>, Error = io::Error> + Send>;
+        type Error = io::Error;
+
+        fn get_next_id(&mut self) -> u64 {
+            7
+        }
+
+        fn send(&self, _: Vec<u8>) -> Self::Future {
+            unreachable!("only streams are sent");
+        }
+    }
+
+    impl StreamingTransport for CollectingTransport {
+        fn send_stream(&self, body: BodyStream) -> Self::Future {
+            Box::new(body.concat2().and_then(|body| {
+                let request: JsonValue = serde_json::from_slice(&body).unwrap();
+                let response = json!({"jsonrpc": "2.0", "id": 7, "result": request});
+                future::ok(serde_json::to_vec(&response).unwrap())
+            }))
+        }
+    }
+
+    #[test]
+    fn streams_params() {
+        let chunks = vec![b"[1,".to_vec(), b"2,".to_vec(), b"3]".to_vec()];
+        let params = Box::new(stream::iter_ok(chunks));
+        let request: JsonValue = call_method_streaming(&mut CollectingTransport, "add", params)
+            .wait()
+            .unwrap();
+        assert_eq!(
+            request,
+            json!({"jsonrpc": "2.0", "id": 7, "method": "add", "params": [1, 2, 3]})
+        );
+    }
+
+    #[test]
+    fn stream_errors_fail_call() {
+        let error = io::Error::new(io::ErrorKind::UnexpectedEof, "gone");
+        let params = Box::new(stream::once(Err(error)));
+        let result: ::Result<JsonValue> =
+            call_method_streaming(&mut CollectingTransport, "add", params).wait();
+        match *result.unwrap_err().kind() {
+            ErrorKind::TransportError => (),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+}
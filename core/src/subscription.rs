@@ -0,0 +1,435 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use futures::future::Executor;
+use futures::{Async, Future, Poll, Stream};
+use serde;
+use serde_json::Value as JsonValue;
+use std::collections::VecDeque;
+use {call_method, Error, RpcRequest, Transport};
+
+/// A future completing when the next poll is due.
+type Delay = Box<Future<Item = (), Error = ()> + Send>;
+
+/// The future unsubscribing a dropped subscription, run on the executor set with
+/// `RpcSubscription::executor`.
+pub type UnsubscribeFuture = Box<Future<Item = (), Error = ()> + Send>;
+
+/// A `Stream` of the events of a subscription, polled for over a transport without push support,
+/// like HTTP.
+///
+/// Created by methods returning `RpcSubscription<T>` in the `jsonrpc_client` macro. A method
+/// named `block_events` calls `subscribe_block_events` with its arguments, which must return a
+/// subscription id. The stream then calls `poll` with the id over and over. Every poll returns a
+/// JSON array of the events queued since the last poll, which the stream yields one by one. The
+/// server can hold a poll until there are events, to keep the latency down. When the stream is
+/// dropped, `unsubscribe_block_events` is called with the id on the executor set with
+/// `executor`.
+///
+/// Every call is a normal request on the transport, so the timeout settings of the transport
+/// apply to each poll. A server holding polls must answer them within that timeout.
+///
+/// The stream starts no threads or timers of its own. Waiting between polls takes a delay future
+/// from `poll_delay`, like a timeout of the event loop of the transport.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// jsonrpc_client!(pub struct ChainClient {
+///     pub fn block_events(&mut self) -> RpcSubscription<Block>;
+/// });
+///
+/// let mut client = ChainClient::new(transport_handle);
+/// let handle = core.handle();
+/// let blocks = client
+///     .block_events()
+///     .poll_delay(move || Timeout::new(Duration::from_secs(1), &handle).unwrap())
+///     .executor(core.remote())
+///     .continue_on_error(true);
+/// for block in blocks.wait() {
+///     println!("{:?}", block);
+/// }
+/// ```
+pub struct RpcSubscription<T, Tr: Transport> {
+    transport: Tr,
+    name: String,
+    /// The subscription id, or `None` before subscribing succeeded and after unsubscribing.
+    id: Option<JsonValue>,
+    state: State<T, Tr::Future>,
+    events: VecDeque<T>,
+    poll_method: String,
+    poll_delay: Option<Box<FnMut() -> Delay + Send>>,
+    executor: Option<Box<Executor<UnsubscribeFuture> + Send>>,
+    continue_on_error: bool,
+}
+
+enum State<T, F> {
+    Subscribing(RpcRequest<JsonValue, F>),
+    Polling(RpcRequest<Vec<T>, F>),
+    /// A poll returned, the next one starts when its events are consumed.
+    Polled,
+    Waiting(Delay),
+    Done,
+}
+
+/// What to do after polling the current state.
+enum Next<T> {
+    Subscribed(JsonValue),
+    Events(Vec<T>),
+    Wait,
+    Poll,
+    Fail(Error),
+    End(Error),
+}
+
+/// Subscribes to `name` with the given parameters and returns the stream of its events. See
+/// [`RpcSubscription`](struct.RpcSubscription.html).
+///
+/// # Not intended for direct use
+/// This is being called from the client structs generated by the `jsonrpc_client` macro. This
+/// function is not intended to be used directly, only the generated structs should call this.
+pub fn subscribe<T, Tr, P>(mut transport: Tr, name: String, params: P) -> RpcSubscription<T, Tr>
+where
+    T: serde::de::DeserializeOwned + Send + 'static,
+    Tr: Transport,
+    P: serde::Serialize,
+{
+    let request = call_method(&mut transport, format!("subscribe_{}", name), params);
+    RpcSubscription {
+        transport,
+        name,
+        id: None,
+        state: State::Subscribing(request),
+        events: VecDeque::new(),
+        poll_method: "poll".to_owned(),
+        poll_delay: None,
+        executor: None,
+        continue_on_error: false,
+    }
+}
+
+impl<T, Tr> RpcSubscription<T, Tr>
+where
+    T: serde::de::DeserializeOwned + Send + 'static,
+    Tr: Transport,
+{
+    /// Sets the function creating the delay to wait for between polls, like a `Timeout` on the
+    /// event loop of the transport. The next poll starts when the events of the last one are
+    /// consumed and the delay created then has completed. A delay failing ends the wait as well.
+    /// The default is to poll again right away, which suits servers holding polls until there
+    /// are events.
+    pub fn poll_delay<F, D>(mut self, mut poll_delay: F) -> Self
+    where
+        F: FnMut() -> D + Send + 'static,
+        D: Future<Item = ()> + Send + 'static,
+    {
+        self.poll_delay = Some(Box::new(move || -> Delay {
+            Box::new(poll_delay().then(|_| Ok(())))
+        }));
+        self
+    }
+
+    /// Sets the executor the subscription is removed on when the stream is dropped, like the
+    /// `Remote` of the event loop of the transport. Without one, dropping the stream leaves the
+    /// subscription on the server. Use `unsubscribe` to remove it then.
+    pub fn executor<E>(mut self, executor: E) -> Self
+    where
+        E: Executor<UnsubscribeFuture> + Send + 'static,
+    {
+        self.executor = Some(Box::new(executor));
+        self
+    }
+
+    /// Sets the method called with the subscription id to fetch the queued events. Defaults to
+    /// `poll`.
+    pub fn poll_method(mut self, poll_method: &str) -> Self {
+        self.poll_method = poll_method.to_owned();
+        self
+    }
+
+    /// Sets if the stream keeps polling after a poll failed. By default the stream ends after
+    /// yielding the error of a failed poll. Failing to subscribe always ends the stream.
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Returns the subscription id, or `None` if subscribing has not succeeded yet.
+    pub fn id(&self) -> Option<&JsonValue> {
+        self.id.as_ref()
+    }
+
+    /// Ends the subscription and returns the call to the matching `unsubscribe_*` method, for
+    /// waiting until the server has removed the subscription. Returns `None` if there is no
+    /// subscription to remove. Dropping the stream unsubscribes on the executor instead.
+    pub fn unsubscribe(mut self) -> Option<RpcRequest<JsonValue, Tr::Future>> {
+        self.unsubscribe_request()
+    }
+
+    fn poll_request(&mut self) -> State<T, Tr::Future> {
+        let id = self.id.clone().expect("Polling without a subscription id");
+        let method = self.poll_method.clone();
+        State::Polling(call_method(&mut self.transport, method, [id]))
+    }
+
+    /// Returns the state to continue with after a poll, waiting for the poll delay if there is
+    /// one.
+    fn after_poll(&mut self) -> State<T, Tr::Future> {
+        match self.poll_delay {
+            Some(ref mut poll_delay) => State::Waiting(poll_delay()),
+            None => self.poll_request(),
+        }
+    }
+}
+
+impl<T, Tr> Stream for RpcSubscription<T, Tr>
+where
+    T: serde::de::DeserializeOwned + Send + 'static,
+    Tr: Transport,
+{
+    type Item = T;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<T>, Error> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Async::Ready(Some(event)));
+            }
+            let next = match self.state {
+                State::Subscribing(ref mut request) => match request.poll() {
+                    Ok(Async::Ready(id)) => Next::Subscribed(id),
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(error) => Next::End(error),
+                },
+                State::Polling(ref mut request) => match request.poll() {
+                    Ok(Async::Ready(events)) => Next::Events(events),
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(error) => Next::Fail(error),
+                },
+                State::Polled => Next::Wait,
+                State::Waiting(ref mut delay) => match delay.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    _ => Next::Poll,
+                },
+                State::Done => return Ok(Async::Ready(None)),
+            };
+            self.state = match next {
+                Next::Subscribed(id) => {
+                    debug!("Subscribed to \"{}\" with id {}", self.name, id);
+                    self.id = Some(id);
+                    self.poll_request()
+                }
+                Next::Events(events) => {
+                    trace!("Polled {} events of \"{}\"", events.len(), self.name);
+                    self.events.extend(events);
+                    State::Polled
+                }
+                Next::Wait => self.after_poll(),
+                Next::Poll => self.poll_request(),
+                Next::End(error) => {
+                    self.state = State::Done;
+                    return Err(error);
+                }
+                Next::Fail(error) => {
+                    self.state = if self.continue_on_error {
+                        self.after_poll()
+                    } else {
+                        State::Done
+                    };
+                    return Err(error);
+                }
+            };
+        }
+    }
+}
+
+impl<T, Tr: Transport> RpcSubscription<T, Tr> {
+    fn unsubscribe_request(&mut self) -> Option<RpcRequest<JsonValue, Tr::Future>> {
+        self.state = State::Done;
+        let id = self.id.take()?;
+        debug!("Unsubscribing from \"{}\" with id {}", self.name, id);
+        let method = format!("unsubscribe_{}", self.name);
+        Some(call_method(&mut self.transport, method, [id]))
+    }
+}
+
+impl<T, Tr: Transport> Drop for RpcSubscription<T, Tr> {
+    fn drop(&mut self) {
+        let executor = match self.executor.take() {
+            Some(executor) => executor,
+            None => {
+                if let Some(ref id) = self.id {
+                    debug!("Leaving subscription \"{}\" with id {}", self.name, id);
+                }
+                return;
+            }
+        };
+        let request = match self.unsubscribe_request() {
+            Some(request) => request,
+            None => return,
+        };
+        // Dropping the request could abort it, so it is driven to completion on the executor.
+        let unsubscribe = request.then(|result| {
+            if let Err(error) = result {
+                warn!("Unable to unsubscribe: {}", error);
+            }
+            Ok(())
+        });
+        if executor.execute(Box::new(unsubscribe)).is_err() {
+            warn!("Unable to unsubscribe: The executor is shut down");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::{self, ExecuteError};
+    use mock::{MockResponse, MockTransport};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    jsonrpc_client!(pub struct SubscriptionClient {
+        pub fn block_events(&mut self, from: u64) -> RpcSubscription<u64>;
+    });
+
+    fn subscribed_transport(poll: MockResponse) -> MockTransport {
+        let transport = MockTransport::new();
+        transport
+            .on_method("subscribe_block_events", MockResponse::result("sub-1"))
+            .on_method("poll", poll)
+            .on_method("unsubscribe_block_events", MockResponse::result(true));
+        transport
+    }
+
+    /// Collects the spawned futures, to be run by the test.
+    #[derive(Clone, Default)]
+    struct TestExecutor(Arc<Mutex<Vec<UnsubscribeFuture>>>);
+
+    impl Executor<UnsubscribeFuture> for TestExecutor {
+        fn execute(
+            &self,
+            future: UnsubscribeFuture,
+        ) -> ::std::result::Result<(), ExecuteError<UnsubscribeFuture>> {
+            self.0.lock().unwrap().push(future);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn yields_polled_events() {
+        let transport = subscribed_transport(MockResponse::result([1, 2]));
+        let mut client = SubscriptionClient::new(transport.clone());
+        let events = client.block_events(10).take(5).collect().wait().unwrap();
+        assert_eq!(events, vec![1, 2, 1, 2, 1]);
+
+        let subscribe = transport.requests_for("subscribe_block_events");
+        assert_eq!(subscribe[0].params, Some(json!([10])));
+        let polls = transport.requests_for("poll");
+        assert_eq!(polls.len(), 3);
+        assert_eq!(polls[0].params, Some(json!(["sub-1"])));
+    }
+
+    #[test]
+    fn unsubscribes_explicitly() {
+        let transport = subscribed_transport(MockResponse::result([1, 2]));
+        let mut client = SubscriptionClient::new(transport.clone());
+        let (_, events) = client.block_events(0).into_future().wait().ok().unwrap();
+        assert_eq!(events.id(), Some(&json!("sub-1")));
+        assert_eq!(events.unsubscribe().unwrap().wait().unwrap(), json!(true));
+        let unsubscribe = transport.requests_for("unsubscribe_block_events");
+        assert_eq!(unsubscribe[0].params, Some(json!(["sub-1"])));
+    }
+
+    #[test]
+    fn unsubscribes_on_drop() {
+        let transport = subscribed_transport(MockResponse::result([1, 2]));
+        let mut client = SubscriptionClient::new(transport.clone());
+        let executor = TestExecutor::default();
+        client
+            .block_events(0)
+            .executor(executor.clone())
+            .take(1)
+            .collect()
+            .wait()
+            .unwrap();
+
+        let spawned = executor
+            .0
+            .lock()
+            .unwrap()
+            .pop()
+            .expect("Never unsubscribed");
+        spawned.wait().unwrap();
+        let unsubscribe = transport.requests_for("unsubscribe_block_events");
+        assert_eq!(unsubscribe[0].params, Some(json!(["sub-1"])));
+    }
+
+    #[test]
+    fn stays_subscribed_on_drop_without_executor() {
+        let transport = subscribed_transport(MockResponse::result([1, 2]));
+        let mut client = SubscriptionClient::new(transport.clone());
+        client.block_events(0).take(1).collect().wait().unwrap();
+        assert!(transport
+            .requests_for("unsubscribe_block_events")
+            .is_empty());
+    }
+
+    #[test]
+    fn waits_for_poll_delay() {
+        let transport = subscribed_transport(MockResponse::result([1]));
+        let mut client = SubscriptionClient::new(transport.clone());
+        let delays = Arc::new(AtomicUsize::new(0));
+        let delay_count = delays.clone();
+        let events = client
+            .block_events(0)
+            .poll_delay(move || {
+                delay_count.fetch_add(1, Ordering::SeqCst);
+                future::ok::<(), ()>(())
+            })
+            .take(3)
+            .collect()
+            .wait()
+            .unwrap();
+        assert_eq!(events, vec![1, 1, 1]);
+        assert_eq!(delays.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn ends_after_failed_poll() {
+        let transport = subscribed_transport(MockResponse::transport_error("gone"));
+        let mut client = SubscriptionClient::new(transport.clone());
+        let mut events = client.block_events(0).wait();
+        assert!(events.next().unwrap().is_err());
+        assert!(events.next().is_none());
+    }
+
+    #[test]
+    fn continues_after_failed_poll() {
+        let transport = subscribed_transport(MockResponse::transport_error("gone"));
+        let mut client = SubscriptionClient::new(transport.clone());
+        let mut events = client
+            .block_events(0)
+            .poll_delay(|| future::ok::<(), ()>(()))
+            .continue_on_error(true)
+            .wait();
+        assert!(events.next().unwrap().is_err());
+        assert!(events.next().unwrap().is_err());
+        assert_eq!(transport.requests_for("poll").len(), 2);
+    }
+
+    #[test]
+    fn failed_subscribe_ends_stream() {
+        let transport = MockTransport::new();
+        let mut client = SubscriptionClient::new(transport.clone());
+        let mut events = client.block_events(0).continue_on_error(true).wait();
+        assert!(events.next().unwrap().is_err());
+        assert!(events.next().is_none());
+        assert!(transport.requests_for("poll").is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Measures the cost of creating many handles to one transport, with `cargo bench`. Compares
+//! `HttpTransport::handle` with a `HandleFactory`, and sharing the default headers with copying
+//! them for every handle, like handles did before they shared them.
+
+extern crate jsonrpc_client_http;
+
+use std::time::{Duration, Instant};
+
+use jsonrpc_client_http::header::{self, Accept, Authorization, Bearer, UserAgent};
+use jsonrpc_client_http::{HttpHandle, HttpTransport};
+
+/// How many handles are created per measurement, one per tenant endpoint.
+const HANDLES: usize = 10_000;
+/// How many different endpoints the handles are created for.
+const ENDPOINTS: usize = 100;
+
+fn main() {
+    let transport = HttpTransport::new()
+        .user_agent("bench")
+        .default_header(Accept::json())
+        .default_header(Authorization(Bearer {
+            token: "0123456789abcdef0123456789abcdef".to_owned(),
+        }))
+        .standalone()
+        .unwrap();
+    let uris: Vec<String> = (0..ENDPOINTS)
+        .map(|tenant| format!("https://node.example.com/tenants/{}/rpc", tenant))
+        .collect();
+
+    let handles = measure("HttpTransport::handle", || {
+        (0..HANDLES)
+            .map(|i| transport.handle(&uris[i % ENDPOINTS]).unwrap())
+            .collect()
+    });
+
+    let factory = transport.handle_factory();
+    measure("HandleFactory::handle", || {
+        (0..HANDLES)
+            .map(|i| factory.handle(&uris[i % ENDPOINTS]).unwrap())
+            .collect()
+    });
+
+    let headers = handles[0].headers().clone();
+    measure("HttpHandle::clone", || {
+        handles.iter().map(HttpHandle::clone).collect()
+    });
+    measure("header::Headers::clone", || {
+        (0..HANDLES).map(|_| headers.clone()).collect::<Vec<_>>()
+    });
+
+    measure("HttpHandle::set_header", || {
+        handles
+            .iter()
+            .map(|handle| {
+                let mut handle = handle.clone();
+                handle.set_header(UserAgent::new("tenant"));
+                handle
+            })
+            .collect()
+    });
+    measure("header::Headers::set", || {
+        (0..HANDLES)
+            .map(|_| {
+                let mut headers: header::Headers = headers.clone();
+                headers.set(UserAgent::new("tenant"));
+                headers
+            })
+            .collect::<Vec<_>>()
+    });
+}
+
+/// Runs `create` a few times and prints the fastest time per created item. Returns the items of
+/// the last run.
+fn measure<T, F: FnMut() -> Vec<T>>(name: &str, mut create: F) -> Vec<T> {
+    let mut fastest = None;
+    let mut items = Vec::new();
+    for _ in 0..10 {
+        drop(items);
+        let start = Instant::now();
+        items = create();
+        let elapsed = start.elapsed();
+        if fastest.map_or(true, |fastest| elapsed < fastest) {
+            fastest = Some(elapsed);
+        }
+    }
+    let per_item = nanos(fastest.unwrap()) / items.len() as u64;
+    println!("{:<24} {:>8} ns per item", name, per_item);
+    items
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos())
+}
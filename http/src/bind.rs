@@ -0,0 +1,417 @@
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use futures_cpupool::{Builder as CpuPoolBuilder, CpuPool};
+use hyper::client::{HttpConnector, Service};
+use hyper::Uri;
+use net2::TcpBuilder;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use tokio_core::net::TcpStream;
+use tokio_core::reactor::Handle;
+use ErrorKind;
+
+/// Which IP versions of the addresses a host name resolves to are connected to. See
+/// `HttpTransportBuilder::address_family`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// Try all addresses, in the order they were resolved in.
+    Auto,
+    /// Only try IPv4 addresses.
+    Ipv4Only,
+    /// Only try IPv6 addresses.
+    Ipv6Only,
+    /// Try all IPv4 addresses before the IPv6 addresses.
+    PreferIpv4,
+}
+
+impl AddressFamily {
+    /// Filters and orders the resolved `addresses` as this family says.
+    fn apply(&self, mut addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        match *self {
+            AddressFamily::Auto => (),
+            AddressFamily::Ipv4Only => addresses.retain(|address| address.is_ipv4()),
+            AddressFamily::Ipv6Only => addresses.retain(|address| address.is_ipv6()),
+            // The sort is stable, so the order within each family is kept.
+            AddressFamily::PreferIpv4 => addresses.sort_by_key(|address| address.is_ipv6()),
+        }
+        addresses
+    }
+}
+
+/// Hyper connector working like `hyper::client::HttpConnector`, that can also bind the sockets
+/// of outgoing connections to a local address before connecting. This decides which address
+/// connections are made from on hosts with several.
+///
+/// Only the addresses of the server with the same IP version as the local address are tried.
+/// When the socket can not be bound, the request fails with a `LocalAddressUnavailable` error.
+/// Which addresses are tried can also be limited with an `AddressFamily`, and host names can be
+/// given fixed addresses instead of resolving them. The addresses are tried one after the other
+/// until a connection succeeds.
+#[derive(Debug, Clone)]
+pub struct BindConnector {
+    http: HttpConnector,
+    dns: CpuPool,
+    local_address: Option<IpAddr>,
+    address_family: AddressFamily,
+    /// The addresses set with `resolve` and `connect_to`, by lowercase host name. Addresses set
+    /// with `resolve` have no port, the port of the URI is used for them.
+    resolved: HashMap<String, Vec<(IpAddr, Option<u16>)>>,
+    enforce_http: bool,
+    handle: Handle,
+}
+
+impl BindConnector {
+    /// Creates a connector resolving host names on `threads` threads, and binding to
+    /// `local_address` if it is set. Without a local address it connects exactly like an
+    /// `HttpConnector`.
+    pub fn new(threads: usize, handle: &Handle, local_address: Option<IpAddr>) -> Self {
+        let dns = CpuPoolBuilder::new()
+            .name_prefix("jsonrpc-http-dns")
+            .pool_size(threads)
+            .create();
+        BindConnector {
+            http: HttpConnector::new_with_executor(dns.clone(), handle),
+            dns,
+            local_address,
+            address_family: AddressFamily::Auto,
+            resolved: HashMap::new(),
+            enforce_http: true,
+            handle: handle.clone(),
+        }
+    }
+
+    /// Configure if only URIs with the `http` scheme are accepted, like
+    /// `HttpConnector::enforce_http`. Enabled by default.
+    pub fn enforce_http(&mut self, enforce: bool) {
+        self.http.enforce_http(enforce);
+        self.enforce_http = enforce;
+    }
+
+    /// Configure which addresses of the server are connected to. Defaults to
+    /// `AddressFamily::Auto`.
+    pub fn address_family(&mut self, family: AddressFamily) {
+        self.address_family = family;
+    }
+
+    /// Connect to `address` for URIs with the host name `host`, instead of resolving it. Calling
+    /// this several times for the same host adds more addresses, which are tried in order. The
+    /// port is taken from the URI.
+    pub fn resolve(&mut self, host: &str, address: IpAddr) {
+        self.add_resolved(host, address, None);
+    }
+
+    /// Connect to `address` for URIs with the host name `host`, like `resolve`, but to the port
+    /// of `address` rather than the port of the URI.
+    pub fn connect_to(&mut self, host: &str, address: SocketAddr) {
+        self.add_resolved(host, address.ip(), Some(address.port()));
+    }
+
+    fn add_resolved(&mut self, host: &str, address: IpAddr, port: Option<u16>) {
+        match self.resolved.entry(host.to_ascii_lowercase()) {
+            Entry::Occupied(mut entry) => entry.get_mut().push((address, port)),
+            Entry::Vacant(entry) => {
+                entry.insert(vec![(address, port)]);
+            }
+        }
+    }
+
+    fn is_resolved(&self, uri: &Uri) -> bool {
+        match uri.host() {
+            Some(host) => self.resolved.contains_key(&host.to_ascii_lowercase()),
+            None => false,
+        }
+    }
+
+    /// Resolves the host of `uri`, unless it has been given addresses with `resolve` or
+    /// `connect_to`, and connects to its addresses allowed by the local address and the address
+    /// family.
+    fn connect_resolved(&self, uri: &Uri) -> Box<Future<Item = TcpStream, Error = io::Error>> {
+        let supported_scheme = match uri.scheme() {
+            Some(scheme) => !self.enforce_http || scheme == "http",
+            None => false,
+        };
+        if !supported_scheme {
+            return invalid_uri("invalid URI, unsupported scheme");
+        }
+        // IPv6 addresses are enclosed in brackets in URIs.
+        let host = match uri.host() {
+            Some(host) => host.trim_matches(|c| c == '[' || c == ']').to_owned(),
+            None => return invalid_uri("invalid URI, missing host"),
+        };
+        let port = match uri.port() {
+            Some(port) => port,
+            None if uri.scheme() == Some("https") => 443,
+            None => 80,
+        };
+        let handle = self.handle.clone();
+        let local_address = self.local_address;
+        let address_family = self.address_family;
+        let resolve = match self.resolved.get(&host.to_ascii_lowercase()) {
+            Some(addresses) => {
+                let addresses = addresses
+                    .iter()
+                    .map(|&(address, fixed_port)| {
+                        SocketAddr::new(address, fixed_port.unwrap_or(port))
+                    })
+                    .collect::<Vec<_>>();
+                Either::A(future::ok(addresses))
+            }
+            None => Either::B(self.dns.spawn_fn(move || {
+                (&host[..], port)
+                    .to_socket_addrs()
+                    .map(|addresses| addresses.collect::<Vec<_>>())
+            })),
+        };
+        Box::new(resolve.and_then(move |addresses| {
+            let mut addresses = address_family.apply(addresses);
+            if let Some(local_address) = local_address {
+                addresses.retain(|address| address.is_ipv4() == local_address.is_ipv4());
+            }
+            connect_any(addresses, local_address, handle)
+        }))
+    }
+}
+
+impl Service for BindConnector {
+    type Request = Uri;
+    type Response = TcpStream;
+    type Error = io::Error;
+    type Future = Box<Future<Item = TcpStream, Error = io::Error>>;
+
+    fn call(&self, uri: Uri) -> Self::Future {
+        if self.local_address.is_none()
+            && self.address_family == AddressFamily::Auto
+            && !self.is_resolved(&uri)
+        {
+            Box::new(self.http.call(uri))
+        } else {
+            self.connect_resolved(&uri)
+        }
+    }
+}
+
+fn invalid_uri(msg: &'static str) -> Box<Future<Item = TcpStream, Error = io::Error>> {
+    Box::new(future::err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        msg,
+    )))
+}
+
+/// Connects to the addresses one after the other, until a connection succeeds.
+fn connect_any(
+    addresses: Vec<SocketAddr>,
+    local_address: Option<IpAddr>,
+    handle: Handle,
+) -> Box<Future<Item = TcpStream, Error = io::Error>> {
+    let msg = match local_address {
+        Some(local_address) => format!(
+            "no address of the server has the IP version of {} and the address family",
+            local_address
+        ),
+        None => "no address of the server has the address family".to_owned(),
+    };
+    let no_address = io::Error::new(io::ErrorKind::AddrNotAvailable, msg);
+    let initial_state = (addresses.into_iter(), no_address);
+    Box::new(future::loop_fn(
+        initial_state,
+        move |(mut addresses, last_error)| {
+            let address = match addresses.next() {
+                Some(address) => address,
+                None => return Either::A(future::err(last_error)),
+            };
+            Either::B(
+                connect(&address, local_address, &handle).then(move |result| {
+                    match result {
+                        Ok(stream) => Ok(Loop::Break(stream)),
+                        // Binding fails the same way for all the other addresses.
+                        Err(error) => {
+                            if bind_error_kind(&error).is_some() {
+                                Err(error)
+                            } else {
+                                debug!("Unable to connect to {}: {}", address, error);
+                                Ok(Loop::Continue((addresses, error)))
+                            }
+                        }
+                    }
+                }),
+            )
+        },
+    ))
+}
+
+/// Connects to `address`, from `local_address` if it is set.
+fn connect(
+    address: &SocketAddr,
+    local_address: Option<IpAddr>,
+    handle: &Handle,
+) -> Box<Future<Item = TcpStream, Error = io::Error> + Send> {
+    let local_address = match local_address {
+        Some(local_address) => local_address,
+        None => return Box::new(TcpStream::connect(address, handle)),
+    };
+    let builder = if address.is_ipv4() {
+        TcpBuilder::new_v4()
+    } else {
+        TcpBuilder::new_v6()
+    };
+    let socket = builder.and_then(|builder| {
+        builder
+            .bind(SocketAddr::new(local_address, 0))
+            .map_err(|error| {
+                let kind = error.kind();
+                io::Error::new(
+                    kind,
+                    BindError {
+                        address: local_address,
+                        error,
+                    },
+                )
+            })?;
+        builder.to_tcp_stream()
+    });
+    match socket {
+        Ok(socket) => TcpStream::connect_stream(socket, address, handle),
+        Err(error) => Box::new(future::err(error)),
+    }
+}
+
+/// Returns the error kind for errors created by a `BindConnector` failing to bind a socket.
+pub(crate) fn bind_error_kind(error: &io::Error) -> Option<ErrorKind> {
+    let bind_error = match error.get_ref() {
+        Some(inner) => inner.downcast_ref::<BindError>()?,
+        None => return None,
+    };
+    Some(ErrorKind::LocalAddressUnavailable(bind_error.address))
+}
+
+#[derive(Debug)]
+struct BindError {
+    address: IpAddr,
+    error: io::Error,
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unable to bind to local address {}: {}",
+            self.address, self.error
+        )
+    }
+}
+
+impl error::Error for BindError {
+    fn description(&self) -> &str {
+        "Unable to bind to local address"
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::client::Connect;
+    use std::net::TcpListener;
+    use tokio_core::reactor::Core;
+
+    #[test]
+    fn connects_from_local_address() {
+        let mut core = Core::new().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!(
+            "http://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        );
+        let local_address = "127.0.0.1".parse().unwrap();
+        let connector = BindConnector::new(1, &core.handle(), Some(local_address));
+
+        let stream = core.run(connector.connect(uri.parse().unwrap())).unwrap();
+        assert_eq!(stream.local_addr().unwrap().ip(), local_address);
+        let (_, peer) = listener.accept().unwrap();
+        assert_eq!(peer, stream.local_addr().unwrap());
+    }
+
+    #[test]
+    fn unavailable_local_address() {
+        let mut core = Core::new().unwrap();
+        // Reserved for documentation, so never assigned to a local interface.
+        let local_address = "192.0.2.1".parse().unwrap();
+        let connector = BindConnector::new(1, &core.handle(), Some(local_address));
+
+        let error = core
+            .run(connector.connect("http://127.0.0.1:1/".parse().unwrap()))
+            .unwrap_err();
+        match bind_error_kind(&error) {
+            Some(ErrorKind::LocalAddressUnavailable(address)) => {
+                assert_eq!(address, local_address)
+            }
+            kind => panic!("unexpected error kind {:?} of {}", kind, error),
+        }
+    }
+
+    #[test]
+    fn address_families() {
+        let addresses: Vec<SocketAddr> = ["[::1]:1", "127.0.0.1:2", "[::2]:3", "127.0.0.2:4"]
+            .iter()
+            .map(|address| address.parse().unwrap())
+            .collect();
+        let ports = |family: AddressFamily| {
+            family
+                .apply(addresses.clone())
+                .iter()
+                .map(|address| address.port())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ports(AddressFamily::Auto), [1, 2, 3, 4]);
+        assert_eq!(ports(AddressFamily::Ipv4Only), [2, 4]);
+        assert_eq!(ports(AddressFamily::Ipv6Only), [1, 3]);
+        assert_eq!(ports(AddressFamily::PreferIpv4), [2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn resolve_overrides_dns() {
+        let mut core = Core::new().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut connector = BindConnector::new(1, &core.handle(), None);
+        // The .invalid top level domain never resolves.
+        connector.resolve("API.rpc.invalid", "127.0.0.1".parse().unwrap());
+
+        let uri = format!("http://api.rpc.invalid:{}/", port).parse().unwrap();
+        let stream = core.run(connector.connect(uri)).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+    }
+
+    #[test]
+    fn connect_to_overrides_port() {
+        let mut core = Core::new().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut connector = BindConnector::new(1, &core.handle(), None);
+        connector.connect_to("api.rpc.invalid", listener.local_addr().unwrap());
+
+        let uri = "http://api.rpc.invalid/".parse().unwrap();
+        let stream = core.run(connector.connect(uri)).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+    }
+
+    #[test]
+    fn only_http_by_default() {
+        let mut core = Core::new().unwrap();
+        let local_address = "127.0.0.1".parse().unwrap();
+        let mut connector = BindConnector::new(1, &core.handle(), Some(local_address));
+        let uri: Uri = "https://127.0.0.1:1/".parse().unwrap();
+
+        let error = core.run(connector.connect(uri.clone())).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        connector.enforce_http(false);
+        let error = core.run(connector.connect(uri)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    }
+}
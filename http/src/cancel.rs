@@ -0,0 +1,100 @@
+use futures::sync::oneshot;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Cancels a request sent with
+/// [`HttpHandle::send_cancelable`](struct.HttpHandle.html#method.send_cancelable), without
+/// having to drop the future waiting for it.
+#[derive(Debug, Clone)]
+pub struct CallHandle {
+    state: Arc<CancelState>,
+}
+
+impl CallHandle {
+    /// Aborts the request. If the response has not arrived yet, the request is canceled on the
+    /// event loop, which closes its connection, and the future fails with a `RequestCanceled`
+    /// error. Does nothing if the request has already completed.
+    pub fn cancel(&self) {
+        self.state.canceled.store(true, Ordering::SeqCst);
+        self.state.abort();
+    }
+}
+
+#[derive(Debug)]
+struct CancelState {
+    abort_tx: Mutex<Option<oneshot::Sender<()>>>,
+    canceled: AtomicBool,
+}
+
+impl CancelState {
+    fn abort(&self) {
+        let abort_tx = match self.abort_tx.lock() {
+            Ok(mut abort_tx) => abort_tx.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+        if let Some(abort_tx) = abort_tx {
+            let _ = abort_tx.send(());
+        }
+    }
+}
+
+/// Kept by the future waiting for the response of a request. When it is dropped, which happens
+/// when the caller stops waiting, the request is aborted on the event loop.
+#[derive(Debug)]
+pub(crate) struct Cancellation {
+    state: Arc<CancelState>,
+}
+
+impl Cancellation {
+    /// Returns a new `Cancellation`, and the receiver the event loop listens on for aborting the
+    /// request.
+    pub fn new() -> (Self, oneshot::Receiver<()>) {
+        let (abort_tx, abort_rx) = oneshot::channel();
+        let state = Arc::new(CancelState {
+            abort_tx: Mutex::new(Some(abort_tx)),
+            canceled: AtomicBool::new(false),
+        });
+        (Cancellation { state }, abort_rx)
+    }
+
+    pub fn call_handle(&self) -> CallHandle {
+        CallHandle {
+            state: self.state.clone(),
+        }
+    }
+
+    /// Returns true if the request was canceled through a `CallHandle`.
+    pub fn is_canceled(&self) -> bool {
+        self.state.canceled.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Cancellation {
+    fn drop(&mut self) {
+        self.state.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Future;
+
+    #[test]
+    fn aborted_when_dropped() {
+        let (cancellation, abort_rx) = Cancellation::new();
+        drop(cancellation);
+        assert_eq!(abort_rx.wait(), Ok(()));
+    }
+
+    #[test]
+    fn aborted_when_canceled() {
+        let (cancellation, abort_rx) = Cancellation::new();
+        let call_handle = cancellation.call_handle();
+        assert!(!cancellation.is_canceled());
+        call_handle.cancel();
+        call_handle.cancel();
+        assert!(cancellation.is_canceled());
+        assert_eq!(abort_rx.wait(), Ok(()));
+    }
+}
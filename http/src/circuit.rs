@@ -0,0 +1,178 @@
+use hyper::Uri;
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use {ErrorKind, HttpResponseMeta, Result, RetryPolicy};
+
+/// Stops sending requests to endpoints that keep failing, see
+/// `HttpTransportBuilder::circuit_breaker`. Every endpoint, a scheme and authority, has a circuit
+/// of its own. Clones share the circuits.
+#[derive(Debug, Clone)]
+pub(crate) struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    circuits: Arc<Mutex<HashMap<String, Circuit>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Circuit {
+    /// Requests are sent. Contains the number of requests that failed in a row.
+    Closed(u32),
+    /// Requests fail right away until the given time.
+    Open(Instant),
+    /// A probe request has been sent, other requests fail right away until it completes or the
+    /// given time has passed.
+    HalfOpen(Instant),
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold: cmp::max(failure_threshold, 1),
+            cooldown,
+            circuits: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns if a request can be sent to `uri`, or the `CircuitOpen` error to fail it with.
+    /// Once the cooldown of an open circuit has passed, one request is let through as a probe.
+    pub fn allow(&self, uri: &Uri) -> Result<()> {
+        let endpoint = endpoint(uri);
+        let now = Instant::now();
+        let mut circuits = self.lock();
+        let circuit = circuits
+            .entry(endpoint.clone())
+            .or_insert(Circuit::Closed(0));
+        match *circuit {
+            Circuit::Closed(_) => Ok(()),
+            Circuit::Open(until) | Circuit::HalfOpen(until) if now >= until => {
+                debug!(
+                    "Sending a probe request to {} through its open circuit",
+                    endpoint
+                );
+                *circuit = Circuit::HalfOpen(now + self.cooldown);
+                Ok(())
+            }
+            Circuit::Open(until) | Circuit::HalfOpen(until) => {
+                Err(ErrorKind::CircuitOpen(endpoint, until - now).into())
+            }
+        }
+    }
+
+    /// Records the result of a request sent to `uri`. Errors `RetryPolicy::is_transient`
+    /// considers transient count as failures, everything else means that the endpoint is alive.
+    pub fn record(&self, uri: &Uri, result: &Result<HttpResponseMeta>) {
+        let failed = match *result {
+            Err(ref error) => RetryPolicy::is_transient(error),
+            Ok(_) => false,
+        };
+        let endpoint = endpoint(uri);
+        let mut circuits = self.lock();
+        let circuit = circuits
+            .entry(endpoint.clone())
+            .or_insert(Circuit::Closed(0));
+        *circuit = match *circuit {
+            Circuit::HalfOpen(_) if !failed => {
+                info!(
+                    "Closing the circuit to {} after a successful probe",
+                    endpoint
+                );
+                Circuit::Closed(0)
+            }
+            _ if !failed => Circuit::Closed(0),
+            Circuit::Closed(failures) if failures + 1 < self.failure_threshold => {
+                Circuit::Closed(failures + 1)
+            }
+            // A request sent before the circuit opened does not extend the cooldown.
+            Circuit::Open(until) => Circuit::Open(until),
+            _ => {
+                warn!(
+                    "Opening the circuit to {} for {:?} after repeated failures",
+                    endpoint, self.cooldown
+                );
+                Circuit::Open(Instant::now() + self.cooldown)
+            }
+        };
+    }
+
+    fn lock<'a>(&'a self) -> MutexGuard<'a, HashMap<String, Circuit>> {
+        self.circuits.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns the endpoint the circuit of `uri` belongs to.
+fn endpoint(uri: &Uri) -> String {
+    format!(
+        "{}://{}",
+        uri.scheme().unwrap_or("http"),
+        uri.authority().unwrap_or("")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{Headers, StatusCode};
+    use std::thread;
+    use {Error, RequestContext};
+
+    fn failure() -> Result<HttpResponseMeta> {
+        Err(ErrorKind::HttpError(StatusCode::ServiceUnavailable, RequestContext::default()).into())
+    }
+
+    fn success() -> Result<HttpResponseMeta> {
+        Ok(HttpResponseMeta {
+            status: StatusCode::Ok,
+            headers: Headers::new(),
+            body: Vec::new(),
+        })
+    }
+
+    fn is_open(result: Result<()>) -> bool {
+        match result {
+            Err(Error(ErrorKind::CircuitOpen(..), _)) => true,
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(()) => false,
+        }
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        let uri = "http://localhost:1234/rpc".parse().unwrap();
+        let other_path = "http://localhost:1234/other".parse().unwrap();
+        let other_port = "http://localhost:1235/rpc".parse().unwrap();
+
+        breaker.record(&uri, &failure());
+        breaker.record(&uri, &failure());
+        breaker.record(&uri, &success());
+        breaker.record(&uri, &failure());
+        breaker.record(&uri, &failure());
+        assert!(!is_open(breaker.allow(&uri)));
+        breaker.record(&uri, &failure());
+        assert!(is_open(breaker.clone().allow(&other_path)));
+        assert!(!is_open(breaker.allow(&other_port)));
+    }
+
+    #[test]
+    fn probe_after_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(50));
+        let uri = "http://localhost/".parse().unwrap();
+        breaker.record(&uri, &failure());
+        assert!(is_open(breaker.allow(&uri)));
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(!is_open(breaker.allow(&uri)));
+        // Only the probe goes through until it completes.
+        assert!(is_open(breaker.allow(&uri)));
+        breaker.record(&uri, &failure());
+        assert!(is_open(breaker.allow(&uri)));
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(!is_open(breaker.allow(&uri)));
+        breaker.record(&uri, &success());
+        assert!(!is_open(breaker.allow(&uri)));
+        assert!(!is_open(breaker.allow(&uri)));
+    }
+}
@@ -1,6 +1,12 @@
-use hyper::client::{Client, Connect, HttpConnector};
+use bind::{AddressFamily, BindConnector};
+use connector::TimeoutConnector;
+use hyper::client::{Client, Config, Connect};
 use hyper::Body;
+use proxy::{ProxyConfig, ProxyConnector};
 use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio_core::reactor::Handle;
 
 /// Trait for types able to produce Hyper `Client`s for use in `HttpTransport`.
@@ -13,19 +19,82 @@ pub trait ClientCreator: Send + 'static {
 
     /// Tries to create a Hyper `Client` based on the given Tokio `Handle`.
     fn create(&self, handle: &Handle) -> Result<Client<Self::Connect, Body>, Self::Error>;
+
+    /// Returns if the created `Client` can connect to `https` URIs. Redirects from `http` to
+    /// `https` URIs are only followed if it can. Defaults to `true`.
+    fn supports_https(&self) -> bool {
+        true
+    }
+}
+
+/// Settings for the connection pool of the `Client`s created by the default creators.
+#[derive(Debug, Clone)]
+pub(crate) struct PoolSettings {
+    pub keep_alive: bool,
+    pub keep_alive_timeout: Duration,
 }
 
-/// Default `Client` creator that defaults to creating a standard `Client` with just
-/// `hyper::Client::new(handle)`.
-#[derive(Debug, Default)]
-pub struct DefaultClient;
+impl Default for PoolSettings {
+    /// The same defaults as `hyper::Client` uses.
+    fn default() -> Self {
+        PoolSettings {
+            keep_alive: true,
+            keep_alive_timeout: Duration::from_secs(90),
+        }
+    }
+}
+
+impl PoolSettings {
+    fn configure<C, B>(&self, config: Config<C, B>) -> Config<C, B> {
+        config
+            .keep_alive(self.keep_alive)
+            .keep_alive_timeout(Some(self.keep_alive_timeout))
+    }
+}
+
+/// Default `Client` creator that creates a standard `Client` like `hyper::Client::new(handle)`,
+/// but connecting with a `BindConnector` wrapped in a `ProxyConnector` and a `TimeoutConnector`.
+///
+/// Configured through the methods on `HttpTransportBuilder<DefaultClient>`.
+#[derive(Debug, Default, Clone)]
+pub struct DefaultClient {
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) proxy: ProxyConfig,
+    pub(crate) pool: PoolSettings,
+    pub(crate) local_address: Option<IpAddr>,
+    /// `None` for `AddressFamily::Auto`.
+    pub(crate) address_family: Option<AddressFamily>,
+    /// The addresses from `resolve` and `connect_to`, without a port for `resolve`.
+    pub(crate) resolve: Vec<(String, IpAddr, Option<u16>)>,
+}
 
 impl ClientCreator for DefaultClient {
-    type Connect = HttpConnector;
+    type Connect = TimeoutConnector<ProxyConnector<BindConnector>>;
     type Error = io::Error;
 
-    fn create(&self, handle: &Handle) -> Result<Client<HttpConnector, Body>, io::Error> {
-        Ok(Client::new(handle))
+    fn create(
+        &self,
+        handle: &Handle,
+    ) -> Result<Client<TimeoutConnector<ProxyConnector<BindConnector>>, Body>, io::Error> {
+        // Same number of DNS threads as `hyper::Client::new` uses.
+        let mut connector = BindConnector::new(4, handle, self.local_address);
+        if let Some(family) = self.address_family {
+            connector.address_family(family);
+        }
+        for &(ref host, address, port) in &self.resolve {
+            match port {
+                Some(port) => connector.connect_to(host, SocketAddr::new(address, port)),
+                None => connector.resolve(host, address),
+            }
+        }
+        let connector = ProxyConnector::new(connector, self.proxy.without_https());
+        let connector = TimeoutConnector::new(connector, self.connect_timeout, handle);
+        let config = self.pool.configure(Client::configure());
+        Ok(config.connector(connector).build(handle))
+    }
+
+    fn supports_https(&self) -> bool {
+        false
     }
 }
 
@@ -44,32 +113,97 @@ where
     }
 }
 
+/// A `ClientCreator` shared between the event loops of a standalone transport restarted with
+/// `HttpTransportBuilder::restart_on_failure`, so each of them can create its own `Client`.
+pub(crate) struct SharedCreator<C>(Arc<Mutex<C>>);
+
+impl<C> SharedCreator<C> {
+    pub fn new(client_creator: C) -> Self {
+        SharedCreator(Arc::new(Mutex::new(client_creator)))
+    }
+}
+
+impl<C> Clone for SharedCreator<C> {
+    fn clone(&self) -> Self {
+        SharedCreator(self.0.clone())
+    }
+}
+
+impl<C: ClientCreator> ClientCreator for SharedCreator<C> {
+    type Connect = C::Connect;
+    type Error = C::Error;
+
+    fn create(&self, handle: &Handle) -> Result<Client<C::Connect, Body>, C::Error> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .create(handle)
+    }
+
+    fn supports_https(&self) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .supports_https()
+    }
+}
 
 #[cfg(feature = "tls")]
 mod tls {
     use super::*;
     use hyper_tls::HttpsConnector;
     use native_tls::Error;
+    use tls_config::TlsConfig;
 
     /// Number of threads in the thread pool doing DNS resolutions.
     /// Since DNS is resolved via blocking syscall they must be run on separate threads.
     static DNS_THREADS: usize = 2;
 
     /// Default `Client` creator for TLS enabled clients. Creates a Hyper `Client` based on
-    /// `hyper_tls::HttpsConnector`.
-    #[derive(Debug, Default)]
-    pub struct DefaultTlsClient;
+    /// `hyper_tls::HttpsConnector`, wrapped in a `TimeoutConnector`. The `HttpsConnector` in turn
+    /// wraps a `ProxyConnector`, so https connections through a proxy are encrypted end to end,
+    /// and that wraps a `BindConnector`.
+    ///
+    /// Configured through the methods on `HttpTransportBuilder<DefaultTlsClient>`, and the
+    /// `TlsConfig` given to `HttpTransport::with_tls_config`.
+    #[derive(Debug, Default, Clone)]
+    pub struct DefaultTlsClient {
+        pub(crate) connect_timeout: Option<Duration>,
+        pub(crate) proxy: ProxyConfig,
+        pub(crate) pool: PoolSettings,
+        pub(crate) local_address: Option<IpAddr>,
+        pub(crate) address_family: Option<AddressFamily>,
+        /// The addresses from `resolve` and `connect_to`, without a port for `resolve`.
+        pub(crate) resolve: Vec<(String, IpAddr, Option<u16>)>,
+        pub(crate) tls_config: TlsConfig,
+    }
 
     impl ClientCreator for DefaultTlsClient {
-        type Connect = HttpsConnector<HttpConnector>;
+        type Connect = TimeoutConnector<HttpsConnector<ProxyConnector<BindConnector>>>;
         type Error = Error;
 
-        fn create(
-            &self,
-            handle: &Handle,
-        ) -> Result<Client<HttpsConnector<HttpConnector>, Body>, Error> {
-            let connector = HttpsConnector::new(DNS_THREADS, handle)?;
-            let client = Client::configure().connector(connector).build(handle);
+        fn create(&self, handle: &Handle) -> Result<Client<Self::Connect, Body>, Error> {
+            // Same setup as `HttpsConnector::new`, with the `ProxyConnector` added.
+            let mut connector = BindConnector::new(DNS_THREADS, handle, self.local_address);
+            connector.enforce_http(false);
+            if let Some(family) = self.address_family {
+                connector.address_family(family);
+            }
+            for &(ref host, address, port) in &self.resolve {
+                match port {
+                    Some(port) => connector.connect_to(host, SocketAddr::new(address, port)),
+                    None => connector.resolve(host, address),
+                }
+            }
+            let connector = ProxyConnector::new(connector, self.proxy.clone());
+            let tls = self.tls_config.connector()?;
+            let mut connector = HttpsConnector::from((connector, tls));
+            connector.danger_disable_hostname_verification(
+                self.tls_config.hostname_verification_disabled(),
+            );
+            let connector = TimeoutConnector::new(connector, self.connect_timeout, handle);
+            let config = self.pool.configure(Client::configure());
+            let client = config.connector(connector).build(handle);
             Ok(client)
         }
     }
@@ -0,0 +1,302 @@
+use hyper::header::Encoding;
+use {ErrorKind, RequestContext, Result};
+
+#[cfg(feature = "compression")]
+use miniz_oxide::deflate;
+#[cfg(feature = "compression")]
+use miniz_oxide::inflate::{self, TINFLStatus};
+
+/// How request bodies are compressed, see
+/// [`HttpHandle::compress_request`](struct.HttpHandle.html#method.compress_request).
+#[cfg(feature = "compression")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionKind {
+    /// Gzip, as described in RFC 1952.
+    Gzip,
+    /// Zlib compressed data, as described in RFC 1950, which is what the `deflate` content coding
+    /// means.
+    Deflate,
+}
+
+#[cfg(feature = "compression")]
+impl CompressionKind {
+    /// The content coding to send in the `Content-Encoding` header.
+    pub(crate) fn encoding(&self) -> Encoding {
+        match *self {
+            CompressionKind::Gzip => Encoding::Gzip,
+            CompressionKind::Deflate => Encoding::Deflate,
+        }
+    }
+}
+
+/// The compression level used for request bodies, the same default as zlib and gzip use.
+#[cfg(feature = "compression")]
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// Compresses a request body.
+#[cfg(feature = "compression")]
+pub(crate) fn encode(kind: CompressionKind, body: &[u8]) -> Vec<u8> {
+    match kind {
+        CompressionKind::Gzip => {
+            // No flags, modification time or extra flags, and an unknown operating system.
+            let mut encoded = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+            encoded.extend_from_slice(&deflate::compress_to_vec(body, COMPRESSION_LEVEL));
+            encoded.extend_from_slice(&u32_le(crc32(body)));
+            encoded.extend_from_slice(&u32_le(body.len() as u32));
+            encoded
+        }
+        CompressionKind::Deflate => deflate::compress_to_vec_zlib(body, COMPRESSION_LEVEL),
+    }
+}
+
+/// Decodes a response body with the given content codings, listed in the order they were
+/// applied, like in the `Content-Encoding` header. Gzip and deflate are only supported with the
+/// "compression" feature. Fails with a `ResponseTooLarge` error if the decoded body is larger
+/// than `max_size`.
+#[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+pub(crate) fn decode(
+    encodings: &[Encoding],
+    mut body: Vec<u8>,
+    max_size: Option<usize>,
+) -> Result<Vec<u8>> {
+    for encoding in encodings.iter().rev() {
+        body = match *encoding {
+            Encoding::Identity => body,
+            #[cfg(feature = "compression")]
+            Encoding::Gzip | Encoding::Deflate => {
+                let limit = max_size.unwrap_or(!0usize);
+                let decoded = match *encoding {
+                    Encoding::Gzip => gunzip(&body, limit),
+                    _ => inflate_deflate(&body, limit),
+                };
+                match decoded {
+                    Ok(decoded) => decoded,
+                    Err(Failure::TooLarge) => {
+                        bail!(ErrorKind::ResponseTooLarge(
+                            limit,
+                            RequestContext::default()
+                        ))
+                    }
+                    Err(Failure::Invalid) => {
+                        bail!(ErrorKind::InvalidCompressedBody(
+                            encoding.to_string(),
+                            RequestContext::default()
+                        ))
+                    }
+                }
+            }
+            ref encoding => bail!(ErrorKind::UnsupportedContentEncoding(
+                encoding.to_string(),
+                RequestContext::default()
+            )),
+        };
+    }
+    Ok(body)
+}
+
+/// Why a body could not be decompressed.
+#[cfg(feature = "compression")]
+#[derive(Debug, PartialEq)]
+enum Failure {
+    TooLarge,
+    Invalid,
+}
+
+#[cfg(feature = "compression")]
+impl From<inflate::DecompressError> for Failure {
+    fn from(error: inflate::DecompressError) -> Self {
+        match error.status {
+            TINFLStatus::HasMoreOutput => Failure::TooLarge,
+            _ => Failure::Invalid,
+        }
+    }
+}
+
+/// Decodes the `deflate` content coding. That should be zlib data, but some servers send raw
+/// deflate data, so that is accepted as well.
+#[cfg(feature = "compression")]
+fn inflate_deflate(data: &[u8], limit: usize) -> ::std::result::Result<Vec<u8>, Failure> {
+    match inflate::decompress_to_vec_zlib_with_limit(data, limit) {
+        Ok(decoded) => Ok(decoded),
+        Err(ref error) if error.status == TINFLStatus::HasMoreOutput => Err(Failure::TooLarge),
+        Err(_) => Ok(inflate::decompress_to_vec_with_limit(data, limit)?),
+    }
+}
+
+/// Decodes gzip data with a single member, as described in RFC 1952.
+#[cfg(feature = "compression")]
+fn gunzip(data: &[u8], limit: usize) -> ::std::result::Result<Vec<u8>, Failure> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if data.len() < 10 || data[0] != 0x1f || data[1] != 0x8b || data[2] != 8 {
+        return Err(Failure::Invalid);
+    }
+    let flags = data[3];
+    let mut rest = &data[10..];
+    if flags & FEXTRA != 0 {
+        if rest.len() < 2 {
+            return Err(Failure::Invalid);
+        }
+        let length = rest[0] as usize | (rest[1] as usize) << 8;
+        rest = rest.get(2 + length..).ok_or(Failure::Invalid)?;
+    }
+    if flags & FNAME != 0 {
+        rest = skip_zero_terminated(rest)?;
+    }
+    if flags & FCOMMENT != 0 {
+        rest = skip_zero_terminated(rest)?;
+    }
+    if flags & FHCRC != 0 {
+        rest = rest.get(2..).ok_or(Failure::Invalid)?;
+    }
+    if rest.len() < 8 {
+        return Err(Failure::Invalid);
+    }
+    let (deflated, trailer) = rest.split_at(rest.len() - 8);
+    let decoded = inflate::decompress_to_vec_with_limit(deflated, limit)?;
+    if read_u32_le(&trailer[..4]) != crc32(&decoded)
+        || read_u32_le(&trailer[4..]) != decoded.len() as u32
+    {
+        return Err(Failure::Invalid);
+    }
+    Ok(decoded)
+}
+
+#[cfg(feature = "compression")]
+fn skip_zero_terminated(data: &[u8]) -> ::std::result::Result<&[u8], Failure> {
+    match data.iter().position(|&byte| byte == 0) {
+        Some(end) => Ok(&data[end + 1..]),
+        None => Err(Failure::Invalid),
+    }
+}
+
+#[cfg(feature = "compression")]
+fn read_u32_le(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | u32::from(byte))
+}
+
+#[cfg(feature = "compression")]
+fn u32_le(value: u32) -> [u8; 4] {
+    [
+        value as u8,
+        (value >> 8) as u8,
+        (value >> 16) as u8,
+        (value >> 24) as u8,
+    ]
+}
+
+/// The CRC-32 checksum used by gzip.
+#[cfg(feature = "compression")]
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (index, entry) in table.iter_mut().enumerate() {
+        *entry = (0..8).fold(index as u32, |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            }
+        });
+    }
+    !data.iter().fold(!0u32, |crc, &byte| {
+        table[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+    use miniz_oxide::deflate::{compress_to_vec, compress_to_vec_zlib};
+
+    /// `echo -n '{"result": 5}' | gzip -n`
+    const GZIPPED: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xab, 0x56, 0x2a, 0x4a, 0x2d,
+        0x2e, 0xcd, 0x29, 0x51, 0xb2, 0x52, 0x30, 0xad, 0x05, 0x00, 0x10, 0x00, 0xa7, 0x56, 0x0d,
+        0x00, 0x00, 0x00,
+    ];
+    const BODY: &[u8] = br#"{"result": 5}"#;
+
+    fn decode_as(encoding: Encoding, body: &[u8], max_size: Option<usize>) -> Result<Vec<u8>> {
+        decode(&[encoding], body.to_vec(), max_size)
+    }
+
+    #[test]
+    fn gzip() {
+        assert_eq!(decode_as(Encoding::Gzip, GZIPPED, None).unwrap(), BODY);
+
+        // With a file name and a comment.
+        let mut with_name = GZIPPED[..10].to_vec();
+        with_name[3] = 0x08 | 0x10;
+        with_name.extend_from_slice(b"body.json\0a comment\0");
+        with_name.extend_from_slice(&GZIPPED[10..]);
+        assert_eq!(decode_as(Encoding::Gzip, &with_name, None).unwrap(), BODY);
+
+        let mut corrupt = GZIPPED.to_vec();
+        corrupt[GZIPPED.len() - 5] ^= 1;
+        match *decode_as(Encoding::Gzip, &corrupt, None)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::InvalidCompressedBody(ref encoding, _) if encoding == "gzip" => (),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn deflate() {
+        let zlib = compress_to_vec_zlib(BODY, 6);
+        assert_eq!(decode_as(Encoding::Deflate, &zlib, None).unwrap(), BODY);
+        let raw = compress_to_vec(BODY, 6);
+        assert_eq!(decode_as(Encoding::Deflate, &raw, None).unwrap(), BODY);
+    }
+
+    #[test]
+    fn several_encodings() {
+        let body = compress_to_vec_zlib(GZIPPED, 6);
+        let encodings = [Encoding::Gzip, Encoding::Identity, Encoding::Deflate];
+        assert_eq!(decode(&encodings, body, None).unwrap(), BODY);
+    }
+
+    #[test]
+    fn decoded_size_is_limited() {
+        let body = compress_to_vec_zlib(&[b'x'; 1001], 6);
+        assert!(decode_as(Encoding::Deflate, &body, Some(1001)).is_ok());
+        match *decode_as(Encoding::Deflate, &body, Some(1000))
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::ResponseTooLarge(1000, _) => (),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+        assert!(decode_as(Encoding::Gzip, GZIPPED, Some(5)).is_err());
+    }
+
+    #[test]
+    fn unsupported_encoding() {
+        match *decode_as(Encoding::Brotli, BODY, None).unwrap_err().kind() {
+            ErrorKind::UnsupportedContentEncoding(ref encoding, _) if encoding == "br" => (),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let body = vec![b'x'; 10_000];
+        for &kind in &[CompressionKind::Gzip, CompressionKind::Deflate] {
+            let encoded = encode(kind, &body);
+            assert!(encoded.len() < 100);
+            assert_eq!(decode_as(kind.encoding(), &encoded, None).unwrap(), body);
+        }
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+}
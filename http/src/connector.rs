@@ -0,0 +1,133 @@
+use futures::future::{self, Either};
+use futures::Future;
+use hyper::client::{Connect, Service};
+use hyper::Uri;
+use std::error;
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+use tokio_core::reactor::{Handle, Timeout};
+
+/// Hyper connector wrapping another connector and giving each connection attempt a time limit.
+///
+/// When wrapping a TLS connector, the time limit includes the TLS handshake. A connection
+/// attempt that takes too long makes the request fail with a `ConnectTimeout` error.
+#[derive(Debug, Clone)]
+pub struct TimeoutConnector<C> {
+    connector: C,
+    timeout: Option<Duration>,
+    handle: Handle,
+}
+
+impl<C: Connect> TimeoutConnector<C> {
+    /// Wraps `connector` to fail connection attempts taking longer than `timeout`. A `timeout`
+    /// of `None` means no time limit.
+    pub fn new(connector: C, timeout: Option<Duration>, handle: &Handle) -> Self {
+        TimeoutConnector {
+            connector,
+            timeout,
+            handle: handle.clone(),
+        }
+    }
+}
+
+impl<C: Connect> Service for TimeoutConnector<C> {
+    type Request = Uri;
+    type Response = C::Output;
+    type Error = io::Error;
+    type Future = Box<Future<Item = C::Output, Error = io::Error>>;
+
+    fn call(&self, uri: Uri) -> Self::Future {
+        let connect = self.connector.connect(uri);
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return Box::new(connect),
+        };
+        let start = Instant::now();
+        let timer = match Timeout::new(timeout, &self.handle) {
+            Ok(timer) => timer,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        Box::new(connect.select2(timer).then(move |result| match result {
+            Ok(Either::A((stream, _))) => Ok(stream),
+            Ok(Either::B(((), _))) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                ConnectTimeoutError {
+                    timeout,
+                    elapsed: start.elapsed(),
+                },
+            )),
+            Err(Either::A((error, _))) => Err(error),
+            Err(Either::B((error, _))) => Err(error),
+        }))
+    }
+}
+
+/// Returns the connect timeout and the time spent connecting if the error was created by a
+/// `TimeoutConnector` giving up on a connection attempt.
+pub(crate) fn connect_timeout(error: &io::Error) -> Option<(Duration, Duration)> {
+    error
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<ConnectTimeoutError>())
+        .map(|inner| (inner.timeout, inner.elapsed))
+}
+
+#[derive(Debug)]
+struct ConnectTimeoutError {
+    timeout: Duration,
+    elapsed: Duration,
+}
+
+impl fmt::Display for ConnectTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Timeout while connecting, gave up after {:?}",
+            self.elapsed
+        )
+    }
+}
+
+impl error::Error for ConnectTimeoutError {
+    fn description(&self) -> &str {
+        "Timeout while connecting"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_core::net::TcpStream;
+    use tokio_core::reactor::Core;
+
+    /// Connector whose connection attempts never finish.
+    struct NeverConnect;
+
+    impl Service for NeverConnect {
+        type Request = Uri;
+        type Response = TcpStream;
+        type Error = io::Error;
+        type Future = future::Empty<TcpStream, io::Error>;
+
+        fn call(&self, _: Uri) -> Self::Future {
+            future::empty()
+        }
+    }
+
+    #[test]
+    fn slow_connect_times_out() {
+        let mut core = Core::new().unwrap();
+        let connector = TimeoutConnector::new(
+            NeverConnect,
+            Some(Duration::from_millis(10)),
+            &core.handle(),
+        );
+        let uri = "http://127.0.0.1/".parse().unwrap();
+
+        let error = core.run(connector.connect(uri)).unwrap_err();
+        let (timeout, _) = connect_timeout(&error).unwrap();
+        assert_eq!(timeout, Duration::from_millis(10));
+        let other_timeout = io::Error::new(io::ErrorKind::TimedOut, "other timeout");
+        assert!(connect_timeout(&other_timeout).is_none());
+    }
+}
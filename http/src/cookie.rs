@@ -0,0 +1,321 @@
+use hyper::header::{Cookie as CookieHeader, Headers, HttpDate, SetCookie};
+use hyper::Uri;
+use std::cmp;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The longest `Max-Age` in seconds that is respected, 400 days like in browsers.
+const MAX_COOKIE_AGE: u64 = 400 * 24 * 60 * 60;
+
+/// Keeps the cookies set by servers and sends them back with later requests, see
+/// `HttpTransportBuilder::cookie_store`. Clones share the cookies.
+///
+/// Follows RFC 6265 where it matters for API servers: cookies are only sent to the hosts and
+/// paths that their `Domain` and `Path` attributes allow, `Secure` cookies only over https, and
+/// cookies are removed once their `Max-Age` or `Expires` has passed. Cookies with a `Domain`
+/// attribute that does not match the host setting them are ignored, but public suffixes are not
+/// known, so a server can set cookies for a whole top level domain.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CookieJar {
+    cookies: Arc<Mutex<Vec<Cookie>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Cookie {
+    name: String,
+    value: String,
+    /// The lowercase host the cookie was set by, or the value of its `Domain` attribute.
+    domain: String,
+    /// Set if the cookie has no `Domain` attribute, and is only sent to the exact host.
+    host_only: bool,
+    path: String,
+    secure: bool,
+    /// `None` for session cookies, kept until the transport is dropped.
+    expires: Option<SystemTime>,
+}
+
+impl CookieJar {
+    /// Stores the cookies in the `Set-Cookie` headers of a response to a request sent to `uri`.
+    pub fn store(&self, uri: &Uri, headers: &Headers) {
+        let set_cookies = match headers.get::<SetCookie>() {
+            Some(set_cookies) => set_cookies,
+            None => return,
+        };
+        let host = match uri.host() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return,
+        };
+        let now = SystemTime::now();
+        let mut cookies = self.lock();
+        cookies.retain(|cookie| !cookie.is_expired(now));
+        for set_cookie in set_cookies.iter() {
+            let cookie = match Cookie::parse(set_cookie, &host, uri.path(), now) {
+                Some(cookie) => cookie,
+                None => {
+                    debug!("Ignoring invalid cookie from {}: {}", host, set_cookie);
+                    continue;
+                }
+            };
+            trace!(
+                "Storing cookie {} for {}{}",
+                cookie.name,
+                cookie.domain,
+                cookie.path
+            );
+            cookies.retain(|stored| !cookie.replaces(stored));
+            // An expiry in the past is how servers delete cookies.
+            if !cookie.is_expired(now) {
+                cookies.push(cookie);
+            }
+        }
+    }
+
+    /// Adds the cookies to send to `uri` to the `Cookie` header in `headers`. Cookies already in
+    /// the header, set on the handle, take precedence over stored cookies with the same name.
+    pub fn add_to(&self, uri: &Uri, headers: &mut Headers) {
+        let host = match uri.host() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return,
+        };
+        let secure = uri.scheme() == Some("https");
+        let now = SystemTime::now();
+        let mut matching: Vec<Cookie> = self
+            .lock()
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now) && cookie.matches(&host, uri.path(), secure))
+            .cloned()
+            .collect();
+        if matching.is_empty() {
+            return;
+        }
+        // Cookies with longer paths are sent first, as RFC 6265 recommends.
+        matching.sort_by_key(|cookie| cmp::Reverse(cookie.path.len()));
+        let mut header = headers
+            .get::<CookieHeader>()
+            .cloned()
+            .unwrap_or_else(CookieHeader::new);
+        for cookie in matching {
+            if header.get(&cookie.name).is_none() {
+                header.append(cookie.name, cookie.value);
+            }
+        }
+        headers.set(header);
+    }
+
+    fn lock<'a>(&'a self) -> MutexGuard<'a, Vec<Cookie>> {
+        self.cookies.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Cookie {
+    /// Parses the value of a `Set-Cookie` header in a response from `host`, to a request for
+    /// `request_path`. Returns `None` if the cookie is invalid or may not be set by `host`.
+    fn parse(set_cookie: &str, host: &str, request_path: &str, now: SystemTime) -> Option<Self> {
+        let mut parts = set_cookie.split(';');
+        let (name, value) = split_pair(parts.next()?)?;
+        if name.is_empty() {
+            return None;
+        }
+        let mut cookie = Cookie {
+            name: name.to_owned(),
+            value: value.trim_matches('"').to_owned(),
+            domain: host.to_owned(),
+            host_only: true,
+            path: default_path(request_path).to_owned(),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attribute in parts {
+            let (key, value) = match split_pair(attribute) {
+                Some(pair) => pair,
+                None => (attribute.trim(), ""),
+            };
+            if key.eq_ignore_ascii_case("domain") {
+                let domain = value.trim_matches('.').to_ascii_lowercase();
+                if !domain.is_empty() {
+                    if !domain_matches(host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+            } else if key.eq_ignore_ascii_case("path") {
+                if value.starts_with('/') {
+                    cookie.path = value.to_owned();
+                }
+            } else if key.eq_ignore_ascii_case("secure") {
+                cookie.secure = true;
+            } else if key.eq_ignore_ascii_case("max-age") {
+                max_age = value.parse::<i64>().ok();
+            } else if key.eq_ignore_ascii_case("expires") {
+                if let Ok(date) = value.parse::<HttpDate>() {
+                    cookie.expires = Some(SystemTime::from(date));
+                }
+            }
+        }
+        // Max-Age takes precedence over Expires.
+        match max_age {
+            Some(seconds) if seconds <= 0 => cookie.expires = Some(UNIX_EPOCH),
+            Some(seconds) => {
+                let max_age = cmp::min(seconds as u64, MAX_COOKIE_AGE);
+                cookie.expires = Some(now + Duration::from_secs(max_age));
+            }
+            None => (),
+        }
+        Some(cookie)
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.map_or(false, |expires| expires <= now)
+    }
+
+    /// Returns if this cookie replaces `stored`, because they have the same name, domain and
+    /// path.
+    fn replaces(&self, stored: &Cookie) -> bool {
+        self.name == stored.name && self.domain == stored.domain && self.path == stored.path
+    }
+
+    /// Returns if this cookie is sent with requests to `host` and `path`, over https if `secure`
+    /// is set.
+    fn matches(&self, host: &str, path: &str, secure: bool) -> bool {
+        let host_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(host, &self.domain)
+        };
+        host_matches && path_matches(path, &self.path) && (secure || !self.secure)
+    }
+}
+
+/// Splits `name=value` into its trimmed name and value.
+fn split_pair(pair: &str) -> Option<(&str, &str)> {
+    let equals = pair.find('=')?;
+    Some((pair[..equals].trim(), pair[equals + 1..].trim()))
+}
+
+/// Returns if `host` is `domain` or one of its subdomains.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain || (host.ends_with(domain) && host[..host.len() - domain.len()].ends_with('.'))
+}
+
+/// Returns if `path` is `cookie_path` or below it.
+fn path_matches(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+}
+
+/// Returns the path of cookies without a `Path` attribute, the directory of the request path.
+fn default_path(request_path: &str) -> &str {
+    match request_path.rfind('/') {
+        Some(0) | None => "/",
+        Some(slash) => &request_path[..slash],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jar_with(uri: &str, set_cookies: &[&str]) -> CookieJar {
+        let jar = CookieJar::default();
+        let mut headers = Headers::new();
+        let set_cookies = set_cookies
+            .iter()
+            .map(|cookie| cookie.to_string())
+            .collect();
+        headers.set(SetCookie(set_cookies));
+        jar.store(&uri.parse().unwrap(), &headers);
+        jar
+    }
+
+    fn cookies_for(jar: &CookieJar, uri: &str) -> Option<String> {
+        let mut headers = Headers::new();
+        jar.add_to(&uri.parse().unwrap(), &mut headers);
+        headers
+            .get::<CookieHeader>()
+            .map(|cookie| cookie.to_string())
+    }
+
+    #[test]
+    fn host_only_by_default() {
+        let jar = jar_with(
+            "http://api.example.com/rpc/login",
+            &["session=abc; HttpOnly"],
+        );
+        assert_eq!(
+            cookies_for(&jar, "http://API.example.com/rpc/call"),
+            Some("session=abc".to_owned())
+        );
+        assert_eq!(cookies_for(&jar, "http://other.api.example.com/rpc"), None);
+        assert_eq!(cookies_for(&jar, "http://api.example.com/other"), None);
+    }
+
+    #[test]
+    fn domain_and_path_attributes() {
+        let jar = jar_with(
+            "http://api.example.com/",
+            &[
+                "a=1; Domain=.example.com; Path=/",
+                "b=2; Domain=example.com; Path=/rpc",
+                "c=3; Domain=other.com",
+                "d=4; Secure",
+            ],
+        );
+        assert_eq!(
+            cookies_for(&jar, "http://www.example.com/rpc/v1"),
+            Some("b=2; a=1".to_owned())
+        );
+        assert_eq!(
+            cookies_for(&jar, "http://example.com/rpcs"),
+            Some("a=1".to_owned())
+        );
+        assert_eq!(cookies_for(&jar, "http://notexample.com/"), None);
+        assert_eq!(
+            cookies_for(&jar, "https://api.example.com/"),
+            Some("a=1; d=4".to_owned())
+        );
+    }
+
+    #[test]
+    fn expired_cookies_are_removed() {
+        let jar = jar_with(
+            "http://localhost/",
+            &[
+                "a=1",
+                "b=2; Max-Age=3600; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+                "c=3; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+            ],
+        );
+        assert_eq!(
+            cookies_for(&jar, "http://localhost/"),
+            Some("a=1; b=2".to_owned())
+        );
+
+        let mut headers = Headers::new();
+        headers.set(SetCookie(vec![
+            "a=deleted; Max-Age=0".to_owned(),
+            "b=3".to_owned(),
+        ]));
+        jar.store(&"http://localhost/".parse().unwrap(), &headers);
+        assert_eq!(
+            cookies_for(&jar, "http://localhost/"),
+            Some("b=3".to_owned())
+        );
+    }
+
+    #[test]
+    fn handle_cookies_take_precedence() {
+        let jar = jar_with("http://localhost/", &["a=1", "b=2"]);
+        let mut headers = Headers::new();
+        let mut cookie = CookieHeader::new();
+        cookie.append("a", "set");
+        headers.set(cookie);
+        jar.add_to(&"http://localhost/".parse().unwrap(), &mut headers);
+        assert_eq!(
+            headers.get::<CookieHeader>().unwrap().to_string(),
+            "a=set; b=2"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use hyper::{StatusCode, Uri};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+use {Error, HttpResponseMeta, Result};
+
+/// Describes a finished request, passed to the callback registered with
+/// [`HttpTransportBuilder::inspector`](struct.HttpTransportBuilder.html#method.inspector).
+///
+/// The bodies are borrowed from the request being processed, so they are not copied unless the
+/// callback does it.
+#[derive(Debug)]
+pub struct RequestEvent<'a> {
+    /// The URI the request was sent to.
+    pub uri: &'a Uri,
+    /// The JSON-RPC request sent to the server.
+    pub request_body: &'a [u8],
+    /// The body of the response, or the error the request failed with. The body is empty for
+    /// notifications sent with `HttpHandle::notify`, since it is never read.
+    pub response: ::std::result::Result<&'a [u8], &'a Error>,
+    /// The status code of the last response, also if it was not accepted. `None` if the request
+    /// failed before a response arrived, for example with a timeout.
+    pub status: Option<StatusCode>,
+    /// When the request was handed to the `HttpHandle`.
+    pub queued_at: Instant,
+    /// When the event loop started sending the request. If the request was retried, this is
+    /// when the first attempt was sent.
+    pub sent_at: Instant,
+    /// When the request succeeded or failed for the last time.
+    pub completed_at: Instant,
+}
+
+/// The callback registered with `HttpTransportBuilder::inspector`.
+#[derive(Clone)]
+pub(crate) struct RequestInspector(pub Arc<Fn(&RequestEvent) + Send + Sync>);
+
+impl RequestInspector {
+    /// Calls the callback with an event for a request completed just now.
+    pub fn inspect(
+        &self,
+        uri: &Uri,
+        request_body: &[u8],
+        response: &Result<HttpResponseMeta>,
+        queued_at: Instant,
+        sent_at: Instant,
+    ) {
+        let (response, status) = match *response {
+            Ok(ref response) => (Ok(&response.body[..]), Some(response.status)),
+            Err(ref error) => (Err(error), error.http_status()),
+        };
+        (self.0)(&RequestEvent {
+            uri,
+            request_body,
+            response,
+            status,
+            queued_at,
+            sent_at,
+            completed_at: Instant::now(),
+        });
+    }
+}
+
+impl fmt::Debug for RequestInspector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RequestInspector")
+    }
+}
@@ -0,0 +1,119 @@
+use hyper::Uri;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use {HttpHandle, HttpTransport, Result};
+
+/// Creates handles to a `HttpTransport`, like `HttpTransport::handle`, but parses every URI only
+/// once. Returned from
+/// [`HttpTransport::handle_factory`](struct.HttpTransport.html#method.handle_factory).
+///
+/// All handles share the default headers of the transport until their headers are changed, so
+/// creating a handle for a URI seen before only clones a few reference counted values. Clones of
+/// the factory share the parsed URIs.
+#[derive(Debug, Clone)]
+pub struct HandleFactory {
+    transport: HttpTransport,
+    uris: Arc<Mutex<HashMap<String, Uri>>>,
+}
+
+impl HandleFactory {
+    pub(crate) fn new(transport: HttpTransport) -> Self {
+        HandleFactory {
+            transport,
+            uris: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns a handle valid for the given URI, just like `HttpTransport::handle` would. Fails
+    /// if the URI is not valid.
+    pub fn handle(&self, uri: &str) -> Result<HttpHandle> {
+        Ok(self.transport.handle_for_uri(self.parse(uri)?))
+    }
+
+    /// Returns how many different URIs have been parsed by this factory and its clones.
+    pub fn cached_uris(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Forgets all parsed URIs, to free their memory.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn parse(&self, uri: &str) -> Result<Uri> {
+        if let Some(parsed) = self.lock().get(uri) {
+            return Ok(parsed.clone());
+        }
+        let parsed = Uri::from_str(uri)?;
+        self.lock().insert(uri.to_owned(), parsed.clone());
+        Ok(parsed)
+    }
+
+    fn lock<'a>(&'a self) -> ::std::sync::MutexGuard<'a, HashMap<String, Uri>> {
+        self.uris.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use header;
+    use std::thread;
+
+    #[test]
+    fn parses_every_uri_once() {
+        let transport = HttpTransport::new().standalone().unwrap();
+        let factory = transport.handle_factory();
+        factory.handle("http://localhost/a").unwrap();
+        factory.clone().handle("http://localhost/a").unwrap();
+        let handle = factory.handle("http://localhost/b").unwrap();
+        assert_eq!(handle.uri.to_string(), "http://localhost/b");
+        assert_eq!(factory.cached_uris(), 2);
+        assert!(factory.handle("http://[::1").is_err());
+        assert_eq!(factory.cached_uris(), 2);
+
+        factory.clear();
+        assert_eq!(factory.cached_uris(), 0);
+    }
+
+    #[test]
+    fn handles_share_default_headers_until_changed() {
+        let transport = HttpTransport::new()
+            .default_header(header::UserAgent::new("test"))
+            .standalone()
+            .unwrap();
+        let factory = transport.handle_factory();
+        let shared = factory.handle("http://localhost").unwrap();
+        let mut changed = factory.handle("http://localhost").unwrap();
+        assert!(Arc::ptr_eq(&shared.headers.0, &changed.headers.0));
+
+        changed.set_header(header::Accept::json());
+        assert!(!Arc::ptr_eq(&shared.headers.0, &changed.headers.0));
+        assert_eq!(shared.headers().len(), 1);
+        assert_eq!(changed.headers().len(), 2);
+    }
+
+    #[test]
+    fn handles_sharing_headers_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        let transport = HttpTransport::new()
+            .default_header(header::UserAgent::new("test"))
+            .standalone()
+            .unwrap();
+        let handle = transport.handle("http://localhost").unwrap();
+        assert_send_sync(&handle);
+
+        let headers = handle.headers();
+        let mut changed = handle.clone();
+        changed.set_header(header::Accept::json());
+        assert_eq!(headers.len(), 1);
+
+        let reader = handle.clone();
+        let user_agent =
+            thread::spawn(move || reader.headers().get::<header::UserAgent>().cloned())
+                .join()
+                .unwrap();
+        assert_eq!(user_agent, Some(header::UserAgent::new("test")));
+    }
+}
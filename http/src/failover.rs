@@ -0,0 +1,228 @@
+use hyper::{self, StatusCode, Uri};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use {Error, ErrorKind, Result};
+
+/// The status codes failing over to the next endpoint, unless configured with
+/// `HttpTransportBuilder::failover_statuses`.
+pub(crate) const DEFAULT_STATUSES: [StatusCode; 3] = [
+    StatusCode::BadGateway,
+    StatusCode::ServiceUnavailable,
+    StatusCode::GatewayTimeout,
+];
+
+/// The endpoints of a handle created with `HttpTransport::handle_with_fallbacks`. The primary
+/// endpoint is shared between the clones of the handle.
+#[derive(Debug, Clone)]
+pub(crate) struct Endpoints {
+    pub uris: Vec<Uri>,
+    primary: Arc<AtomicUsize>,
+}
+
+impl Endpoints {
+    pub fn new(uris: Vec<Uri>) -> Self {
+        Endpoints {
+            uris,
+            primary: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns the state of a new request, and the URI to send it to first.
+    pub fn start(&self) -> (Failover, Uri) {
+        let first = self.primary.load(Ordering::SeqCst) % self.uris.len();
+        let failover = Failover {
+            endpoints: self.clone(),
+            first,
+            tried: 0,
+            failures: Vec::new(),
+        };
+        (failover, self.uris[first].clone())
+    }
+}
+
+/// Tracks which endpoints a request has been sent to so far.
+#[derive(Debug)]
+pub(crate) struct Failover {
+    endpoints: Endpoints,
+    /// The index of the endpoint the request was sent to first.
+    first: usize,
+    /// How many endpoints have failed so far.
+    tried: usize,
+    failures: Vec<(Uri, Error)>,
+}
+
+/// What to do after a request to one endpoint completed.
+pub(crate) enum Step<T> {
+    /// Send the request again, to this endpoint.
+    Next(Uri),
+    /// Done, with the final result of the request.
+    Done(Result<T>),
+}
+
+impl Failover {
+    /// Decides what to do with the result of sending the request to the current endpoint.
+    /// Failures for which `fail_over` is true move on to the next endpoint, until all have been
+    /// tried. The endpoint of a successful request becomes the new primary endpoint.
+    pub fn step<T>(&mut self, result: Result<T>, fail_over: bool) -> Step<T> {
+        let current = (self.first + self.tried) % self.endpoints.uris.len();
+        let error = match result {
+            Ok(response) => {
+                self.endpoints.primary.store(current, Ordering::SeqCst);
+                return Step::Done(Ok(response));
+            }
+            Err(ref error) if !fail_over => {
+                debug!("Not failing over after error: {}", error);
+                return Step::Done(result);
+            }
+            Err(error) => error,
+        };
+        let uri = self.endpoints.uris[current].clone();
+        self.tried += 1;
+        if self.tried == self.endpoints.uris.len() {
+            let mut failures = self.failures.drain(..).collect::<Vec<_>>();
+            failures.push((uri, error));
+            return Step::Done(Err(ErrorKind::AllEndpointsFailed(failures).into()));
+        }
+        let next = self.endpoints.uris[(current + 1) % self.endpoints.uris.len()].clone();
+        warn!(
+            "Request to {} failed, trying {} instead: {}",
+            uri, next, error
+        );
+        self.failures.push((uri, error));
+        Step::Next(next)
+    }
+}
+
+/// Returns if a request failing with `error` should be sent to the next endpoint. That is the
+/// case for timeouts, connection errors and open circuits, and for the given status codes.
+pub(crate) fn should_fail_over(error: &Error, statuses: &[StatusCode]) -> bool {
+    match *error.kind() {
+        ErrorKind::RequestTimeout(..)
+        | ErrorKind::ConnectTimeout(..)
+        | ErrorKind::ResponseTimeout(..) => true,
+        ErrorKind::HttpError(status, _) | ErrorKind::HttpErrorWithBody(status, _, _) => {
+            statuses.contains(&status)
+        }
+        ErrorKind::ConnectionFailed(..) | ErrorKind::CircuitOpen(..) => true,
+        ErrorKind::Hyper(hyper::Error::Io(ref io_error)) => {
+            io_error.kind() != io::ErrorKind::InvalidInput
+        }
+        ErrorKind::Hyper(hyper::Error::Incomplete)
+        | ErrorKind::Hyper(hyper::Error::Closed)
+        | ErrorKind::Hyper(hyper::Error::Cancel(_)) => true,
+        _ => false,
+    }
+}
+
+/// Lists the endpoints tried and their errors, for the message of `AllEndpointsFailed`.
+pub(crate) fn describe(failures: &[(Uri, Error)]) -> String {
+    failures
+        .iter()
+        .map(|failure| format!("{} ({})", failure.0, failure.1))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use RequestContext;
+
+    fn endpoints() -> Endpoints {
+        let uris = ["http://a/", "http://b/", "http://c/"];
+        Endpoints::new(uris.iter().map(|uri| uri.parse().unwrap()).collect())
+    }
+
+    fn timeout() -> Result<()> {
+        let second = Duration::from_secs(1);
+        Err(ErrorKind::RequestTimeout(second, second, RequestContext::default()).into())
+    }
+
+    #[test]
+    fn fails_over_in_order() {
+        let endpoints = endpoints();
+        let (mut failover, first) = endpoints.start();
+        assert_eq!(first, "http://a/");
+        match failover.step(timeout(), true) {
+            Step::Next(uri) => assert_eq!(uri, "http://b/"),
+            Step::Done(_) => panic!("did not fail over"),
+        }
+        match failover.step(timeout(), true) {
+            Step::Next(uri) => assert_eq!(uri, "http://c/"),
+            Step::Done(_) => panic!("did not fail over"),
+        }
+        match failover.step(timeout(), true) {
+            Step::Done(Err(error)) => match *error.kind() {
+                ErrorKind::AllEndpointsFailed(ref failures) => {
+                    let uris: Vec<_> = failures.iter().map(|f| f.0.to_string()).collect();
+                    assert_eq!(uris, ["http://a/", "http://b/", "http://c/"]);
+                }
+                ref kind => panic!("unexpected error: {}", kind),
+            },
+            _ => panic!("did not give up"),
+        }
+    }
+
+    #[test]
+    fn remembers_working_endpoint() {
+        let endpoints = endpoints();
+        let (mut failover, _) = endpoints.start();
+        failover.step(timeout(), true);
+        if let Step::Next(_) = failover.step(Ok(()), true) {
+            panic!("failed over after success");
+        }
+        let (mut failover, first) = endpoints.start();
+        assert_eq!(first, "http://b/");
+        failover.step(timeout(), true);
+        match failover.step(timeout(), true) {
+            Step::Next(uri) => assert_eq!(uri, "http://a/"),
+            Step::Done(_) => panic!("did not fail over"),
+        }
+    }
+
+    #[test]
+    fn other_errors_do_not_fail_over() {
+        let (mut failover, _) = endpoints().start();
+        let error = ErrorKind::HttpError(StatusCode::BadRequest, RequestContext::default());
+        let error = Err(error.into());
+        if let Step::Next(_) = failover.step::<()>(error, false) {
+            panic!("failed over after other error");
+        }
+    }
+
+    #[test]
+    fn failover_errors() {
+        let statuses = [StatusCode::ServiceUnavailable];
+        let fails_over = |kind: ErrorKind| should_fail_over(&kind.into(), &statuses);
+        let http_error = |status| ErrorKind::HttpError(status, RequestContext::default());
+        let second = Duration::from_secs(1);
+        assert!(fails_over(ErrorKind::RequestTimeout(
+            second,
+            second,
+            RequestContext::default()
+        )));
+        assert!(fails_over(ErrorKind::ResponseTimeout(
+            second,
+            second,
+            RequestContext::default()
+        )));
+        assert!(fails_over(http_error(StatusCode::ServiceUnavailable)));
+        assert!(!fails_over(http_error(StatusCode::BadGateway)));
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+        assert!(fails_over(ErrorKind::Hyper(hyper::Error::Io(refused))));
+        assert!(fails_over(ErrorKind::ConnectionFailed(
+            io::ErrorKind::ConnectionRefused,
+            RequestContext::default()
+        )));
+        assert!(fails_over(ErrorKind::CircuitOpen(
+            "http://localhost".to_owned(),
+            second
+        )));
+        assert!(!fails_over(ErrorKind::ResponseTooLarge(
+            10,
+            RequestContext::default()
+        )));
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Tracks if the event loop of a transport is still processing requests, and how many requests
+/// are pending. Shared between a transport, all its handles and the event loop.
+#[derive(Clone)]
+pub(crate) struct Health(Arc<Inner>);
+
+struct Inner {
+    pending: AtomicUsize,
+    state: Mutex<State>,
+}
+
+struct State {
+    alive: bool,
+    death_callbacks: Vec<Box<FnMut() + Send>>,
+}
+
+impl Health {
+    pub(crate) fn new() -> Self {
+        Health(Arc::new(Inner {
+            pending: AtomicUsize::new(0),
+            state: Mutex::new(State {
+                alive: true,
+                death_callbacks: Vec::new(),
+            }),
+        }))
+    }
+
+    pub(crate) fn is_alive(&self) -> bool {
+        self.with_state(|state| state.alive)
+    }
+
+    pub(crate) fn pending_requests(&self) -> usize {
+        self.0.pending.load(Ordering::SeqCst)
+    }
+
+    /// Counts one more request as pending, until the returned value is dropped.
+    pub(crate) fn track_request(&self) -> PendingRequest {
+        self.0.pending.fetch_add(1, Ordering::SeqCst);
+        PendingRequest(self.clone())
+    }
+
+    /// Calls `callback` when the event loop stops, or right away if it already has.
+    pub(crate) fn on_death<F: FnOnce() + Send + 'static>(&self, callback: F) {
+        let mut callback = Some(callback);
+        let callback = move || {
+            if let Some(callback) = callback.take() {
+                callback()
+            }
+        };
+        let dead_callback = self.with_state(move |state| {
+            if state.alive {
+                state.death_callbacks.push(Box::new(callback));
+                None
+            } else {
+                Some(callback)
+            }
+        });
+        if let Some(mut callback) = dead_callback {
+            callback();
+        }
+    }
+
+    /// Returns a guard to move into the future processing the requests. The event loop counts
+    /// as dead when it is dropped, whether the future completed or was dropped unfinished.
+    pub(crate) fn life_guard(&self) -> LifeGuard {
+        LifeGuard(self.clone())
+    }
+
+    /// Runs `f` with the state locked.
+    fn with_state<T, F: FnOnce(&mut State) -> T>(&self, f: F) -> T {
+        // The state is never left inconsistent, so a poisoned lock is still safe to use.
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut state)
+    }
+}
+
+impl fmt::Debug for Health {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Health")
+            .field("alive", &self.is_alive())
+            .field("pending_requests", &self.pending_requests())
+            .finish()
+    }
+}
+
+/// Counts a request as pending until dropped.
+#[derive(Debug)]
+pub(crate) struct PendingRequest(Health);
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        (self.0).0.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Marks the event loop as dead and calls the death callbacks when dropped.
+#[derive(Debug)]
+pub(crate) struct LifeGuard(Health);
+
+impl Drop for LifeGuard {
+    fn drop(&mut self) {
+        debug!("The event loop of the transport stopped processing requests");
+        // Called without the lock held, so the callbacks can use the transport.
+        let callbacks = self.0.with_state(|state| {
+            state.alive = false;
+            state.death_callbacks.drain(..).collect::<Vec<_>>()
+        });
+        for mut callback in callbacks {
+            callback();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_requests() {
+        let health = Health::new();
+        let first = health.track_request();
+        let second = health.track_request();
+        assert_eq!(health.pending_requests(), 2);
+        drop(first);
+        assert_eq!(health.pending_requests(), 1);
+        drop(second);
+        assert_eq!(health.pending_requests(), 0);
+    }
+
+    #[test]
+    fn death_callbacks() {
+        let health = Health::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let guard = health.life_guard();
+        let early_calls = calls.clone();
+        health.on_death(move || {
+            early_calls.fetch_add(1, Ordering::SeqCst);
+        });
+        assert!(health.is_alive());
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        drop(guard);
+        assert!(!health.is_alive());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let late_calls = calls.clone();
+        health.on_death(move || {
+            late_calls.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
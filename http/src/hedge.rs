@@ -0,0 +1,167 @@
+use futures::{Async, Future, Poll};
+use hyper::client::{Client, Connect, FutureResponse};
+use hyper::{self, Body, Request, Uri};
+use metrics::MetricsSink;
+use std::time::Duration;
+use tokio_core::reactor::{Handle, Timeout};
+use {from_hyper_error, AcceptStatus, Error};
+
+/// When to send duplicates of a request, see `HttpTransportBuilder::hedge`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HedgePolicy {
+    pub after: Duration,
+    pub max_extra: usize,
+}
+
+/// Sends a request, and duplicates of it whenever it has been waiting for a response for another
+/// `HedgePolicy::after`. Completes with the first response with an accepted status, and drops
+/// the other requests, which aborts them.
+///
+/// A response with a status that is not accepted is only returned once all requests have
+/// completed, unless another request gets an accepted response. No more duplicates are sent
+/// after all requests sent so far have failed, the first error is returned then.
+pub(crate) struct Hedged<C> {
+    client: Client<C, Body>,
+    uri: Uri,
+    in_flight: Vec<FutureResponse>,
+    /// Builds the next duplicate once it is due.
+    duplicate: Box<FnMut() -> Request>,
+    /// How many more duplicates may be sent.
+    spares: usize,
+    next_hedge: Option<Timeout>,
+    after: Duration,
+    handle: Handle,
+    accept_status: AcceptStatus,
+    /// The first response with a status that is not accepted.
+    rejected: Option<hyper::Response>,
+    /// The first error of a request.
+    error: Option<Error>,
+    metrics: Option<MetricsSink>,
+}
+
+impl<C: Connect> Hedged<C> {
+    /// Sends `request` on `client` right away, with up to `policy.max_extra` duplicates built by
+    /// `duplicate` following after `policy.after` each.
+    pub fn new(
+        client: Client<C, Body>,
+        request: Request,
+        duplicate: Box<FnMut() -> Request>,
+        policy: HedgePolicy,
+        accept_status: AcceptStatus,
+        metrics: Option<MetricsSink>,
+        handle: &Handle,
+    ) -> Self {
+        let uri = request.uri().clone();
+        let in_flight = vec![client.request(request)];
+        let next_hedge = if policy.max_extra == 0 {
+            None
+        } else {
+            Some(hedge_timeout(policy.after, handle))
+        };
+        Hedged {
+            client,
+            uri,
+            in_flight,
+            duplicate,
+            spares: policy.max_extra,
+            next_hedge,
+            after: policy.after,
+            handle: handle.clone(),
+            accept_status,
+            rejected: None,
+            error: None,
+            metrics,
+        }
+    }
+
+    /// Polls the requests in flight, and returns the first response with an accepted status.
+    fn poll_in_flight(&mut self) -> Option<hyper::Response> {
+        let mut i = 0;
+        while i < self.in_flight.len() {
+            let result = match self.in_flight[i].poll() {
+                Ok(Async::NotReady) => {
+                    i += 1;
+                    continue;
+                }
+                Ok(Async::Ready(response)) => Ok(response),
+                Err(error) => Err(error),
+            };
+            let _ = self.in_flight.swap_remove(i);
+            match result {
+                Ok(response) => {
+                    if self.accept_status.accepts(response.status()) {
+                        return Some(response);
+                    }
+                    if self.rejected.is_none() {
+                        self.rejected = Some(response);
+                    }
+                }
+                Err(error) => {
+                    if self.error.is_none() {
+                        self.error = Some(from_hyper_error(error));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<C: Connect> Future for Hedged<C> {
+    type Item = hyper::Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<hyper::Response, Error> {
+        loop {
+            if let Some(response) = self.poll_in_flight() {
+                if !self.in_flight.is_empty() {
+                    debug!(
+                        "Aborting {} slower duplicates of the request to {}",
+                        self.in_flight.len(),
+                        self.uri
+                    );
+                }
+                return Ok(Async::Ready(response));
+            }
+            if self.in_flight.is_empty() {
+                return match self.rejected.take() {
+                    Some(response) => Ok(Async::Ready(response)),
+                    None => Err(self
+                        .error
+                        .take()
+                        .expect("Hedged request failed without error")),
+                };
+            }
+            let hedge_due = match self.next_hedge {
+                Some(ref mut timeout) => match timeout.poll() {
+                    Ok(Async::NotReady) => false,
+                    // A failing timer ends the wait just like an expired one.
+                    _ => true,
+                },
+                None => false,
+            };
+            if !hedge_due {
+                return Ok(Async::NotReady);
+            }
+            self.spares -= 1;
+            let request = (self.duplicate)();
+            info!(
+                "Sending a duplicate of the request to {}, no response within {:?}",
+                self.uri, self.after
+            );
+            if let Some(ref metrics) = self.metrics {
+                (metrics.0).record_hedge(&self.uri);
+            }
+            self.in_flight.push(self.client.request(request));
+            self.next_hedge = if self.spares == 0 {
+                None
+            } else {
+                Some(hedge_timeout(self.after, &self.handle))
+            };
+        }
+    }
+}
+
+fn hedge_timeout(after: Duration, handle: &Handle) -> Timeout {
+    Timeout::new(after, handle).expect("failure to create Timeout for hedging")
+}
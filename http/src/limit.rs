@@ -0,0 +1,164 @@
+use futures::sync::oneshot;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// What happens to a request sent when the maximum number of pending requests, set with
+/// `HttpTransportBuilder::max_pending_requests`, is already reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadPolicy {
+    /// Wait until another request completes before queuing the request. Requests waiting like
+    /// this are queued in the order they were sent. This is the default.
+    Wait,
+    /// Fail the request right away with a `TransportOverloaded` error.
+    Fail,
+}
+
+/// Limits the number of requests that are queued on, or being processed by, the event loop at
+/// the same time. Shared between a transport and all its handles.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingLimit(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    max: usize,
+    policy: OverloadPolicy,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    pending: usize,
+    waiters: VecDeque<oneshot::Sender<Permit>>,
+}
+
+/// The result of `PendingLimit::acquire`.
+pub(crate) enum Acquire {
+    Acquired(Permit),
+    /// The limit is reached and the policy is to wait. The permit is sent on the channel when
+    /// another request completes.
+    Wait(oneshot::Receiver<Permit>),
+    /// The limit is reached and the policy is to fail.
+    Overloaded,
+}
+
+impl PendingLimit {
+    pub(crate) fn new(max: usize, policy: OverloadPolicy) -> Self {
+        PendingLimit(Arc::new(Inner {
+            max,
+            policy,
+            state: Mutex::new(State {
+                pending: 0,
+                waiters: VecDeque::new(),
+            }),
+        }))
+    }
+
+    /// Tries to count one more request as pending.
+    pub(crate) fn acquire(&self) -> Acquire {
+        self.with_state(|state| {
+            if state.pending < self.0.max {
+                state.pending += 1;
+                return Acquire::Acquired(self.permit());
+            }
+            match self.0.policy {
+                OverloadPolicy::Wait => {
+                    let (permit_tx, permit_rx) = oneshot::channel();
+                    state.waiters.push_back(permit_tx);
+                    Acquire::Wait(permit_rx)
+                }
+                OverloadPolicy::Fail => Acquire::Overloaded,
+            }
+        })
+    }
+
+    fn permit(&self) -> Permit {
+        Permit(Some(self.clone()))
+    }
+
+    /// Runs `f` with the state locked.
+    fn with_state<T, F: FnOnce(&mut State) -> T>(&self, f: F) -> T {
+        // The state is never left inconsistent, so a poisoned lock is still safe to use.
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut state)
+    }
+}
+
+/// Counts a request as pending until dropped. Then hands itself over to the first request still
+/// waiting, if any.
+#[derive(Debug)]
+pub(crate) struct Permit(Option<PendingLimit>);
+
+impl Permit {
+    /// Returns a permit not counting against any limit.
+    pub(crate) fn unlimited() -> Self {
+        Permit(None)
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let limit = match self.0.take() {
+            Some(limit) => limit,
+            None => return,
+        };
+        limit.with_state(|state| {
+            while let Some(waiter) = state.waiters.pop_front() {
+                match waiter.send(limit.permit()) {
+                    Ok(()) => return,
+                    // The request stopped waiting. Make sure the returned permit does nothing
+                    // when dropped, since the lock is already held.
+                    Err(mut permit) => permit.0 = None,
+                }
+            }
+            state.pending -= 1;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Future;
+
+    fn acquired(acquire: Acquire) -> Permit {
+        match acquire {
+            Acquire::Acquired(permit) => permit,
+            _ => panic!("permit not acquired"),
+        }
+    }
+
+    #[test]
+    fn fail_when_full() {
+        let limit = PendingLimit::new(2, OverloadPolicy::Fail);
+        let permit1 = acquired(limit.acquire());
+        let _permit2 = acquired(limit.acquire());
+        if let Acquire::Acquired(_) = limit.acquire() {
+            panic!("permit acquired over the limit");
+        }
+        drop(permit1);
+        acquired(limit.acquire());
+    }
+
+    #[test]
+    fn permit_is_handed_to_waiters_in_order() {
+        let limit = PendingLimit::new(1, OverloadPolicy::Wait);
+        let permit = acquired(limit.acquire());
+        let waiters: Vec<_> = (0..3)
+            .map(|_| match limit.acquire() {
+                Acquire::Wait(permit_rx) => permit_rx,
+                _ => panic!("not waiting"),
+            })
+            .collect();
+        let mut waiters = waiters.into_iter();
+
+        // The first waiter gives up, so the permit goes to the second one.
+        drop(waiters.next());
+        drop(permit);
+        let permit = waiters.next().unwrap().wait().unwrap();
+        drop(permit);
+        let permit = waiters.next().unwrap().wait().unwrap();
+        drop(permit);
+
+        assert_eq!(limit.with_state(|state| state.pending), 0);
+    }
+}
@@ -0,0 +1,320 @@
+use hyper::{StatusCode, Uri};
+use std::fmt;
+use std::str;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use {ErrorKind, HttpResponseMeta, Result};
+
+/// Receives a record of every request processed by a `HttpTransport`. Register an
+/// implementation with
+/// [`HttpTransportBuilder::metrics`](struct.HttpTransportBuilder.html#method.metrics).
+///
+/// The methods are called on the event loop, so they should return quickly.
+/// [`InMemoryMetrics`](struct.InMemoryMetrics.html) is a simple implementation keeping counters
+/// and a latency histogram.
+pub trait TransportMetrics: Send + Sync {
+    /// Records a completed request. `method_hint` is the name of the JSON-RPC method called, if
+    /// it could be found in the request body. For batches it is the method of the first call.
+    /// `duration` is the time from when the request was first sent until it completed,
+    /// including any retries.
+    fn record_request(
+        &self,
+        uri: &Uri,
+        method_hint: Option<&str>,
+        duration: Duration,
+        outcome: RequestOutcome,
+    );
+
+    /// Records that a duplicate of a request to `uri` was sent, because it had not completed
+    /// within the delay set with `HttpTransportBuilder::hedge`. The request itself is still
+    /// recorded once with `record_request`. Does nothing by default.
+    fn record_hedge(&self, _uri: &Uri) {}
+}
+
+/// How a request recorded by `TransportMetrics` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestOutcome {
+    /// A response was received and handed to the caller.
+    Success,
+    /// The request failed with an `HttpError` or `HttpErrorWithBody` for the given status code.
+    HttpError(StatusCode),
+    /// The request failed with a `RequestTimeout` or `ResponseTimeout` error.
+    Timeout,
+    /// The request failed with any other error.
+    Failed,
+    /// The caller stopped waiting for the result, or canceled the request with
+    /// `CallHandle::cancel`. The request is aborted, unless it had already completed.
+    Dropped,
+}
+
+impl RequestOutcome {
+    pub(crate) fn from_result(result: &Result<HttpResponseMeta>) -> Self {
+        match *result {
+            Ok(_) => RequestOutcome::Success,
+            Err(ref error) => match *error.kind() {
+                ErrorKind::HttpError(status, _) | ErrorKind::HttpErrorWithBody(status, _, _) => {
+                    RequestOutcome::HttpError(status)
+                }
+                ErrorKind::RequestTimeout(..) | ErrorKind::ResponseTimeout(..) => {
+                    RequestOutcome::Timeout
+                }
+                _ => RequestOutcome::Failed,
+            },
+        }
+    }
+}
+
+/// The upper bounds of the latency histogram buckets of `InMemoryMetrics`, in milliseconds.
+/// Requests slower than the last bound end up in one more bucket.
+const LATENCY_BUCKETS_MS: [u64; 12] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10_000];
+
+/// A `TransportMetrics` implementation counting the requests by outcome and keeping a
+/// histogram of their latency. Poll it with [`snapshot`](#method.snapshot), for example to
+/// export the numbers to a monitoring system.
+///
+/// ```rust
+/// # extern crate jsonrpc_client_http;
+/// # use jsonrpc_client_http::{HttpTransport, InMemoryMetrics};
+/// # use std::sync::Arc;
+/// # fn main() {
+/// let metrics = Arc::new(InMemoryMetrics::new());
+/// let transport = HttpTransport::new()
+///     .metrics(metrics.clone())
+///     .standalone()
+///     .unwrap();
+/// // ... send requests ...
+/// println!("{} requests sent", metrics.snapshot().requests);
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    snapshot: Mutex<MetricsSnapshot>,
+}
+
+impl InMemoryMetrics {
+    /// Returns an instance where all counts are zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the counts recorded so far.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        match self.snapshot.lock() {
+            Ok(snapshot) => snapshot.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
+
+impl TransportMetrics for InMemoryMetrics {
+    fn record_request(
+        &self,
+        _uri: &Uri,
+        _method_hint: Option<&str>,
+        duration: Duration,
+        outcome: RequestOutcome,
+    ) {
+        let mut snapshot = match self.snapshot.lock() {
+            Ok(snapshot) => snapshot,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        snapshot.record(duration, outcome);
+    }
+
+    fn record_hedge(&self, _uri: &Uri) {
+        let mut snapshot = match self.snapshot.lock() {
+            Ok(snapshot) => snapshot,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        snapshot.hedges += 1;
+    }
+}
+
+/// The counts recorded by `InMemoryMetrics`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// All completed requests.
+    pub requests: u64,
+    /// Requests with the outcome `RequestOutcome::Success`.
+    pub successes: u64,
+    /// Requests with the outcome `RequestOutcome::HttpError`.
+    pub http_errors: u64,
+    /// Requests with the outcome `RequestOutcome::Timeout`.
+    pub timeouts: u64,
+    /// Requests with the outcome `RequestOutcome::Failed`.
+    pub failures: u64,
+    /// Requests with the outcome `RequestOutcome::Dropped`.
+    pub dropped: u64,
+    /// Duplicate requests sent by hedging, see `TransportMetrics::record_hedge`.
+    pub hedges: u64,
+    /// The latency histogram, as pairs of the upper bound of a bucket and the number of requests
+    /// in it. Each request is only counted in the first bucket it fits in. The last bucket has
+    /// no upper bound.
+    pub latency: Vec<(Option<Duration>, u64)>,
+}
+
+impl MetricsSnapshot {
+    fn record(&mut self, duration: Duration, outcome: RequestOutcome) {
+        self.requests += 1;
+        let counter = match outcome {
+            RequestOutcome::Success => &mut self.successes,
+            RequestOutcome::HttpError(_) => &mut self.http_errors,
+            RequestOutcome::Timeout => &mut self.timeouts,
+            RequestOutcome::Failed => &mut self.failures,
+            RequestOutcome::Dropped => &mut self.dropped,
+        };
+        *counter += 1;
+        let bucket = self
+            .latency
+            .iter_mut()
+            .find(|&&mut (bound, _)| match bound {
+                Some(bound) => duration <= bound,
+                None => true,
+            });
+        if let Some(&mut (_, ref mut count)) = bucket {
+            *count += 1;
+        }
+    }
+}
+
+impl Default for MetricsSnapshot {
+    fn default() -> Self {
+        let bounds = LATENCY_BUCKETS_MS
+            .iter()
+            .map(|&millis| Some(Duration::from_millis(millis)));
+        MetricsSnapshot {
+            requests: 0,
+            successes: 0,
+            http_errors: 0,
+            timeouts: 0,
+            failures: 0,
+            dropped: 0,
+            hedges: 0,
+            latency: bounds.chain(Some(None)).map(|bound| (bound, 0)).collect(),
+        }
+    }
+}
+
+/// The sink set with `HttpTransportBuilder::metrics`.
+#[derive(Clone)]
+pub(crate) struct MetricsSink(pub Arc<TransportMetrics>);
+
+impl fmt::Debug for MetricsSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("MetricsSink")
+    }
+}
+
+/// Finds the value of the first `"method"` member in a JSON-RPC request body, without parsing
+/// the whole body. Returns `None` if there is none, or if it contains escape sequences.
+pub(crate) fn method_hint(body: &[u8]) -> Option<&str> {
+    let key = b"\"method\"";
+    let key_end = body.windows(key.len()).position(|window| window == key)? + key.len();
+    let rest = skip_whitespace(&body[key_end..]);
+    if rest.first() != Some(&b':') {
+        return None;
+    }
+    let rest = skip_whitespace(&rest[1..]);
+    if rest.first() != Some(&b'"') {
+        return None;
+    }
+    let value = &rest[1..];
+    let value_end = value
+        .iter()
+        .position(|&byte| byte == b'"' || byte == b'\\')?;
+    if value[value_end] != b'"' {
+        return None;
+    }
+    str::from_utf8(&value[..value_end]).ok()
+}
+
+/// Finds the value of the first `"id"` member in a JSON-RPC request body, without parsing the
+/// whole body. Returns the JSON of the value, including the quotes of a string id, or `None` if
+/// there is no id.
+pub(crate) fn id_hint(body: &[u8]) -> Option<&str> {
+    let key = b"\"id\"";
+    let key_end = body.windows(key.len()).position(|window| window == key)? + key.len();
+    let rest = skip_whitespace(&body[key_end..]);
+    if rest.first() != Some(&b':') {
+        return None;
+    }
+    let value = skip_whitespace(&rest[1..]);
+    let value_end = value
+        .iter()
+        .position(|byte| b",}] \t\r\n".contains(byte))
+        .unwrap_or(value.len());
+    match value_end {
+        0 => None,
+        _ => str::from_utf8(&value[..value_end]).ok(),
+    }
+}
+
+pub(crate) fn skip_whitespace(data: &[u8]) -> &[u8] {
+    let start = data
+        .iter()
+        .position(|byte| !b" \t\r\n".contains(byte))
+        .unwrap_or(data.len());
+    &data[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn method_hints() {
+        let hint = method_hint(br#"{"jsonrpc": "2.0", "method" : "to_upper", "id": 1}"#);
+        assert_eq!(hint, Some("to_upper"));
+        let hint = method_hint(br#"[{"method":"first","id":1},{"method":"second","id":2}]"#);
+        assert_eq!(hint, Some("first"));
+        assert_eq!(method_hint(br#"{"method": "esc\"aped"}"#), None);
+        assert_eq!(method_hint(br#"{"method": 5}"#), None);
+        assert_eq!(method_hint(br#"{"params": []}"#), None);
+        assert_eq!(method_hint(br#"{"method": "unterminated"#), None);
+    }
+
+    #[test]
+    fn id_hints() {
+        assert_eq!(id_hint(br#"{"method": "a", "id": 17}"#), Some("17"));
+        assert_eq!(
+            id_hint(br#"{"id" : "abc-1", "method": "a"}"#),
+            Some("\"abc-1\"")
+        );
+        assert_eq!(id_hint(br#"[{"id":1},{"id":2}]"#), Some("1"));
+        assert_eq!(id_hint(br#"{"method": "note"}"#), None);
+        assert_eq!(id_hint(br#"{"id": }"#), None);
+    }
+
+    #[test]
+    fn snapshot_counts() {
+        let metrics = InMemoryMetrics::new();
+        let uri = "http://localhost".parse().unwrap();
+        let record = |millis, outcome| {
+            metrics.record_request(&uri, None, Duration::from_millis(millis), outcome)
+        };
+        record(3, RequestOutcome::Success);
+        record(5, RequestOutcome::Success);
+        record(60_000, RequestOutcome::Timeout);
+        record(20, RequestOutcome::HttpError(StatusCode::BadGateway));
+        metrics.record_hedge(&uri);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.requests, 4);
+        assert_eq!(snapshot.successes, 2);
+        assert_eq!(snapshot.timeouts, 1);
+        assert_eq!(snapshot.http_errors, 1);
+        assert_eq!(snapshot.failures + snapshot.dropped, 0);
+        assert_eq!(snapshot.hedges, 1);
+        assert_eq!(snapshot.latency[1], (Some(Duration::from_millis(5)), 2));
+        assert_eq!(snapshot.latency[3], (Some(Duration::from_millis(25)), 1));
+        assert_eq!(snapshot.latency.last(), Some(&(None, 1)));
+        assert_eq!(
+            snapshot
+                .latency
+                .iter()
+                .map(|&(_, count)| count)
+                .sum::<u64>(),
+            4
+        );
+    }
+}
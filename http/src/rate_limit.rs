@@ -0,0 +1,77 @@
+use futures::future::{self, Either, FutureResult};
+use retry::{duration_to_secs, secs_to_duration};
+use std::cmp;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_core::reactor::{Handle, Timeout};
+
+/// A token bucket limiting the rate requests are sent at, see `HttpTransportBuilder::rate_limit`.
+/// Clones share the bucket.
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// The tokens in the bucket at `updated`. Negative when requests waiting for tokens have
+    /// reserved them ahead of time.
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        let burst = f64::from(cmp::max(burst, 1));
+        RateLimiter {
+            per_second: f64::from(cmp::max(per_second, 1)),
+            burst,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: burst,
+                updated: Instant::now(),
+            })),
+        }
+    }
+
+    /// Takes a token for sending a request, and returns how long to wait until the token is
+    /// available. Waiting requests reserve the tokens in the order they call this.
+    pub fn reserve(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let refilled = duration_to_secs(now - bucket.updated) * self.per_second;
+        bucket.tokens = (bucket.tokens + refilled).min(self.burst) - 1.0;
+        bucket.updated = now;
+        secs_to_duration(-bucket.tokens / self.per_second)
+    }
+}
+
+/// Returns a future completing after `delay` on the event loop of `handle`, or right away if the
+/// delay is zero.
+pub(crate) fn wait(
+    delay: Duration,
+    handle: &Handle,
+) -> Either<FutureResult<(), io::Error>, Timeout> {
+    if delay == Duration::from_secs(0) {
+        return Either::A(future::ok(()));
+    }
+    Either::B(Timeout::new(delay, handle).expect("failure to create Timeout for rate limit"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_then_rate() {
+        let limiter = RateLimiter::new(10, 3);
+        for _ in 0..3 {
+            assert_eq!(limiter.reserve(), Duration::from_secs(0));
+        }
+        let waits = [limiter.clone().reserve(), limiter.reserve()];
+        assert!(waits[0] > Duration::from_millis(90) && waits[0] <= Duration::from_millis(100));
+        assert!(waits[1] > Duration::from_millis(190) && waits[1] <= Duration::from_millis(200));
+    }
+}
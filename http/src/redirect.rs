@@ -0,0 +1,189 @@
+use hyper::header::Location;
+use hyper::{self, Method, StatusCode, Uri};
+
+/// Which hosts redirects are followed to, see
+/// [`redirect_policy`](struct.HttpTransportBuilder.html#method.redirect_policy) on the builder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectPolicy {
+    /// Follow redirects to any host. The default.
+    AnyHost,
+    /// Only follow redirects to the host the redirect came from, on any port. Redirects to other
+    /// hosts are not followed.
+    SameHost,
+}
+
+/// Where a request is sent after following a redirect, instead of to the URI of its handle.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RedirectTarget {
+    pub method: Method,
+    pub uri: Uri,
+}
+
+/// The redirects followed by a request so far.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Redirects {
+    /// How many redirects have been followed.
+    pub followed: u8,
+    /// Where the last redirect pointed, or `None` if no redirect has been followed.
+    pub target: Option<RedirectTarget>,
+}
+
+/// Returns where to send the request instead, if the response is a redirect that can be
+/// followed. `base` is the URI the request was sent to, and `method` the method of the handle.
+///
+/// 303 See Other is followed with a GET request, as required by RFC 7231. All other redirects
+/// are followed with the same method, since a JSON-RPC call can not be sent as a GET.
+pub(crate) fn target(
+    base: &Uri,
+    method: &Method,
+    response: &hyper::Response,
+) -> Option<RedirectTarget> {
+    let method = match response.status() {
+        StatusCode::SeeOther => Method::Get,
+        StatusCode::MovedPermanently
+        | StatusCode::Found
+        | StatusCode::TemporaryRedirect
+        | StatusCode::PermanentRedirect => method.clone(),
+        _ => return None,
+    };
+    let location = response.headers().get::<Location>()?;
+    let uri = resolve(base, location)?;
+    Some(RedirectTarget { method, uri })
+}
+
+/// Returns if a redirect from `base` to `target` may be followed. Redirects to other hosts are
+/// only followed when `same_host_only` is not set, and redirects to `https` URIs only when
+/// `https_supported` is set, since they would fail to connect otherwise.
+pub(crate) fn allowed(
+    base: &Uri,
+    target: &Uri,
+    same_host_only: bool,
+    https_supported: bool,
+) -> bool {
+    if target.scheme() == Some("https") && !https_supported {
+        debug!(
+            "Not following redirect to {}, https is not supported",
+            target
+        );
+        return false;
+    }
+    let same_host = match (base.host(), target.host()) {
+        (Some(base_host), Some(target_host)) => base_host.eq_ignore_ascii_case(target_host),
+        _ => false,
+    };
+    if same_host_only && !same_host {
+        debug!(
+            "Not following redirect from {} to another host, {}",
+            base, target
+        );
+        return false;
+    }
+    true
+}
+
+/// Resolves the value of a `Location` header against the URI of the request it was a response
+/// to. Fragments are not supported, since they are never sent to the server anyway.
+fn resolve(base: &Uri, location: &str) -> Option<Uri> {
+    let location = match location.find('#') {
+        Some(index) => &location[..index],
+        None => location,
+    };
+    if let Ok(uri) = location.parse::<Uri>() {
+        if uri.is_absolute() {
+            return Some(uri);
+        }
+    }
+    let scheme = base.scheme()?;
+    let authority = base.authority()?;
+    let resolved = if location.starts_with("//") {
+        format!("{}:{}", scheme, location)
+    } else if location.starts_with('/') {
+        format!("{}://{}{}", scheme, authority, location)
+    } else if location.is_empty() {
+        return Some(base.clone());
+    } else if location.starts_with('?') {
+        format!("{}://{}{}{}", scheme, authority, base.path(), location)
+    } else {
+        let path = base.path();
+        let directory = &path[..path.rfind('/').map(|index| index + 1).unwrap_or(0)];
+        format!("{}://{}{}{}", scheme, authority, directory, location)
+    };
+    resolved.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::Location;
+    use hyper::Response;
+
+    #[test]
+    fn resolve_locations() {
+        let base: Uri = "http://example.com:8080/api/rpc?key=1".parse().unwrap();
+        let resolve = |location| resolve(&base, location).map(|uri| uri.to_string());
+        assert_eq!(
+            resolve("https://other.com/rpc"),
+            Some("https://other.com/rpc".to_owned())
+        );
+        assert_eq!(
+            resolve("//other.com/rpc"),
+            Some("http://other.com/rpc".to_owned())
+        );
+        assert_eq!(
+            resolve("/v2/rpc"),
+            Some("http://example.com:8080/v2/rpc".to_owned())
+        );
+        assert_eq!(
+            resolve("v2"),
+            Some("http://example.com:8080/api/v2".to_owned())
+        );
+        assert_eq!(
+            resolve("?key=2"),
+            Some("http://example.com:8080/api/rpc?key=2".to_owned())
+        );
+        assert_eq!(
+            resolve("/rpc#part"),
+            Some("http://example.com:8080/rpc".to_owned())
+        );
+        assert_eq!(resolve(""), Some(base.to_string()));
+    }
+
+    #[test]
+    fn redirect_methods() {
+        let base: Uri = "http://example.com/".parse().unwrap();
+        let target = |status| {
+            let response = Response::new()
+                .with_status(status)
+                .with_header(Location::new("/new"));
+            target(&base, &Method::Put, &response).map(|target| target.method)
+        };
+        assert_eq!(target(StatusCode::MovedPermanently), Some(Method::Put));
+        assert_eq!(target(StatusCode::Found), Some(Method::Put));
+        assert_eq!(target(StatusCode::SeeOther), Some(Method::Get));
+        assert_eq!(target(StatusCode::TemporaryRedirect), Some(Method::Put));
+        assert_eq!(target(StatusCode::PermanentRedirect), Some(Method::Put));
+        assert_eq!(target(StatusCode::NotModified), None);
+        assert_eq!(target(StatusCode::Ok), None);
+
+        let response = Response::new().with_status(StatusCode::Found);
+        assert_eq!(super::target(&base, &Method::Post, &response), None);
+    }
+
+    #[test]
+    fn allowed_redirects() {
+        let base: Uri = "http://example.com/rpc".parse().unwrap();
+        let allowed = |target: &str, same_host_only, https_supported| {
+            allowed(
+                &base,
+                &target.parse().unwrap(),
+                same_host_only,
+                https_supported,
+            )
+        };
+        assert!(allowed("http://other.com/", false, false));
+        assert!(!allowed("http://other.com/", true, true));
+        assert!(allowed("http://EXAMPLE.com:8080/", true, false));
+        assert!(!allowed("https://example.com/", true, false));
+        assert!(allowed("https://example.com/", true, true));
+    }
+}
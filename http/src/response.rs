@@ -0,0 +1,392 @@
+use futures::future;
+use futures::{Future, Stream};
+use hyper::{self, header, StatusCode, Uri};
+use std::cell::Cell;
+use tokio_core::reactor::Handle;
+
+use {compression, metrics, AcceptStatus, Error, ErrorKind, HttpResponseMeta, Result};
+
+/// The largest body of a response with an unaccepted status code that is passed on to the RPC
+/// client.
+const MAX_ERROR_BODY_SIZE: usize = 1024 * 1024;
+
+impl Error {
+    /// Returns the status code of the response if this is an `HttpError` or an
+    /// `HttpErrorWithBody`.
+    pub fn http_status(&self) -> Option<StatusCode> {
+        match *self.kind() {
+            ErrorKind::HttpError(status, _) | ErrorKind::HttpErrorWithBody(status, _, _) => {
+                Some(status)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns which request failed, if this is an error of a kind containing a
+    /// `RequestContext`.
+    pub fn context(&self) -> Option<&RequestContext> {
+        match *self.kind() {
+            ErrorKind::HttpError(_, ref context)
+            | ErrorKind::HttpErrorWithBody(_, _, ref context)
+            | ErrorKind::RequestTimeout(_, _, ref context)
+            | ErrorKind::ConnectTimeout(_, _, ref context)
+            | ErrorKind::ResponseTimeout(_, _, ref context)
+            | ErrorKind::ConnectionFailed(_, ref context)
+            | ErrorKind::ResponseTooLarge(_, ref context)
+            | ErrorKind::UnsupportedContentEncoding(_, ref context)
+            | ErrorKind::UnexpectedContentType(_, _, ref context)
+            | ErrorKind::InvalidCompressedBody(_, ref context) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Returns the id of the JSON-RPC request that failed, as JSON, if this is an error of a kind
+    /// containing a `RequestContext` and the request has an id. String ids include their quotes.
+    pub fn request_id(&self) -> Option<&str> {
+        self.context().and_then(RequestContext::request_id)
+    }
+
+    /// Sets the URI a request was sent to, and the method and id of the JSON-RPC request in
+    /// `body`, on errors of the kinds containing a `RequestContext`. Errors of other kinds are
+    /// returned as they are.
+    pub(crate) fn with_context(mut self, uri: &Uri, body: &[u8]) -> Self {
+        match self.0 {
+            ErrorKind::HttpError(_, ref mut context)
+            | ErrorKind::HttpErrorWithBody(_, _, ref mut context)
+            | ErrorKind::RequestTimeout(_, _, ref mut context)
+            | ErrorKind::ConnectTimeout(_, _, ref mut context)
+            | ErrorKind::ResponseTimeout(_, _, ref mut context)
+            | ErrorKind::ConnectionFailed(_, ref mut context)
+            | ErrorKind::ResponseTooLarge(_, ref mut context)
+            | ErrorKind::UnsupportedContentEncoding(_, ref mut context)
+            | ErrorKind::UnexpectedContentType(_, _, ref mut context)
+            | ErrorKind::InvalidCompressedBody(_, ref mut context) => {
+                *context = RequestContext::new(uri, body);
+            }
+            _ => (),
+        }
+        self
+    }
+}
+
+/// Which request an error is about, for the error kinds containing one: the URI it was sent to, and the method and id of the JSON-RPC request. Errors of
+/// batches have no method or id, since they have no single one.
+///
+/// The URI and the method are part of the error messages, like in `Http error. Status code 502
+/// Bad Gateway calling "eth_getLogs" on https://node1.example.com/rpc (request id 42)`. The
+/// query of the URI is left out of messages, since it often carries API keys.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestContext {
+    // Boxed, as the context is part of many error kinds and would make every `Error` large.
+    parts: Box<ContextParts>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ContextParts {
+    uri: Option<Uri>,
+    method: Option<String>,
+    request_id: Option<String>,
+}
+
+impl RequestContext {
+    pub(crate) fn new(uri: &Uri, body: &[u8]) -> Self {
+        let batch = metrics::skip_whitespace(body).first() == Some(&b'[');
+        let hint = |hint: Option<&str>| if batch { None } else { hint.map(str::to_owned) };
+        let parts = ContextParts {
+            uri: Some(uri.clone()),
+            method: hint(metrics::method_hint(body)),
+            request_id: hint(metrics::id_hint(body)),
+        };
+        RequestContext {
+            parts: Box::new(parts),
+        }
+    }
+
+    /// Returns the URI the request was sent to, when it is known.
+    pub fn uri(&self) -> Option<&Uri> {
+        self.parts.uri.as_ref()
+    }
+
+    /// Returns the name of the JSON-RPC method that was called, when it is known.
+    pub fn method(&self) -> Option<&str> {
+        self.parts.method.as_ref().map(|method| &method[..])
+    }
+
+    /// Returns the id of the JSON-RPC request as JSON, when it is known. String ids include
+    /// their quotes.
+    pub fn request_id(&self) -> Option<&str> {
+        self.parts.request_id.as_ref().map(|id| &id[..])
+    }
+
+    /// Describes the request for the message of an error kind.
+    pub(crate) fn suffix(&self) -> String {
+        let mut suffix = String::new();
+        if let Some(ref method) = self.parts.method {
+            suffix.push_str(&format!(" calling \"{}\"", method));
+        }
+        if let Some(ref uri) = self.parts.uri {
+            let scheme = uri
+                .scheme()
+                .map(|scheme| format!("{}://", scheme))
+                .unwrap_or_default();
+            let authority = uri.authority().unwrap_or("");
+            suffix.push_str(&format!(" on {}{}{}", scheme, authority, uri.path()));
+        }
+        if let Some(ref id) = self.parts.request_id {
+            suffix.push_str(&format!(" (request id {})", id));
+        }
+        suffix
+    }
+}
+
+/// How the body of a response is read, set up from the settings of the transport and the
+/// request.
+pub(crate) struct BodyReader {
+    pub accept_status: AcceptStatus,
+    pub accept_non_200_bodies: bool,
+    /// The content type of the request, if the content type of responses is checked.
+    pub check_content_type: Option<header::ContentType>,
+    pub max_response_size: Option<usize>,
+    pub decompress: bool,
+    /// Set for notifications, whose response body is drained without being passed on.
+    pub discard: bool,
+}
+
+impl BodyReader {
+    /// Reads the body of `response` with `read_response`, or with `discard_response` if the body
+    /// is discarded, and returns it together with the status code and headers.
+    pub fn read(
+        &self,
+        response: hyper::Response,
+        body_started: &Cell<bool>,
+        handle: &Handle,
+    ) -> Box<Future<Item = HttpResponseMeta, Error = Error>> {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = if self.discard {
+            discard_response(response, &self.accept_status, body_started, handle)
+        } else {
+            read_response(
+                response,
+                &self.accept_status,
+                self.accept_non_200_bodies,
+                self.check_content_type.clone(),
+                self.max_response_size,
+                self.decompress,
+                body_started,
+            )
+        };
+        Box::new(body.map(move |body| HttpResponseMeta {
+            status,
+            headers,
+            body,
+        }))
+    }
+}
+
+/// Reads the body of a response. Responses with a status code that is not accepted fail as
+/// described for `read_error_body`, unless `accept_non_200_bodies` is set and they have a
+/// non-empty JSON body of at most `MAX_ERROR_BODY_SIZE` bytes. Accepted bodies larger than
+/// `max_response_size` fail with a `ResponseTooLarge` error. If `decompress` is set, the body is
+/// decoded according to its `Content-Encoding`. If `check_content_type` is set to the content type
+/// of the request, accepted responses must be JSON or have that content type, see `expect_json`.
+/// Sets `body_started` if the body is read.
+fn read_response(
+    response: hyper::Response,
+    accept_status: &AcceptStatus,
+    accept_non_200_bodies: bool,
+    check_content_type: Option<header::ContentType>,
+    max_response_size: Option<usize>,
+    decompress: bool,
+    body_started: &Cell<bool>,
+) -> Box<Future<Item = Vec<u8>, Error = Error>> {
+    let status = response.status();
+    let encodings = content_encodings(&response, decompress);
+    if accept_status.accepts(status) {
+        body_started.set(true);
+        if let Some(max) = max_response_size {
+            match response.headers().get::<header::ContentLength>() {
+                Some(&header::ContentLength(length)) if length > max as u64 => {
+                    let kind = ErrorKind::ResponseTooLarge(max, RequestContext::default());
+                    return Box::new(future::err(kind.into()));
+                }
+                _ => (),
+            }
+        }
+        let content_type = response.headers().get::<header::ContentType>().cloned();
+        let body = response
+            .body()
+            .from_err()
+            .fold(Vec::new(), move |mut body, chunk| match max_response_size {
+                Some(max) if body.len() + chunk.len() > max => {
+                    let kind = ErrorKind::ResponseTooLarge(max, RequestContext::default());
+                    Err(Error::from(kind))
+                }
+                _ => {
+                    body.extend_from_slice(&chunk);
+                    Ok(body)
+                }
+            })
+            .and_then(move |body| compression::decode(&encodings, body, max_response_size))
+            .and_then(move |body| {
+                if let Some(ref sent) = check_content_type {
+                    expect_json(content_type.as_ref(), sent, body)
+                } else {
+                    Ok(body)
+                }
+            });
+        Box::new(body)
+    } else if accept_non_200_bodies && has_json_content_type(&response) {
+        body_started.set(true);
+        let body = response
+            .body()
+            .from_err()
+            .fold(Vec::new(), move |mut body, chunk| {
+                if body.len() + chunk.len() > MAX_ERROR_BODY_SIZE {
+                    let kind = ErrorKind::HttpError(status, RequestContext::default());
+                    return Err(Error::from(kind));
+                }
+                body.extend_from_slice(&chunk);
+                Ok(body)
+            })
+            .and_then(move |body| {
+                if body.is_empty() {
+                    Err(ErrorKind::HttpError(status, RequestContext::default()).into())
+                } else {
+                    compression::decode(&encodings, body, Some(MAX_ERROR_BODY_SIZE))
+                }
+            });
+        Box::new(body)
+    } else {
+        read_error_body(response, encodings)
+    }
+}
+
+/// Returns the content codings of the response body, or none if it should not be decoded.
+fn content_encodings(response: &hyper::Response, decompress: bool) -> Vec<header::Encoding> {
+    match response.headers().get::<header::ContentEncoding>() {
+        Some(content_encoding) if decompress => content_encoding.0.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Reads the body of a response with a status code that is not accepted, and fails with an
+/// `HttpErrorWithBody` if it is not empty and at most `MAX_ERROR_BODY_SIZE` bytes, or with an
+/// `HttpError` otherwise. The body is decoded with `encodings` if possible, and kept as it is
+/// otherwise. Errors while reading the body are ignored, since the status code is what decides
+/// if the request is retried.
+fn read_error_body(
+    response: hyper::Response,
+    encodings: Vec<header::Encoding>,
+) -> Box<Future<Item = Vec<u8>, Error = Error>> {
+    let status = response.status();
+    match response.headers().get::<header::ContentLength>() {
+        Some(&header::ContentLength(length)) if length > MAX_ERROR_BODY_SIZE as u64 => {
+            let kind = ErrorKind::HttpError(status, RequestContext::default());
+            return Box::new(future::err(kind.into()));
+        }
+        _ => (),
+    }
+    let body = response
+        .body()
+        .fold(Vec::new(), move |mut body, chunk| {
+            if body.len() + chunk.len() > MAX_ERROR_BODY_SIZE {
+                return Err(hyper::Error::TooLarge);
+            }
+            body.extend_from_slice(&chunk);
+            Ok(body)
+        })
+        .then(move |result| {
+            let kind = match result {
+                Ok(ref body) if body.is_empty() => {
+                    ErrorKind::HttpError(status, RequestContext::default())
+                }
+                Ok(body) => {
+                    if encodings.is_empty() {
+                        ErrorKind::HttpErrorWithBody(status, body, RequestContext::default())
+                    } else {
+                        let decoded = compression::decode(
+                            &encodings,
+                            body.clone(),
+                            Some(MAX_ERROR_BODY_SIZE),
+                        );
+                        let body = decoded.unwrap_or(body);
+                        ErrorKind::HttpErrorWithBody(status, body, RequestContext::default())
+                    }
+                }
+                Err(_) => ErrorKind::HttpError(status, RequestContext::default()),
+            };
+            Err(kind.into())
+        });
+    Box::new(body)
+}
+
+/// Like `read_response`, but the body of an accepted response is drained in the background,
+/// instead of being waited for, and an empty body is returned right away. The body of responses
+/// with other status codes is never passed on.
+fn discard_response(
+    response: hyper::Response,
+    accept_status: &AcceptStatus,
+    body_started: &Cell<bool>,
+    handle: &Handle,
+) -> Box<Future<Item = Vec<u8>, Error = Error>> {
+    let status = response.status();
+    if accept_status.accepts(status) {
+        body_started.set(true);
+        // Reading the whole body lets Hyper reuse the connection.
+        handle.spawn(response.body().for_each(|_| Ok(())).then(|_| Ok(())));
+        Box::new(future::ok(Vec::new()))
+    } else {
+        Box::new(future::err(
+            ErrorKind::HttpError(status, RequestContext::default()).into(),
+        ))
+    }
+}
+
+fn has_json_content_type(response: &hyper::Response) -> bool {
+    match response.headers().get::<header::ContentType>() {
+        Some(content_type) => is_json(content_type),
+        None => false,
+    }
+}
+
+/// Returns if the content type is `application/json` or `application/json-rpc`, with any
+/// parameters.
+fn is_json(content_type: &header::ContentType) -> bool {
+    content_type.type_() == hyper::mime::APPLICATION
+        && (content_type.subtype() == hyper::mime::JSON
+            || content_type.subtype().as_str() == "json-rpc")
+}
+
+/// Returns if the content types have the same type and subtype, ignoring parameters.
+fn same_media_type(a: &header::ContentType, b: &header::ContentType) -> bool {
+    a.type_() == b.type_() && a.subtype() == b.subtype()
+}
+
+/// How much of the body an `UnexpectedContentType` error keeps.
+const UNEXPECTED_BODY_PREFIX: usize = 256;
+
+/// Passes on the body of a successful response if its content type is JSON or the type and
+/// subtype of `sent`, the content type of the request, or if there is no content type and the
+/// body is empty or starts like a JSON object or array. Fails with an `UnexpectedContentType`
+/// error otherwise.
+fn expect_json(
+    content_type: Option<&header::ContentType>,
+    sent: &header::ContentType,
+    body: Vec<u8>,
+) -> Result<Vec<u8>> {
+    let json = match content_type {
+        Some(content_type) => is_json(content_type) || same_media_type(content_type, sent),
+        None => match body.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(&byte) => byte == b'{' || byte == b'[',
+            None => true,
+        },
+    };
+    if json {
+        return Ok(body);
+    }
+    let content_type = content_type.map(ToString::to_string).unwrap_or_default();
+    let mut body = body;
+    body.truncate(UNEXPECTED_BODY_PREFIX);
+    Err(ErrorKind::UnexpectedContentType(content_type, body, RequestContext::default()).into())
+}
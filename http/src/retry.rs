@@ -0,0 +1,344 @@
+use hyper::StatusCode;
+use rand;
+use std::cmp;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use {Error, ErrorKind};
+
+/// Policy deciding if, and how, a failed request is sent again by the `HttpTransport`.
+///
+/// Retries happen on the event loop, so the caller of the RPC only sees the result of the last
+/// attempt. The exact same request body is sent in every attempt, meaning the JSON-RPC request
+/// id stays the same across retries.
+///
+/// The default policy never retries. Only enable retries for RPC methods that are safe to call
+/// more than once.
+///
+/// # Example
+///
+/// ```rust
+/// # use jsonrpc_client_http::RetryPolicy;
+/// # use std::time::Duration;
+/// // Wait a random time of up to 100, 200, 400 and 800 ms between five attempts, but give up
+/// // if the whole thing takes longer than two seconds.
+/// let policy = RetryPolicy::exponential(Duration::from_millis(100), 2.0, Duration::from_secs(1))
+///     .max_attempts(5)
+///     .jitter(true)
+///     .deadline(Duration::from_secs(2));
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Backoff,
+    jitter: bool,
+    deadline: Option<Duration>,
+    retryable: Arc<Fn(&Error) -> bool + Send + Sync>,
+}
+
+/// How the delay between attempts changes with each attempt.
+#[derive(Debug, Clone, Copy)]
+enum Backoff {
+    Fixed(Duration),
+    Exponential {
+        base: Duration,
+        factor: f64,
+        max: Duration,
+    },
+}
+
+impl RetryPolicy {
+    /// Returns a policy that never retries a failed request.
+    pub fn never() -> Self {
+        Self::new(1, Duration::from_secs(0))
+    }
+
+    /// Returns a policy making at most `max_attempts` attempts in total, waiting `delay` between
+    /// each of them. One attempt is always made, even if `max_attempts` is zero.
+    ///
+    /// Only errors for which [`is_transient`](#method.is_transient) returns true are retried. Use
+    /// [`retry_if`](#method.retry_if) to change that.
+    pub fn new(max_attempts: u32, delay: Duration) -> Self {
+        RetryPolicy {
+            max_attempts,
+            backoff: Backoff::Fixed(delay),
+            jitter: false,
+            deadline: None,
+            retryable: Arc::new(Self::is_transient),
+        }
+    }
+
+    /// Returns a policy with exponential backoff. The delay before the first retry is `base`, and
+    /// every following delay is `factor` times longer than the previous one, but never longer
+    /// than `max`.
+    ///
+    /// Makes at most three attempts unless changed with [`max_attempts`](#method.max_attempts).
+    /// The same errors as for [`new`](#method.new) are retried.
+    pub fn exponential(base: Duration, factor: f64, max: Duration) -> Self {
+        RetryPolicy {
+            backoff: Backoff::Exponential { base, factor, max },
+            ..Self::new(3, base)
+        }
+    }
+
+    /// Sets the maximum number of attempts in total, including the first one. One attempt is
+    /// always made, even if this is zero.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Enables or disables full jitter. With jitter enabled, the time waited before each retry is
+    /// a random duration between zero and the delay given by the backoff. This spreads out the
+    /// retries from many clients failing at the same time.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets a deadline for a request, including all of its retries and the delays between them,
+    /// measured from when the first attempt is sent. No retry is started if its delay would end
+    /// after the deadline, and an attempt still running at the deadline fails with a
+    /// `RequestTimeout` error. The request timeout of the transport works the same way.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Replaces the predicate deciding which errors are worth retrying. The default predicate is
+    /// [`is_transient`](#method.is_transient), which can be called from the new predicate to
+    /// extend it.
+    ///
+    /// ```rust
+    /// # use jsonrpc_client_http::{RetryPolicy, StatusCode};
+    /// # use std::time::Duration;
+    /// let policy = RetryPolicy::new(3, Duration::from_millis(100)).retry_if(|error| {
+    ///     error.http_status() == Some(StatusCode::TooManyRequests)
+    ///         || RetryPolicy::is_transient(error)
+    /// });
+    /// ```
+    pub fn retry_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&Error) -> bool + Send + Sync + 'static,
+    {
+        self.retryable = Arc::new(predicate);
+        self
+    }
+
+    /// The default retry predicate. Returns true for errors that are likely to go away by
+    /// themselves: timeouts, refused or reset connections and the HTTP status codes 502, 503 and
+    /// 504.
+    pub fn is_transient(error: &Error) -> bool {
+        match *error.kind() {
+            ErrorKind::RequestTimeout(..)
+            | ErrorKind::ConnectTimeout(..)
+            | ErrorKind::ResponseTimeout(..) => true,
+            ErrorKind::HttpError(status, _) | ErrorKind::HttpErrorWithBody(status, _, _) => {
+                status == StatusCode::BadGateway
+                    || status == StatusCode::ServiceUnavailable
+                    || status == StatusCode::GatewayTimeout
+            }
+            ErrorKind::ConnectionFailed(kind, _) => {
+                kind == io::ErrorKind::ConnectionRefused
+                    || kind == io::ErrorKind::ConnectionReset
+                    || kind == io::ErrorKind::ConnectionAborted
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns this policy with the deadline shortened to `timeout`, if that is shorter.
+    pub(crate) fn limit_deadline(&self, timeout: Option<Duration>) -> RetryPolicy {
+        let deadline = match (self.deadline, timeout) {
+            (Some(deadline), Some(timeout)) => Some(cmp::min(deadline, timeout)),
+            (deadline, timeout) => deadline.or(timeout),
+        };
+        RetryPolicy {
+            deadline,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the delay to wait before the next attempt, or `None` if the given error from the
+    /// given attempt (counting from one) should not be retried. `elapsed` is the time since the
+    /// first attempt was sent.
+    pub(crate) fn retry_delay(
+        &self,
+        attempt: u32,
+        elapsed: Duration,
+        error: &Error,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts || !(self.retryable)(error) {
+            return None;
+        }
+        let delay = if self.jitter {
+            mul_duration(self.backoff_delay(attempt), rand::random::<f64>())
+        } else {
+            self.backoff_delay(attempt)
+        };
+        match self.remaining(elapsed) {
+            Some(remaining) if remaining <= delay => None,
+            _ => Some(delay),
+        }
+    }
+
+    /// Returns how much time is left until the deadline, or `None` if there is no deadline.
+    pub(crate) fn remaining(&self, elapsed: Duration) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.checked_sub(elapsed).unwrap_or_default())
+    }
+
+    /// The delay after the given attempt before any jitter is applied.
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { base, factor, max } => {
+                let delay = duration_to_secs(base) * factor.powf(f64::from(attempt - 1));
+                if delay.is_finite() && delay < duration_to_secs(max) {
+                    secs_to_duration(delay)
+                } else {
+                    max
+                }
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::never()
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("backoff", &self.backoff)
+            .field("jitter", &self.jitter)
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+fn mul_duration(duration: Duration, factor: f64) -> Duration {
+    secs_to_duration(duration_to_secs(duration) * factor)
+}
+
+pub(crate) fn duration_to_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0
+}
+
+pub(crate) fn secs_to_duration(secs: f64) -> Duration {
+    let secs = secs.max(0.0);
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1_000_000_000.0) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RequestContext;
+
+    fn error() -> Error {
+        let second = Duration::from_secs(1);
+        ErrorKind::RequestTimeout(second, second, RequestContext::default()).into()
+    }
+
+    #[test]
+    fn transient_errors() {
+        let transient = |kind: ErrorKind| RetryPolicy::is_transient(&kind.into());
+        let http_error = |status| ErrorKind::HttpError(status, RequestContext::default());
+        assert!(transient(ErrorKind::ConnectionFailed(
+            io::ErrorKind::ConnectionRefused,
+            RequestContext::default()
+        )));
+        assert!(!transient(ErrorKind::ConnectionFailed(
+            io::ErrorKind::UnexpectedEof,
+            RequestContext::default()
+        )));
+        assert!(transient(http_error(StatusCode::ServiceUnavailable)));
+        assert!(!transient(http_error(StatusCode::BadRequest)));
+    }
+
+    #[test]
+    fn exponential_backoff_is_capped() {
+        let policy =
+            RetryPolicy::exponential(Duration::from_millis(100), 3.0, Duration::from_secs(1))
+                .max_attempts(1_000_000);
+        let elapsed = Duration::from_secs(0);
+        let delays: Vec<_> = (1..6)
+            .map(|attempt| policy.retry_delay(attempt, elapsed, &error()).unwrap())
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(300),
+                Duration::from_millis(900),
+                Duration::from_secs(1),
+                Duration::from_secs(1),
+            ]
+        );
+        assert_eq!(
+            policy.retry_delay(999_999, elapsed, &error()),
+            Some(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn jitter_stays_below_backoff() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(100)).jitter(true);
+        for _ in 0..100 {
+            let delay = policy
+                .retry_delay(1, Duration::from_secs(0), &error())
+                .unwrap();
+            assert!(delay <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn no_retry_past_deadline() {
+        let policy =
+            RetryPolicy::new(10, Duration::from_millis(100)).deadline(Duration::from_millis(250));
+        assert!(policy
+            .retry_delay(1, Duration::from_millis(100), &error())
+            .is_some());
+        assert!(policy
+            .retry_delay(2, Duration::from_millis(200), &error())
+            .is_none());
+        assert_eq!(
+            policy.remaining(Duration::from_millis(300)),
+            Some(Duration::from_secs(0))
+        );
+    }
+
+    #[test]
+    fn timeout_shortens_deadline() {
+        let policy = RetryPolicy::never().deadline(Duration::from_secs(2));
+        let one_sec = Some(Duration::from_secs(1));
+        let two_secs = Some(Duration::from_secs(2));
+        assert_eq!(policy.limit_deadline(one_sec).deadline, one_sec);
+        assert_eq!(
+            policy.limit_deadline(Some(Duration::from_secs(3))).deadline,
+            two_secs
+        );
+        assert_eq!(policy.limit_deadline(None).deadline, two_secs);
+        assert_eq!(
+            RetryPolicy::never().limit_deadline(one_sec).deadline,
+            one_sec
+        );
+        assert_eq!(RetryPolicy::never().limit_deadline(None).deadline, None);
+    }
+
+    #[test]
+    fn stops_after_max_attempts() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(100));
+        let elapsed = Duration::from_secs(0);
+        assert!(policy.retry_delay(1, elapsed, &error()).is_some());
+        assert!(policy.retry_delay(2, elapsed, &error()).is_none());
+        assert!(RetryPolicy::never()
+            .retry_delay(1, elapsed, &error())
+            .is_none());
+    }
+}
@@ -0,0 +1,94 @@
+use futures::{Future, Poll};
+use hyper::{StatusCode, Uri};
+use std::fmt;
+use {HttpResponseMeta, Result};
+
+#[cfg(feature = "tracing")]
+use metrics;
+
+/// The `tracing` span of a request processed by the event loop, carrying the JSON-RPC request id
+/// and method and the host the request is sent to. Does nothing without the "tracing" feature.
+#[derive(Clone)]
+pub(crate) struct RequestSpan {
+    #[cfg(feature = "tracing")]
+    span: ::tracing::Span,
+}
+
+impl fmt::Debug for RequestSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RequestSpan")
+    }
+}
+
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+impl RequestSpan {
+    /// Creates the span of a request with the given body, sent to `uri`. The id and method are
+    /// found in the body like `metrics::method_hint` finds the method, and left empty for
+    /// streamed requests.
+    pub fn new(uri: &Uri, body: &[u8]) -> Self {
+        RequestSpan {
+            #[cfg(feature = "tracing")]
+            span: ::tracing::debug_span!(
+                "jsonrpc_request",
+                id = metrics::id_hint(body).unwrap_or(""),
+                method = metrics::method_hint(body).unwrap_or(""),
+                host = uri.host().unwrap_or(""),
+            ),
+        }
+    }
+
+    /// Records that an attempt of the request is sent to `uri`.
+    pub fn sent(&self, uri: &Uri, attempt: u32) {
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!(parent: &self.span, uri = %uri, attempt, "Sending request");
+    }
+
+    /// Records that a response with the given status code arrived.
+    pub fn response(&self, status: StatusCode) {
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!(parent: &self.span, status = %status, "Received response");
+    }
+
+    /// Records the final result of the request.
+    pub fn completed(&self, result: &Result<HttpResponseMeta>) {
+        #[cfg(feature = "tracing")]
+        {
+            match *result {
+                Ok(ref response) => ::tracing::debug!(
+                    parent: &self.span,
+                    size = response.body.len(),
+                    "Request succeeded"
+                ),
+                Err(ref error) => {
+                    ::tracing::warn!(parent: &self.span, error = %error, "Request failed")
+                }
+            }
+        }
+    }
+
+    /// Returns `future` running inside this span every time it is polled.
+    pub fn instrument<F: Future>(&self, future: F) -> Traced<F> {
+        Traced {
+            span: self.clone(),
+            future,
+        }
+    }
+}
+
+/// A future running inside a `RequestSpan`, see `RequestSpan::instrument`.
+pub(crate) struct Traced<F> {
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    span: RequestSpan,
+    future: F,
+}
+
+impl<F: Future> Future for Traced<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        #[cfg(feature = "tracing")]
+        let _entered = self.span.span.enter();
+        self.future.poll()
+    }
+}
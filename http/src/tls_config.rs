@@ -0,0 +1,241 @@
+use native_tls::{Certificate, Error, Pkcs12, TlsConnector, TlsConnectorBuilder};
+use std::fmt;
+
+/// Configuration of the TLS connections made by a transport created with
+/// [`HttpTransport::with_tls_config`](struct.HttpTransport.html#method.with_tls_config).
+///
+/// The certificates and identity are only parsed when the transport is built, and invalid data
+/// makes [`HttpTransportBuilder::standalone`] and [`HttpTransportBuilder::shared`] fail with a
+/// `ClientCreatorError`.
+///
+/// [`HttpTransportBuilder::standalone`]: struct.HttpTransportBuilder.html#method.standalone
+/// [`HttpTransportBuilder::shared`]: struct.HttpTransportBuilder.html#method.shared
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # extern crate jsonrpc_client_http;
+/// # use jsonrpc_client_http::{HttpTransport, TlsConfig};
+/// # use std::fs::File;
+/// # use std::io::Read;
+/// # fn read(path: &str) -> Vec<u8> {
+/// #     let mut data = Vec::new();
+/// #     File::open(path).unwrap().read_to_end(&mut data).unwrap();
+/// #     data
+/// # }
+/// # fn main() {
+/// let config = TlsConfig::new()
+///     .add_root_certificate_pem(&read("internal-ca.pem"))
+///     .identity_pkcs12(&read("client.p12"), "password");
+/// let transport = HttpTransport::with_tls_config(config).standalone().unwrap();
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+    root_certificates: Vec<RootCertificate>,
+    identity: Option<(Vec<u8>, String)>,
+    disable_hostname_verification: bool,
+    accept_invalid_certs: bool,
+}
+
+#[derive(Debug, Clone)]
+enum RootCertificate {
+    Pem(Vec<u8>),
+    Der(Vec<u8>),
+}
+
+impl TlsConfig {
+    /// Returns a configuration trusting the roots of the system trust store and nothing else,
+    /// like the one used by [`HttpTransport::with_tls`](struct.HttpTransport.html#method.with_tls).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust the PEM encoded root certificates in `pem`, in addition to the system trust
+    /// store. Every certificate in the data is added.
+    pub fn add_root_certificate_pem(mut self, pem: &[u8]) -> Self {
+        self.root_certificates
+            .push(RootCertificate::Pem(pem.to_vec()));
+        self
+    }
+
+    /// Trust the DER encoded root certificate in `der`, in addition to the system trust store.
+    pub fn add_root_certificate_der(mut self, der: &[u8]) -> Self {
+        self.root_certificates
+            .push(RootCertificate::Der(der.to_vec()));
+        self
+    }
+
+    /// Trust the root certificates in `der_or_pem`, in addition to the system trust store. The
+    /// data is treated as PEM, which can contain several certificates, if it has a PEM header,
+    /// and as a single DER encoded certificate otherwise.
+    pub fn add_root_certificate(self, der_or_pem: &[u8]) -> Self {
+        if is_pem(der_or_pem) {
+            self.add_root_certificate_pem(der_or_pem)
+        } else {
+            self.add_root_certificate_der(der_or_pem)
+        }
+    }
+
+    /// Present the client certificate and private key in the DER encoded PKCS #12 archive `der`
+    /// to servers asking for one, for mutual TLS. `password` decrypts the archive. Replaces any
+    /// identity set earlier.
+    pub fn identity_pkcs12(mut self, der: &[u8], password: &str) -> Self {
+        self.identity = Some((der.to_vec(), password.to_owned()));
+        self
+    }
+
+    /// Configure if the host name of servers should not be checked against their certificate.
+    ///
+    /// # Warning
+    ///
+    /// Only use this in test environments. Without the check any valid certificate is trusted
+    /// for any server, which makes the connections open to man-in-the-middle attacks.
+    pub fn danger_disable_hostname_verification(mut self, disable: bool) -> Self {
+        self.disable_hostname_verification = disable;
+        self
+    }
+
+    /// Configure if the certificates of servers should not be verified, so that any certificate
+    /// is accepted, including self-signed and expired ones. The host name is still checked
+    /// against the certificate unless disabled with
+    /// [`danger_disable_hostname_verification`](#method.danger_disable_hostname_verification).
+    ///
+    /// Only supported on platforms using OpenSSL, since native-tls has no setting for it. On
+    /// Windows and macOS an error is logged and certificates are still verified.
+    ///
+    /// # Warning
+    ///
+    /// Only use this in test environments. Without verification the connections are open to
+    /// man-in-the-middle attacks. A warning is logged when a connector is created with it.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    pub(crate) fn hostname_verification_disabled(&self) -> bool {
+        self.disable_hostname_verification
+    }
+
+    /// Creates the `TlsConnector` described by this configuration.
+    pub(crate) fn connector(&self) -> Result<TlsConnector, Error> {
+        let mut builder = TlsConnector::builder()?;
+        for root_certificate in &self.root_certificates {
+            match *root_certificate {
+                RootCertificate::Pem(ref pem) => {
+                    for certificate in split_pem(pem) {
+                        builder.add_root_certificate(Certificate::from_pem(certificate)?)?;
+                    }
+                }
+                RootCertificate::Der(ref der) => {
+                    builder.add_root_certificate(Certificate::from_der(der)?)?;
+                }
+            }
+        }
+        if let Some((ref der, ref password)) = self.identity {
+            builder.identity(Pkcs12::from_der(der, password)?)?;
+        }
+        if self.accept_invalid_certs {
+            warn!("TLS certificate verification is disabled, do not use this in production");
+            accept_invalid_certs(&mut builder);
+        }
+        builder.build()
+    }
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Leaves out the identity, since it contains a private key and its password.
+        f.debug_struct("TlsConfig")
+            .field("root_certificates", &self.root_certificates.len())
+            .field("identity", &self.identity.is_some())
+            .field(
+                "disable_hostname_verification",
+                &self.disable_hostname_verification,
+            )
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .finish()
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "ios")))]
+fn accept_invalid_certs(builder: &mut TlsConnectorBuilder) {
+    use native_tls::backend::openssl::TlsConnectorBuilderExt;
+    use openssl::ssl::SSL_VERIFY_NONE;
+    builder.builder_mut().set_verify(SSL_VERIFY_NONE);
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "ios"))]
+fn accept_invalid_certs(_builder: &mut TlsConnectorBuilder) {
+    error!("Accepting invalid TLS certificates is not supported on this platform");
+}
+
+/// Returns if `data` looks like PEM rather than DER. DER data always starts with a binary tag,
+/// while PEM is text with a header, possibly after some explanatory text.
+fn is_pem(data: &[u8]) -> bool {
+    const BEGIN: &[u8] = b"-----BEGIN ";
+    data.windows(BEGIN.len()).any(|window| window == BEGIN)
+}
+
+/// Splits PEM data into one slice per certificate, since `Certificate::from_pem` only parses
+/// one of them. Data without any certificate is returned as it is, for the parser to reject.
+fn split_pem(pem: &[u8]) -> Vec<&[u8]> {
+    const END: &[u8] = b"-----END CERTIFICATE-----";
+    let mut certificates = Vec::new();
+    let mut rest = pem;
+    while let Some(end) = rest.windows(END.len()).position(|window| window == END) {
+        let (certificate, remaining) = rest.split_at(end + END.len());
+        certificates.push(certificate);
+        rest = remaining;
+    }
+    if certificates.is_empty() {
+        certificates.push(pem);
+    }
+    certificates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_pem_bundle() {
+        let first = "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----";
+        let second = "\n-----BEGIN CERTIFICATE-----\nBBBB\n-----END CERTIFICATE-----";
+        let bundle = format!("{}{}\n", first, second);
+        assert_eq!(
+            split_pem(bundle.as_bytes()),
+            vec![first.as_bytes(), second.as_bytes()]
+        );
+        assert_eq!(split_pem(b"garbage"), vec![&b"garbage"[..]]);
+    }
+
+    #[test]
+    fn detect_pem() {
+        assert!(is_pem(
+            b"-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n"
+        ));
+        assert!(is_pem(
+            b"Subject: CN=Internal CA\n-----BEGIN CERTIFICATE-----\nAAAA\n"
+        ));
+        assert!(!is_pem(&[0x30, 0x82, 0x01, 0x0a]));
+        let config = TlsConfig::new()
+            .add_root_certificate(b"-----BEGIN CERTIFICATE-----\n")
+            .add_root_certificate(&[0x30, 0x82]);
+        match (&config.root_certificates[0], &config.root_certificates[1]) {
+            (&RootCertificate::Pem(_), &RootCertificate::Der(_)) => (),
+            certificates => panic!("unexpected certificates: {:?}", certificates),
+        }
+    }
+
+    #[test]
+    fn invalid_certificates_fail() {
+        assert!(TlsConfig::new().connector().is_ok());
+        let config = TlsConfig::new().add_root_certificate_pem(b"not a certificate");
+        assert!(config.connector().is_err());
+        let config = TlsConfig::new().add_root_certificate_der(b"not a certificate");
+        assert!(config.connector().is_err());
+        let config = TlsConfig::new().identity_pkcs12(b"not an identity", "password");
+        assert!(config.connector().is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use client_creator::ClientCreator;
+use futures::future::{self, FutureResult};
+use hyper::client::{Client, Service};
+use hyper::{Body, Uri};
+use std::error;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::net;
+use std::path::{Path, PathBuf};
+use std::str;
+use tokio_core::reactor::Handle;
+use tokio_uds::UnixStream;
+use ErrorKind;
+
+/// The URI scheme used for requests sent over unix sockets. The socket path is hex encoded into
+/// the host part of the URI.
+const UNIX_SCHEME: &str = "unix";
+
+/// `Client` creator for sending requests over unix domain sockets instead of TCP.
+///
+/// Used through [`HttpTransport::with_unix_socket`] and [`HttpTransport::unix_handle`].
+///
+/// [`HttpTransport::with_unix_socket`]: struct.HttpTransport.html#method.with_unix_socket
+/// [`HttpTransport::unix_handle`]: struct.HttpTransport.html#method.unix_handle
+#[derive(Debug, Default, Clone)]
+pub struct UnixClient;
+
+impl ClientCreator for UnixClient {
+    type Connect = UnixConnector;
+    type Error = io::Error;
+
+    fn create(&self, handle: &Handle) -> Result<Client<UnixConnector, Body>, io::Error> {
+        let connector = UnixConnector {
+            handle: handle.clone(),
+        };
+        Ok(Client::configure().connector(connector).build(handle))
+    }
+}
+
+/// Hyper connector connecting to the unix socket given in the URI created by
+/// `HttpTransport::unix_handle`.
+#[derive(Debug, Clone)]
+pub struct UnixConnector {
+    handle: Handle,
+}
+
+impl Service for UnixConnector {
+    type Request = Uri;
+    type Response = UnixStream;
+    type Error = io::Error;
+    type Future = FutureResult<UnixStream, io::Error>;
+
+    fn call(&self, uri: Uri) -> Self::Future {
+        let path = match socket_path(&uri) {
+            Some(path) => path,
+            None => {
+                return future::err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "invalid url, not created for a unix socket",
+                ))
+            }
+        };
+        trace!("Connecting to unix socket {}", path.display());
+        // Connecting to a unix socket does not block, so no need to do it asynchronously.
+        let result = net::UnixStream::connect(&path)
+            .and_then(|stream| UnixStream::from_std(stream, self.handle.new_tokio_handle()))
+            .map_err(|error| io::Error::new(error.kind(), SocketConnectError { path, error }));
+        future::result(result)
+    }
+}
+
+/// Creates the URI for sending requests to `request_path` over the unix socket at `socket_path`.
+pub(crate) fn socket_uri(socket_path: &Path, request_path: &str) -> String {
+    let host: String = socket_path
+        .as_os_str()
+        .as_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let separator = if request_path.starts_with('/') {
+        ""
+    } else {
+        "/"
+    };
+    format!("{}://{}{}{}", UNIX_SCHEME, host, separator, request_path)
+}
+
+/// Decodes the socket path from a URI created by `socket_uri`.
+fn socket_path(uri: &Uri) -> Option<PathBuf> {
+    if uri.scheme() != Some(UNIX_SCHEME) {
+        return None;
+    }
+    let host = uri.host()?;
+    let mut bytes = Vec::with_capacity(host.len() / 2);
+    for hex in host.as_bytes().chunks(2) {
+        let hex = str::from_utf8(hex).ok()?;
+        bytes.push(u8::from_str_radix(hex, 16).ok()?);
+    }
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+/// Returns the error kind for errors from a `UnixConnector` failing to connect to the socket.
+pub(crate) fn socket_error_kind(error: &io::Error) -> Option<ErrorKind> {
+    let connect_error = match error.get_ref() {
+        Some(inner) => inner.downcast_ref::<SocketConnectError>()?,
+        None => return None,
+    };
+    let path = connect_error.path.clone();
+    match connect_error.error.kind() {
+        io::ErrorKind::NotFound => Some(ErrorKind::SocketNotFound(path)),
+        io::ErrorKind::PermissionDenied => Some(ErrorKind::SocketPermissionDenied(path)),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+struct SocketConnectError {
+    path: PathBuf,
+    error: io::Error,
+}
+
+impl fmt::Display for SocketConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unable to connect to unix socket {}: {}",
+            self.path.display(),
+            self.error
+        )
+    }
+}
+
+impl error::Error for SocketConnectError {
+    fn description(&self) -> &str {
+        "Unable to connect to unix socket"
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_path_round_trip() {
+        let path = Path::new("/var/run/my daemon/rpc.sock");
+        let uri: Uri = socket_uri(path, "/api").parse().unwrap();
+        assert_eq!(uri.path(), "/api");
+        assert_eq!(socket_path(&uri), Some(path.to_owned()));
+
+        let uri: Uri = socket_uri(path, "api").parse().unwrap();
+        assert_eq!(uri.path(), "/api");
+        assert_eq!(socket_path(&"http://2f746d70/".parse().unwrap()), None);
+    }
+}
@@ -0,0 +1,144 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::ErrorKind;
+use jsonrpc_client_http::HttpTransport;
+
+fn requests(ids: &[u64]) -> Vec<Vec<u8>> {
+    ids.iter()
+        .map(|id| {
+            format!(
+                r#"{{"jsonrpc":"2.0","id":{},"method":"get","params":[]}}"#,
+                id
+            )
+        })
+        .map(String::into_bytes)
+        .collect()
+}
+
+fn text(response: &jsonrpc_client_core::Result<Vec<u8>>) -> String {
+    String::from_utf8(response.as_ref().unwrap().clone()).unwrap()
+}
+
+#[test]
+fn responses_out_of_order() {
+    let server = Server::spawn(
+        r#"[{"jsonrpc":"2.0","id":3,"result":"c"},
+            {"jsonrpc":"2.0","id":1,"result":"a"},
+            {"jsonrpc":"2.0","id":2,"result":"b"}]"#,
+    );
+    let transport = HttpTransport::new().standalone().unwrap();
+    let handle = transport.handle(&server.uri()).unwrap();
+
+    let responses = handle.send_batch(requests(&[1, 2, 3])).wait().unwrap();
+    assert_eq!(responses.len(), 3);
+    for (response, result) in responses.iter().zip(&["\"a\"", "\"b\"", "\"c\""]) {
+        assert!(text(response).contains(result), "{}", text(response));
+    }
+    let received = server.requests();
+    assert_eq!(received.len(), 1);
+    assert!(received[0].starts_with('['));
+    assert!(received[0].contains(r#""id":3"#));
+}
+
+#[test]
+fn missing_response_fails_single_request() {
+    let server = Server::spawn(r#"[{"jsonrpc":"2.0","id":2,"result":"b"}]"#);
+    let transport = HttpTransport::new().standalone().unwrap();
+    let handle = transport.handle(&server.uri()).unwrap();
+
+    let responses = handle.send_batch(requests(&[1, 2])).wait().unwrap();
+    match *responses[0].as_ref().unwrap_err().kind() {
+        ErrorKind::BatchError(_) => (),
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+    assert!(text(&responses[1]).contains("\"b\""));
+}
+
+#[test]
+fn single_error_answers_all_requests() {
+    let server = Server::spawn(
+        r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"Parse error"}}"#,
+    );
+    let transport = HttpTransport::new().standalone().unwrap();
+    let handle = transport.handle(&server.uri()).unwrap();
+
+    let responses = handle.send_batch(requests(&[1, 2])).wait().unwrap();
+    for (response, id) in responses.iter().zip(&[r#""id":1"#, r#""id":2"#]) {
+        assert!(text(response).contains("-32700"), "{}", text(response));
+        assert!(text(response).contains(id), "{}", text(response));
+    }
+}
+
+/// Service responding to every request with the same body, and recording the request bodies.
+#[derive(Clone)]
+struct FixedService {
+    response: &'static str,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl Service for FixedService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let response = self.response;
+        let requests = self.requests.clone();
+        Box::new(request.body().concat2().map(move |body| {
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            requests.lock().unwrap().push(body);
+            Response::new().with_body(response)
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    requests: Arc<Mutex<Vec<String>>>,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(response: &'static str) -> Self {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let service = FixedService {
+            response,
+            requests: requests.clone(),
+        };
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Server {
+            port,
+            requests,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,113 @@
+extern crate futures;
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+
+use std::io::Read;
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use futures::Future;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{ErrorKind, HttpTransport};
+
+#[test]
+fn cancel_aborts_request() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri)
+        .unwrap();
+
+    let (future, call_handle) = handle.send_cancelable(b"body".to_vec());
+    server.wait_for(Event::Received);
+    call_handle.cancel();
+    let error = future.wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::RequestCanceled => (),
+        _ => panic!("unexpected error: {}", error),
+    }
+    server.wait_for(Event::Closed);
+}
+
+#[test]
+fn dropping_future_aborts_request() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri)
+        .unwrap();
+
+    let future = handle.send(b"body".to_vec());
+    server.wait_for(Event::Received);
+    drop(future);
+    server.wait_for(Event::Closed);
+}
+
+#[test]
+fn cancel_after_response_does_nothing() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .timeout(Duration::from_millis(100))
+        .standalone()
+        .unwrap()
+        .handle(&server.uri)
+        .unwrap();
+
+    let (future, call_handle) = handle.send_cancelable(b"body".to_vec());
+    let error = future.wait().unwrap_err();
+    call_handle.cancel();
+    match *error.kind() {
+        ErrorKind::RequestTimeout(..) => (),
+        _ => panic!("unexpected error: {}", error),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Received,
+    Closed,
+}
+
+/// Server accepting one connection and never responding. Reports when it has received data, and
+/// when the connection is closed by the client.
+struct Server {
+    uri: String,
+    events: mpsc::Receiver<Event>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let (events_tx, events) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0; 1024];
+            let mut received = false;
+            loop {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) if !received => {
+                        received = true;
+                        let _ = events_tx.send(Event::Received);
+                    }
+                    Ok(_) => (),
+                }
+            }
+            let _ = events_tx.send(Event::Closed);
+        });
+
+        Server { uri, events }
+    }
+
+    fn wait_for(&self, event: Event) {
+        let received = self.events.recv_timeout(Duration::from_secs(1));
+        assert_eq!(received, Ok(event));
+    }
+}
@@ -0,0 +1,119 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate rmp_serde;
+#[macro_use]
+extern crate serde_json;
+extern crate tokio_service;
+
+mod common;
+
+use futures::future::Future;
+use futures::sync::oneshot;
+use futures::Stream;
+use hyper::header::ContentType;
+use hyper::{Request, Response, StatusCode};
+use serde_json::Value as JsonValue;
+use tokio_service::Service;
+
+use jsonrpc_client_core::JsonCodec;
+#[cfg(feature = "msgpack")]
+use jsonrpc_client_core::{Codec, MsgPackCodec};
+use jsonrpc_client_http::HttpTransport;
+
+jsonrpc_client!(pub struct UpperClient {
+    pub fn to_upper(&mut self, string: &str) -> RpcRequest<String>;
+});
+
+#[test]
+fn json_codec_round_trip() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+    let mut client = UpperClient::with_codec(handle, JsonCodec);
+
+    assert_eq!(client.to_upper("json").call().unwrap(), "JSON");
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn msgpack_codec_round_trip() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+    let mut client = UpperClient::with_codec(handle, MsgPackCodec);
+
+    assert_eq!(client.to_upper("msgpack").call().unwrap(), "MSGPACK");
+    assert_eq!(MsgPackCodec.content_type(), "application/msgpack");
+}
+
+/// Service implementing `to_upper`, decoding requests and encoding responses in JSON or
+/// MessagePack depending on the content type of the request. Other content types are responded
+/// to with `415 Unsupported Media Type`.
+#[derive(Clone)]
+struct UpperService;
+
+impl Service for UpperService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let content_type = request.headers().get::<ContentType>().cloned();
+        Box::new(request.body().concat2().map(move |body| {
+            let msgpack = match content_type {
+                Some(ref content_type) if content_type.subtype().as_str() == "msgpack" => true,
+                Some(ref content_type) if content_type.subtype().as_str() == "json" => false,
+                _ => return Response::new().with_status(StatusCode::UnsupportedMediaType),
+            };
+            let request: JsonValue = if msgpack {
+                rmp_serde::from_slice(&body).unwrap()
+            } else {
+                serde_json::from_slice(&body).unwrap()
+            };
+            let upper = request["params"][0].as_str().unwrap().to_uppercase();
+            let response = json!({"jsonrpc": "2.0", "result": upper, "id": request["id"]});
+            let body = if msgpack {
+                rmp_serde::to_vec(&response).unwrap()
+            } else {
+                serde_json::to_vec(&response).unwrap()
+            };
+            Response::new()
+                .with_header(content_type.unwrap())
+                .with_body(body)
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let (port, shutdown_tx) = common::spawn_server(UpperService);
+
+        Self {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+}
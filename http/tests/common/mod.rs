@@ -1,10 +1,13 @@
 #![allow(dead_code)]
 
+use std::thread;
 use std::time::Duration;
 
 use futures::future::{self, Empty};
+use futures::sync::oneshot;
+use futures::Future;
 use jsonrpc_core::{Error, IoHandler};
-use jsonrpc_http_server::hyper::server::{Request, Response, Service};
+use jsonrpc_http_server::hyper::server::{Http, Request, Response, Service};
 use jsonrpc_http_server::{self, hyper, ServerBuilder};
 
 // Generate server API trait. Actual implementation at bottom of file.
@@ -28,7 +31,6 @@ jsonrpc_client!(pub struct MockRpcClient {
     pub fn sleep(&mut self, time: u64) -> RpcRequest<()>;
 });
 
-
 /// Simple struct that will implement the RPC API defined at the top of this file.
 pub struct MockRpcServer;
 
@@ -60,6 +62,32 @@ impl MockRpcServerApi for MockRpcServer {
     }
 }
 
+/// Spawns a Hyper server on a free port of localhost, handling every connection with a clone of
+/// `service`. Returns the port, and the sender stopping the server when it is dropped.
+pub fn spawn_server<S>(service: S) -> (u16, oneshot::Sender<()>)
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>
+        + Clone
+        + Send
+        + 'static,
+{
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    let (port_tx, port_rx) = oneshot::channel();
+
+    thread::spawn(move || {
+        let address = "127.0.0.1:0".parse().unwrap();
+        let server = Http::new()
+            .bind(&address, move || Ok(service.clone()))
+            .unwrap();
+        let port = server.local_addr().unwrap().port();
+
+        port_tx.send(port).unwrap();
+        server.run_until(shutdown_rx.then(|_| Ok(()))).unwrap();
+    });
+
+    (port_rx.wait().unwrap(), shutdown_tx)
+}
+
 pub struct UnresponsiveService;
 
 impl Service for UnresponsiveService {
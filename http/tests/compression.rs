@@ -0,0 +1,165 @@
+#![cfg(feature = "compression")]
+
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate miniz_oxide;
+extern crate tokio_service;
+
+mod common;
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::header::{AcceptEncoding, ContentEncoding, ContentType, Encoding};
+use hyper::{Request, Response, StatusCode};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{ErrorKind, HttpTransport};
+
+/// `echo -n 'compressed' | gzip -n`
+const GZIPPED: &[u8] = &[
+    0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x4b, 0xce, 0xcf, 0x2d, 0x28, 0x4a,
+    0x2d, 0x2e, 0x4e, 0x4d, 0x01, 0x00, 0x1e, 0x4b, 0x56, 0x97, 0x0a, 0x00, 0x00, 0x00,
+];
+
+#[test]
+fn decompresses_gzip() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .accept_compressed(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(handle.send(b"gzip".to_vec()).wait().unwrap(), b"compressed");
+}
+
+#[test]
+fn decompresses_deflate() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .accept_compressed(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(
+        handle.send(b"deflate".to_vec()).wait().unwrap(),
+        b"compressed"
+    );
+}
+
+#[test]
+fn not_compressed_unless_enabled() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(
+        handle.send(b"gzip".to_vec()).wait().unwrap(),
+        b"not compressed"
+    );
+}
+
+#[test]
+fn unknown_encoding_fails() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .accept_compressed(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let error = handle.send(b"br".to_vec()).wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::UnsupportedContentEncoding(ref encoding, _) if encoding == "br" => (),
+        _ => panic!("unexpected error: {}", error),
+    }
+}
+
+#[test]
+fn decompressed_size_is_limited() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .accept_compressed(true)
+        .max_response_size(5)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let error = handle.send(b"deflate".to_vec()).wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::ResponseTooLarge(5, _) => (),
+        _ => panic!("unexpected error: {}", error),
+    }
+}
+
+/// Service responding with "compressed", encoded with the content coding in the request body,
+/// if the request has an `Accept-Encoding` header. Responds with "not compressed" otherwise.
+#[derive(Clone)]
+struct CompressingService;
+
+impl Service for CompressingService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let accepts_compressed = request.headers().has::<AcceptEncoding>();
+        Box::new(request.body().concat2().map(move |body| {
+            if !accepts_compressed {
+                return Response::new()
+                    .with_header(ContentType::json())
+                    .with_body(&b"not compressed"[..]);
+            }
+            let (encoding, body) = match &body[..] {
+                b"gzip" => (Encoding::Gzip, GZIPPED.to_vec()),
+                b"deflate" => (
+                    Encoding::Deflate,
+                    miniz_oxide::deflate::compress_to_vec_zlib(b"compressed", 6),
+                ),
+                b"br" => (Encoding::Brotli, b"not really brotli".to_vec()),
+                _ => return Response::new().with_status(StatusCode::BadRequest),
+            };
+            Response::new()
+                .with_header(ContentType::json())
+                .with_header(ContentEncoding(vec![encoding]))
+                .with_body(body)
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let (port, shutdown_tx) = common::spawn_server(CompressingService);
+
+        Server {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+}
@@ -0,0 +1,158 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Future};
+use futures::sync::oneshot;
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{ErrorKind, HttpTransport};
+
+#[test]
+fn requests_are_sequential_by_default() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let requests: Vec<_> = (0..4).map(|_| handle.send(Vec::new())).collect();
+    future::join_all(requests).wait().unwrap();
+    assert_eq!(server.max_in_flight(), 1);
+}
+
+#[test]
+fn max_concurrent_requests_is_respected() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .max_concurrent_requests(3)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let requests: Vec<_> = (0..10).map(|_| handle.send(Vec::new())).collect();
+    future::join_all(requests).wait().unwrap();
+    assert_eq!(server.max_in_flight(), 3);
+}
+
+#[test]
+fn rate_limit_delays_requests() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .max_concurrent_requests(4)
+        .rate_limit(10, 1)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    // The first request is sent right away, the others wait 100, 200 and 300 ms for a token.
+    let start = Instant::now();
+    let requests: Vec<_> = (0..4).map(|_| handle.send(Vec::new())).collect();
+    future::join_all(requests).wait().unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(300));
+}
+
+#[test]
+fn rate_limit_wait_counts_against_timeout() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .max_concurrent_requests(2)
+        .rate_limit(1, 1)
+        .timeout(Duration::from_millis(500))
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let first = handle.send(Vec::new());
+    let second = handle.send(Vec::new());
+    first.wait().unwrap();
+    match *second.wait().unwrap_err().kind() {
+        ErrorKind::RequestTimeout(..) => (),
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+/// Service responding to every request after a delay, without blocking other requests. Keeps
+/// track of the highest number of requests it has been processing at the same time.
+#[derive(Clone)]
+pub struct DelayService {
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: Arc<AtomicUsize>,
+}
+
+impl Service for DelayService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, _: Request) -> Self::Future {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        if in_flight > self.max_in_flight.load(Ordering::SeqCst) {
+            self.max_in_flight.store(in_flight, Ordering::SeqCst);
+        }
+
+        let (done_tx, done_rx) = oneshot::channel();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            let _ = done_tx.send(());
+        });
+        let in_flight = self.in_flight.clone();
+        Box::new(done_rx.then(move |_| {
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(Response::new())
+        }))
+    }
+}
+
+pub struct Server {
+    pub port: u16,
+    max_in_flight: Arc<AtomicUsize>,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let service = DelayService {
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            max_in_flight: Arc::new(AtomicUsize::new(0)),
+        };
+        let max_in_flight = service.max_in_flight.clone();
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Self {
+            port,
+            max_in_flight,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+}
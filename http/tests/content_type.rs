@@ -0,0 +1,138 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use futures::future::{Future, FutureResult, IntoFuture};
+use futures::sync::oneshot;
+use hyper::header::ContentType;
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{ErrorKind, HttpTransport};
+
+const RESPONSE: &[u8] = br#"{"jsonrpc":"2.0","result":true,"id":1}"#;
+
+#[test]
+fn json_content_types_are_accepted() {
+    let transport = HttpTransport::new().standalone().unwrap();
+    let content_types = vec![
+        ContentType::json(),
+        ContentType("application/json; charset=utf-8".parse().unwrap()),
+        ContentType("application/json-rpc".parse().unwrap()),
+    ];
+    for content_type in content_types {
+        let server = Server::spawn(Some(content_type), RESPONSE);
+        let handle = transport.handle(&server.uri()).unwrap();
+        assert_eq!(handle.send(Vec::new()).wait().unwrap(), RESPONSE);
+    }
+}
+
+#[test]
+fn html_is_rejected() {
+    let login_page = format!("<html>{}</html>", "Please log in. ".repeat(30));
+    let server = Server::spawn(Some(ContentType::html()), login_page.as_bytes());
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let error = handle.send(Vec::new()).wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::UnexpectedContentType(ref content_type, ref body, _) => {
+            assert_eq!(content_type, "text/html");
+            assert_eq!(&body[..], &login_page.as_bytes()[..256]);
+        }
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn missing_content_type_is_accepted_for_json_bodies() {
+    let transport = HttpTransport::new().standalone().unwrap();
+    for body in &[RESPONSE, b" [{}]", b""] {
+        let server = Server::spawn(None, body);
+        let handle = transport.handle(&server.uri()).unwrap();
+        assert_eq!(&handle.send(Vec::new()).wait().unwrap(), body);
+    }
+
+    let server = Server::spawn(None, b"Welcome!");
+    let handle = transport.handle(&server.uri()).unwrap();
+    match *handle.send(Vec::new()).wait().unwrap_err().kind() {
+        ErrorKind::UnexpectedContentType(ref content_type, ref body, _) => {
+            assert_eq!(content_type, "");
+            assert_eq!(&body[..], b"Welcome!");
+        }
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn check_can_be_skipped() {
+    let server = Server::spawn(Some(ContentType::plaintext()), RESPONSE);
+    let handle = HttpTransport::new()
+        .skip_content_type_check(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(handle.send(Vec::new()).wait().unwrap(), RESPONSE);
+}
+
+/// Service responding to all requests with the same content type and body.
+#[derive(Clone)]
+struct BodyService {
+    content_type: Option<ContentType>,
+    body: Vec<u8>,
+}
+
+impl Service for BodyService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = FutureResult<Self::Response, Self::Error>;
+
+    fn call(&self, _: Request) -> Self::Future {
+        let mut response = Response::new().with_body(self.body.clone());
+        if let Some(ref content_type) = self.content_type {
+            response.headers_mut().set(content_type.clone());
+        }
+        Ok(response).into_future()
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(content_type: Option<ContentType>, body: &[u8]) -> Self {
+        let service = BodyService {
+            content_type,
+            body: body.to_vec(),
+        };
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Self {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+}
@@ -0,0 +1,104 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use futures::future::{self, FutureResult};
+use futures::sync::oneshot;
+use futures::Future;
+use hyper::header::{ContentType, Cookie, SetCookie};
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::HttpTransport;
+
+#[test]
+fn cookies_are_sent_back() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new()
+        .cookie_store(true)
+        .standalone()
+        .unwrap();
+    let handle = transport.handle(&server.uri()).unwrap();
+    assert_eq!(send(handle.send(Vec::new())), "no cookies");
+
+    send(handle.send_to("login", Vec::new()));
+    assert_eq!(send(handle.send(Vec::new())), "session=abc");
+    // The cookies are shared by all handles of the transport.
+    let other_handle = transport.handle(&server.uri()).unwrap();
+    assert_eq!(send(other_handle.send(Vec::new())), "session=abc");
+}
+
+#[test]
+fn cookies_are_not_stored_by_default() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    send(handle.send_to("login", Vec::new()));
+    assert_eq!(send(handle.send(Vec::new())), "no cookies");
+}
+
+fn send<F: Future<Item = Vec<u8>>>(request: F) -> String
+where
+    F::Error: ::std::fmt::Debug,
+{
+    String::from_utf8(request.wait().unwrap()).unwrap()
+}
+
+/// Service setting a session cookie in responses to `/rpc/login`, and responding with the
+/// cookies of the request to all other requests.
+#[derive(Clone)]
+struct SessionService;
+
+impl Service for SessionService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = FutureResult<Self::Response, Self::Error>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let response = Response::new().with_header(ContentType::json());
+        if request.path() == "/rpc/login" {
+            let set_cookie = SetCookie(vec!["session=abc; Path=/; HttpOnly".to_owned()]);
+            return future::ok(response.with_header(set_cookie));
+        }
+        let cookies = match request.headers().get::<Cookie>() {
+            Some(cookies) => cookies.to_string(),
+            None => "no cookies".to_owned(),
+        };
+        future::ok(response.with_body(cookies))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let (port, shutdown_tx) = common::spawn_server(SessionService);
+
+        Server {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/rpc", self.port)
+    }
+}
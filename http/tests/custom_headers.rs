@@ -1,18 +1,25 @@
 extern crate futures;
 extern crate hyper;
+#[macro_use]
 extern crate jsonrpc_client_core;
 extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
 extern crate tokio_service;
 
+mod common;
+
 use std::sync::mpsc;
-use std::thread;
 use std::time::Duration;
 
 use futures::future::{Future, FutureResult, IntoFuture};
 use futures::sync::oneshot;
-use hyper::server::Http;
 use hyper::{Request, Response, StatusCode};
-use jsonrpc_client_http::header::{ContentLength, ContentType, Host};
+use jsonrpc_client_http::header::{
+    Authorization, Bearer, ContentLength, ContentType, Host, UserAgent,
+};
 use tokio_service::Service;
 
 use jsonrpc_client_core::Transport;
@@ -43,7 +50,6 @@ fn set_host_header_twice() {
         transport.set_header(Host::new(hostname, port));
     };
 
-
     let request = test_custom_headers(set);
     let host = request.headers().get::<Host>().expect("No Host");
     assert_eq!(host.hostname(), hostname);
@@ -59,7 +65,6 @@ fn set_content_type() {
         transport.set_header(content_type);
     };
 
-
     let request = test_custom_headers(set);
     let content_type = request
         .headers()
@@ -69,11 +74,9 @@ fn set_content_type() {
 }
 
 #[test]
-fn set_content_length() {
-    let fake_content_length = ContentLength(100);
-
+fn custom_content_length_is_ignored() {
     let set = move |transport: &mut HttpHandle| {
-        transport.set_header(fake_content_length);
+        transport.set_header(ContentLength(100));
     };
 
     let request = test_custom_headers(set);
@@ -81,7 +84,244 @@ fn set_content_length() {
         .headers()
         .get::<ContentLength>()
         .expect("No ContentLength");
-    assert_eq!(*content_length, fake_content_length);
+    assert_eq!(*content_length, ContentLength(0));
+}
+
+#[test]
+fn set_basic_auth() {
+    let set = |transport: &mut HttpHandle| {
+        transport.set_basic_auth("user", Some("pass"));
+    };
+
+    let request = test_custom_headers(set);
+    assert_eq!(authorization(&request), b"Basic dXNlcjpwYXNz");
+}
+
+#[test]
+fn set_basic_auth_without_password() {
+    let set = |transport: &mut HttpHandle| {
+        transport.set_basic_auth("user", None);
+    };
+
+    let request = test_custom_headers(set);
+    assert_eq!(authorization(&request), b"Basic dXNlcjo=");
+}
+
+#[test]
+fn set_basic_auth_with_colon_and_non_ascii() {
+    let set = |transport: &mut HttpHandle| {
+        transport.set_basic_auth("jörg", Some("pässwörd"));
+    };
+
+    let request = test_custom_headers(set);
+    assert_eq!(authorization(&request), b"Basic asO2cmc6cMOkc3N3w7ZyZA==");
+
+    let set = |transport: &mut HttpHandle| {
+        transport.set_basic_auth("Aladdin", Some("open:sesame"));
+    };
+
+    let request = test_custom_headers(set);
+    assert_eq!(
+        authorization(&request),
+        b"Basic QWxhZGRpbjpvcGVuOnNlc2FtZQ=="
+    );
+}
+
+#[test]
+fn set_bearer_auth() {
+    let set = |transport: &mut HttpHandle| {
+        transport.set_bearer_auth("s3cr3t-t0k3n");
+    };
+
+    let request = test_custom_headers(set);
+    assert_eq!(authorization(&request), b"Bearer s3cr3t-t0k3n");
+}
+
+#[test]
+fn auth_replaces_previous_auth() {
+    let set = |transport: &mut HttpHandle| {
+        transport
+            .set_bearer_auth("old-token")
+            .set_basic_auth("user", Some("pass"))
+            .set_bearer_auth("new-token");
+    };
+
+    let request = test_custom_headers(set);
+    let raw = request
+        .headers()
+        .get_raw("Authorization")
+        .expect("No Authorization");
+    assert_eq!(raw.len(), 1);
+    assert_eq!(authorization(&request), b"Bearer new-token");
+}
+
+#[test]
+fn remove_header() {
+    let set = |transport: &mut HttpHandle| {
+        transport
+            .set_bearer_auth("token")
+            .set_header(Host::new("dummy.url", None))
+            .remove_header::<Authorization<Bearer>>();
+    };
+
+    let request = test_custom_headers(set);
+    assert!(request.headers().get_raw("Authorization").is_none());
+    assert_eq!(
+        request.headers().get::<Host>().unwrap().hostname(),
+        "dummy.url"
+    );
+}
+
+#[test]
+fn clear_headers_keeps_builtin_headers() {
+    let set = |transport: &mut HttpHandle| {
+        transport
+            .set_basic_auth("user", Some("pass"))
+            .set_header(ContentType::xml())
+            .clear_headers();
+    };
+
+    let request = test_custom_headers(set);
+    assert!(request.headers().get_raw("Authorization").is_none());
+    assert_eq!(
+        request.headers().get::<ContentType>(),
+        Some(&ContentType::json())
+    );
+}
+
+#[test]
+fn default_headers_are_used_by_all_handles() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new()
+        .default_header(Host::new("default.url", None))
+        .default_header(ContentType::xml())
+        .standalone()
+        .unwrap();
+    let uri = format!("http://127.0.0.1:{}", server.port);
+    let default_handle = transport.handle(&uri).unwrap();
+    let mut custom_handle = transport.handle(&uri).unwrap();
+    custom_handle.set_header(Host::new("custom.url", None));
+
+    for (handle, hostname) in &[
+        (default_handle, "default.url"),
+        (custom_handle, "custom.url"),
+    ] {
+        handle.send(Vec::new()).wait().unwrap();
+        let request = server
+            .requests
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(
+            request.headers().get::<Host>().unwrap().hostname(),
+            *hostname
+        );
+        assert_eq!(
+            request.headers().get::<ContentType>(),
+            Some(&ContentType::xml())
+        );
+    }
+}
+
+#[test]
+fn default_user_agent() {
+    let request = test_custom_headers(|_| ());
+    let user_agent = request
+        .headers()
+        .get_raw("User-Agent")
+        .expect("No User-Agent")
+        .one()
+        .expect("Multiple User-Agent headers");
+    assert!(user_agent.starts_with(b"jsonrpc-client-http/"));
+}
+
+#[test]
+fn user_agent_can_be_replaced_per_handle() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new()
+        .user_agent("billing-service/2.1")
+        .standalone()
+        .unwrap();
+    let uri = format!("http://127.0.0.1:{}", server.port);
+    let default_handle = transport.handle(&uri).unwrap();
+    let mut custom_handle = transport.handle(&uri).unwrap();
+    custom_handle.set_header(UserAgent::new("reports/1.0"));
+
+    for (handle, expected) in &[
+        (default_handle, "billing-service/2.1"),
+        (custom_handle, "reports/1.0"),
+    ] {
+        handle.send(Vec::new()).wait().unwrap();
+        let request = server
+            .requests
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(
+            request.headers().get::<UserAgent>(),
+            Some(&UserAgent::new(*expected))
+        );
+    }
+}
+
+#[test]
+fn request_preparer_signs_body() {
+    let server = Server::spawn();
+    // The server never reads the bodies, so it may close the connection instead of reusing it for
+    // the next request.
+    let transport = HttpTransport::new().keep_alive(false).standalone().unwrap();
+    let uri = format!("http://127.0.0.1:{}", server.port);
+    let mut handle = transport.handle(&uri).unwrap();
+    handle.set_request_preparer(|headers, body| {
+        headers.set_raw("X-Timestamp", "1530000000");
+        headers.set_raw("X-Signature", signature("1530000000", body));
+        headers.set(ContentLength(1));
+    });
+
+    for body in &[&b"[1,2,3]"[..], &b"{}"[..]] {
+        handle.clone().send(body.to_vec()).wait().unwrap();
+        let request = server
+            .requests
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap();
+        let raw = |name| {
+            request
+                .headers()
+                .get_raw(name)
+                .unwrap()
+                .one()
+                .unwrap()
+                .to_vec()
+        };
+        assert_eq!(raw("X-Timestamp"), b"1530000000");
+        assert_eq!(
+            raw("X-Signature"),
+            signature("1530000000", body).into_bytes()
+        );
+        assert_eq!(
+            request.headers().get::<ContentLength>(),
+            Some(&ContentLength(body.len() as u64))
+        );
+    }
+}
+
+/// Stands in for an HMAC, any function of the timestamp and the body does.
+fn signature(timestamp: &str, body: &[u8]) -> String {
+    let sum = timestamp
+        .bytes()
+        .chain(body.iter().cloned())
+        .fold(0u32, |sum, byte| {
+            sum.wrapping_mul(31).wrapping_add(u32::from(byte))
+        });
+    format!("{:08x}", sum)
+}
+
+/// Returns the raw value of the single `Authorization` header in the request.
+fn authorization(request: &Request) -> &[u8] {
+    request
+        .headers()
+        .get_raw("Authorization")
+        .expect("No Authorization")
+        .one()
+        .expect("Multiple Authorization headers")
 }
 
 fn test_custom_headers<S>(set_headers: S) -> Request
@@ -139,21 +379,8 @@ pub struct Server {
 impl Server {
     fn spawn() -> Self {
         let (forward_service, requests) = ForwardToChannel::new();
-        let (shutdown_tx, shutdown_rx) = oneshot::channel();
-        let (port_tx, port_rx) = oneshot::channel();
-
-        thread::spawn(move || {
-            let address = "127.0.0.1:0".parse().unwrap();
-            let server = Http::new()
-                .bind(&address, move || Ok(forward_service.clone()))
-                .unwrap();
-            let port = server.local_addr().unwrap().port();
-
-            port_tx.send(port).unwrap();
-            server.run_until(shutdown_rx.then(|_| Ok(()))).unwrap();
-        });
 
-        let port = port_rx.wait().unwrap();
+        let (port, shutdown_tx) = common::spawn_server(forward_service);
 
         Self {
             port,
@@ -0,0 +1,237 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::header::ContentType;
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{ErrorKind, HttpTransport, StatusCode};
+
+#[test]
+fn fails_over_on_connection_error() {
+    let server = Server::spawn(StatusCode::Ok, "second");
+    let transport = HttpTransport::new().standalone().unwrap();
+    let handle = transport
+        .handle_with_fallbacks(&[&closed_port_uri(), &server.uri()])
+        .unwrap();
+
+    let response = handle.send(b"body".to_vec()).wait().unwrap();
+    assert_eq!(response, b"second body");
+}
+
+#[test]
+fn fails_over_on_unavailable_status() {
+    let unavailable = Server::spawn(StatusCode::ServiceUnavailable, "first");
+    let server = Server::spawn(StatusCode::Ok, "second");
+    let transport = HttpTransport::new().standalone().unwrap();
+    let handle = transport
+        .handle_with_fallbacks(&[&unavailable.uri(), &server.uri()])
+        .unwrap();
+
+    let response = handle.send(b"body".to_vec()).wait().unwrap();
+    assert_eq!(response, b"second body");
+    assert_eq!(unavailable.requests(), 1);
+}
+
+#[test]
+fn other_statuses_do_not_fail_over() {
+    let bad_request = Server::spawn(StatusCode::BadRequest, "first");
+    let server = Server::spawn(StatusCode::Ok, "second");
+    let transport = HttpTransport::new()
+        .failover_statuses(&[StatusCode::ServiceUnavailable])
+        .standalone()
+        .unwrap();
+    let handle = transport
+        .handle_with_fallbacks(&[&bad_request.uri(), &server.uri()])
+        .unwrap();
+
+    let error = handle.send(b"body".to_vec()).wait().unwrap_err();
+    assert_eq!(error.http_status(), Some(StatusCode::BadRequest));
+    assert_eq!(server.requests(), 0);
+}
+
+#[test]
+fn working_endpoint_becomes_primary() {
+    let unavailable = Server::spawn(StatusCode::ServiceUnavailable, "first");
+    let server = Server::spawn(StatusCode::Ok, "second");
+    let transport = HttpTransport::new().standalone().unwrap();
+    let handle = transport
+        .handle_with_fallbacks(&[&unavailable.uri(), &server.uri()])
+        .unwrap();
+
+    handle.send(b"body".to_vec()).wait().unwrap();
+    let response = handle.clone().send(b"again".to_vec()).wait().unwrap();
+    assert_eq!(response, b"second again");
+    assert_eq!(unavailable.requests(), 1);
+    assert_eq!(server.requests(), 2);
+}
+
+#[test]
+fn all_endpoints_failed() {
+    let unavailable = Server::spawn(StatusCode::BadGateway, "first");
+    let closed_port = closed_port_uri();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let handle = transport
+        .handle_with_fallbacks(&[&unavailable.uri(), &closed_port])
+        .unwrap();
+
+    let error = handle.send(b"body".to_vec()).wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::AllEndpointsFailed(ref failures) => {
+            assert_eq!(failures.len(), 2);
+            assert_eq!(failures[0].0.to_string(), unavailable.uri());
+            assert_eq!(failures[0].1.http_status(), Some(StatusCode::BadGateway));
+            assert_eq!(failures[1].0.to_string(), closed_port);
+        }
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+    let message = error.to_string();
+    assert!(message.contains(&unavailable.uri()), "{}", message);
+    assert!(message.contains(&closed_port), "{}", message);
+}
+
+#[test]
+fn no_endpoints() {
+    let transport = HttpTransport::new().standalone().unwrap();
+    assert!(transport.handle_with_fallbacks(&[]).is_err());
+}
+
+/// Returns the URI of a port nothing listens on.
+#[test]
+fn circuit_breaker_fails_fast() {
+    let unavailable = Server::spawn(StatusCode::ServiceUnavailable, "first");
+    let transport = HttpTransport::new()
+        .circuit_breaker(2, Duration::from_secs(60))
+        .standalone()
+        .unwrap();
+    let handle = transport.handle(&unavailable.uri()).unwrap();
+
+    for _ in 0..2 {
+        let error = handle.send(b"body".to_vec()).wait().unwrap_err();
+        assert_eq!(error.http_status(), Some(StatusCode::ServiceUnavailable));
+    }
+    // Other handles of the transport share the circuit.
+    let other_handle = transport.handle(&unavailable.uri()).unwrap();
+    match *other_handle
+        .send(b"body".to_vec())
+        .wait()
+        .unwrap_err()
+        .kind()
+    {
+        ErrorKind::CircuitOpen(ref endpoint, _) => {
+            assert_eq!(format!("{}/", endpoint), unavailable.uri())
+        }
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+    assert_eq!(unavailable.requests(), 2);
+}
+
+#[test]
+fn open_circuit_fails_over() {
+    let unavailable = Server::spawn(StatusCode::ServiceUnavailable, "first");
+    let server = Server::spawn(StatusCode::Ok, "second");
+    let transport = HttpTransport::new()
+        .circuit_breaker(1, Duration::from_secs(60))
+        .standalone()
+        .unwrap();
+    transport
+        .handle(&unavailable.uri())
+        .unwrap()
+        .send(b"body".to_vec())
+        .wait()
+        .unwrap_err();
+
+    let handle = transport
+        .handle_with_fallbacks(&[&unavailable.uri(), &server.uri()])
+        .unwrap();
+    let response = handle.send(b"body".to_vec()).wait().unwrap();
+    assert_eq!(response, b"second body");
+    assert_eq!(unavailable.requests(), 1);
+}
+
+fn closed_port_uri() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!(
+        "http://127.0.0.1:{}/",
+        listener.local_addr().unwrap().port()
+    )
+}
+
+/// Service responding with a fixed status code, and with its name followed by the request body.
+#[derive(Clone)]
+struct FixedService {
+    status: StatusCode,
+    name: &'static str,
+    requests: Arc<AtomicUsize>,
+}
+
+impl Service for FixedService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        let status = self.status;
+        let mut response = format!("{} ", self.name).into_bytes();
+        Box::new(request.body().concat2().map(move |body| {
+            response.extend_from_slice(&body);
+            Response::new()
+                .with_status(status)
+                .with_header(ContentType::json())
+                .with_body(response)
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    requests: Arc<AtomicUsize>,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(status: StatusCode, name: &'static str) -> Self {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let service = FixedService {
+            status,
+            name,
+            requests: requests.clone(),
+        };
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Server {
+            port,
+            requests,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+
+    fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
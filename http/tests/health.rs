@@ -0,0 +1,71 @@
+extern crate futures;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+
+extern crate tokio_core;
+
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+
+mod common;
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use futures::Future;
+use jsonrpc_client_http::HttpTransport;
+use tokio_core::reactor::Core;
+
+use common::{MockRpcClient, MockRpcServer};
+
+#[test]
+fn pending_requests() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut client = MockRpcClient::new(transport.handle(&uri).unwrap());
+    assert_eq!(transport.pending_requests(), 0);
+
+    let rpc_future1 = client.slow_to_upper("first", 100);
+    let rpc_future2 = client.slow_to_upper("second", 100);
+    assert_eq!(transport.pending_requests(), 2);
+    assert_eq!("FIRST", rpc_future1.wait().unwrap());
+    assert_eq!("SECOND", rpc_future2.wait().unwrap());
+    assert_eq!(transport.pending_requests(), 0);
+}
+
+#[test]
+fn standalone_dies_on_shutdown() {
+    let transport = HttpTransport::new().standalone().unwrap();
+    let (death_tx, death_rx) = mpsc::channel();
+    transport.on_death(move || death_tx.send(()).unwrap());
+    assert!(transport.is_alive());
+
+    transport.clone().shutdown(Duration::from_secs(5)).unwrap();
+    death_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert!(!transport.is_alive());
+
+    // Already dead, so called right away.
+    let (death_tx, death_rx) = mpsc::channel();
+    transport.on_death(move || death_tx.send(()).unwrap());
+    death_rx.try_recv().unwrap();
+}
+
+#[test]
+fn shared_dies_with_core() {
+    let core = Core::new().unwrap();
+    let transport = HttpTransport::new().shared(&core.handle()).unwrap();
+    let (death_tx, death_rx) = mpsc::channel();
+    transport.on_death(move || death_tx.send(()).unwrap());
+    assert!(transport.is_alive());
+
+    drop(core);
+    death_rx.try_recv().unwrap();
+    assert!(!transport.is_alive());
+    let handle = transport.handle("http://127.0.0.1/").unwrap();
+    let mut client = MockRpcClient::new(handle);
+    assert!(client.to_upper("dead").wait().is_err());
+}
@@ -0,0 +1,135 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::header::ContentType;
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{HttpTransport, InMemoryMetrics};
+
+#[test]
+fn slow_request_is_hedged() {
+    let server = Server::spawn(1);
+    let metrics = Arc::new(InMemoryMetrics::new());
+    let handle = HttpTransport::new()
+        .hedge(Duration::from_millis(50), 2)
+        .metrics(metrics.clone())
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let start = Instant::now();
+    let response = handle.send(br#"{"id":7}"#.to_vec()).wait().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    // Answered by the duplicate, which carries the same body and id.
+    assert_eq!(String::from_utf8(response).unwrap(), r#"1 {"id":7}"#);
+    assert_eq!(server.requests.load(Ordering::SeqCst), 2);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.requests, 1);
+    assert_eq!(snapshot.successes, 1);
+    assert_eq!(snapshot.hedges, 1);
+}
+
+#[test]
+fn fast_request_is_not_hedged() {
+    let server = Server::spawn(0);
+    let handle = HttpTransport::new()
+        .hedge(Duration::from_millis(500), 2)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let response = handle.send(br#"{"id":1}"#.to_vec()).wait().unwrap();
+    assert_eq!(String::from_utf8(response).unwrap(), r#"0 {"id":1}"#);
+    thread::sleep(Duration::from_millis(600));
+    assert_eq!(server.requests.load(Ordering::SeqCst), 1);
+}
+
+/// Service responding with the number of requests received before a request, followed by its
+/// body. The first `slow` requests are answered after two seconds, the others right away.
+#[derive(Clone)]
+struct SlowFirstService {
+    requests: Arc<AtomicUsize>,
+    slow: usize,
+}
+
+impl Service for SlowFirstService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let number = self.requests.fetch_add(1, Ordering::SeqCst);
+        let (done_tx, done_rx) = oneshot::channel();
+        let delay = if number < self.slow {
+            Duration::from_secs(2)
+        } else {
+            Duration::from_secs(0)
+        };
+        thread::spawn(move || {
+            thread::sleep(delay);
+            let _ = done_tx.send(());
+        });
+        Box::new(request.body().concat2().and_then(move |body| {
+            done_rx.then(move |_| {
+                let mut response = format!("{} ", number).into_bytes();
+                response.extend_from_slice(&body);
+                Ok::<_, hyper::Error>(
+                    Response::new()
+                        .with_header(ContentType::json())
+                        .with_body(response),
+                )
+            })
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    requests: Arc<AtomicUsize>,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(slow: usize) -> Self {
+        let service = SlowFirstService {
+            requests: Arc::new(AtomicUsize::new(0)),
+            slow,
+        };
+        let requests = service.requests.clone();
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Server {
+            port,
+            requests,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+}
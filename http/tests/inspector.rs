@@ -0,0 +1,164 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::net::TcpListener;
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::header::ContentType;
+use hyper::{Request, Response, StatusCode};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::HttpTransport;
+
+/// What the inspector callback saw, copied out of the borrowed event.
+#[derive(Debug)]
+struct Event {
+    uri: String,
+    request_body: Vec<u8>,
+    response: Result<Vec<u8>, String>,
+    status: Option<StatusCode>,
+}
+
+fn transport_with_inspector(timeout: Duration) -> (HttpTransport, mpsc::Receiver<Event>) {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let transport = HttpTransport::new()
+        .timeout(timeout)
+        .inspector(move |event| {
+            assert!(event.queued_at <= event.sent_at);
+            assert!(event.sent_at <= event.completed_at);
+            let event = Event {
+                uri: event.uri.to_string(),
+                request_body: event.request_body.to_vec(),
+                response: event
+                    .response
+                    .map(|body| body.to_vec())
+                    .map_err(|error| error.kind().to_string()),
+                status: event.status,
+            };
+            tx.lock().unwrap().send(event).unwrap();
+        })
+        .standalone()
+        .unwrap();
+    (transport, rx)
+}
+
+#[test]
+fn inspects_successful_request() {
+    let server = Server::spawn();
+    let (transport, events) = transport_with_inspector(Duration::from_secs(5));
+    let handle = transport.handle(&server.uri()).unwrap();
+
+    handle.send(b"ping".to_vec()).wait().unwrap();
+    let event = events.recv().unwrap();
+    assert_eq!(event.uri, server.uri());
+    assert_eq!(event.request_body, b"ping");
+    assert_eq!(event.response, Ok(b"ping".to_vec()));
+    assert_eq!(event.status, Some(StatusCode::Ok));
+}
+
+#[test]
+fn inspects_error_status() {
+    let server = Server::spawn();
+    let (transport, events) = transport_with_inspector(Duration::from_secs(5));
+    let handle = transport.handle(&server.uri()).unwrap();
+
+    let error = handle.send(b"fail".to_vec()).wait().unwrap_err();
+    let event = events.recv().unwrap();
+    assert_eq!(event.response, Err(error.to_string()));
+    assert!(
+        error
+            .to_string()
+            .ends_with(&format!(" on {}", server.uri())),
+        "{}",
+        error
+    );
+    assert_eq!(event.status, Some(StatusCode::InternalServerError));
+}
+
+#[test]
+fn inspects_timeout() {
+    // Accepts connections, but never responds.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri = format!("http://{}/", listener.local_addr().unwrap());
+    let (transport, events) = transport_with_inspector(Duration::from_millis(100));
+    let handle = transport.handle(&uri).unwrap();
+
+    assert!(handle.send(b"hello".to_vec()).wait().is_err());
+    let event = events.recv().unwrap();
+    assert_eq!(event.request_body, b"hello");
+    match event.response {
+        Err(ref error) => assert!(error.starts_with("Request timed out"), "{}", error),
+        Ok(_) => panic!("inspected a response of a timed out request"),
+    }
+    assert_eq!(event.status, None);
+}
+
+#[test]
+fn inspects_notification() {
+    let server = Server::spawn();
+    let (transport, events) = transport_with_inspector(Duration::from_secs(5));
+    let handle = transport.handle(&server.uri()).unwrap();
+
+    handle.notify(b"note".to_vec()).wait().unwrap();
+    let event = events.recv().unwrap();
+    assert_eq!(event.request_body, b"note");
+    assert_eq!(event.response, Ok(Vec::new()));
+}
+
+/// Service echoing back the request body, except for the body "fail" which gets a 500 response.
+#[derive(Clone)]
+struct EchoService;
+
+impl Service for EchoService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        Box::new(request.body().concat2().map(|body| {
+            if &body[..] == b"fail" {
+                Response::new().with_status(StatusCode::InternalServerError)
+            } else {
+                Response::new()
+                    .with_header(ContentType::json())
+                    .with_body(body.to_vec())
+            }
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let (port, shutdown_tx) = common::spawn_server(EchoService);
+
+        Server {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+}
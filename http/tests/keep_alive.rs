@@ -0,0 +1,138 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::header::ContentType;
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::HttpTransport;
+
+#[test]
+fn connections_reused_by_default() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(handle.send(b"first".to_vec()).wait().unwrap(), b"1");
+    assert_eq!(handle.send(b"second".to_vec()).wait().unwrap(), b"1");
+}
+
+#[test]
+fn new_connection_per_request_without_keep_alive() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .keep_alive(false)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(handle.send(b"first".to_vec()).wait().unwrap(), b"1");
+    assert_eq!(handle.send(b"second".to_vec()).wait().unwrap(), b"2");
+}
+
+#[test]
+fn idle_connections_expire() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .keep_alive_timeout(Duration::from_millis(50))
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(handle.send(b"first".to_vec()).wait().unwrap(), b"1");
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(handle.send(b"second".to_vec()).wait().unwrap(), b"2");
+}
+
+#[test]
+fn connections_replaced_after_lifetime() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .max_connection_lifetime(Duration::from_millis(100))
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(handle.send(b"first".to_vec()).wait().unwrap(), b"1");
+    assert_eq!(handle.send(b"second".to_vec()).wait().unwrap(), b"1");
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(handle.send(b"third".to_vec()).wait().unwrap(), b"2");
+    assert_eq!(handle.send(b"fourth".to_vec()).wait().unwrap(), b"2");
+}
+
+/// Service responding with the number of connections the server has accepted so far, told
+/// apart by the address of the client.
+#[derive(Clone)]
+struct CountingService {
+    connections: Arc<Mutex<HashSet<SocketAddr>>>,
+}
+
+impl Service for CountingService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    // The server sets the remote address, which tells the connections apart.
+    #[allow(deprecated)]
+    fn call(&self, request: Request) -> Self::Future {
+        let connections = {
+            let mut connections = self.connections.lock().unwrap();
+            connections.insert(request.remote_addr().unwrap());
+            connections.len()
+        };
+        Box::new(request.body().concat2().map(move |_| {
+            Response::new()
+                .with_header(ContentType::json())
+                .with_body(connections.to_string())
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let service = CountingService {
+            connections: Arc::new(Mutex::new(HashSet::new())),
+        };
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Server {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+}
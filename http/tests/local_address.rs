@@ -0,0 +1,157 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::header::ContentType;
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{AddressFamily, ErrorKind, HttpTransport};
+
+#[test]
+fn connects_from_local_address() {
+    let server = Server::spawn();
+    let local_address: IpAddr = "127.0.0.1".parse().unwrap();
+    let handle = HttpTransport::new()
+        .local_address(local_address)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let response = handle.send(b"request".to_vec()).wait().unwrap();
+    assert_eq!(String::from_utf8(response).unwrap(), "127.0.0.1");
+}
+
+#[test]
+fn unavailable_local_address() {
+    let server = Server::spawn();
+    // Reserved for documentation, so never assigned to a local interface.
+    let local_address: IpAddr = "192.0.2.1".parse().unwrap();
+    let handle = HttpTransport::new()
+        .local_address(local_address)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let error = handle.send(b"request".to_vec()).wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::LocalAddressUnavailable(address) => assert_eq!(address, local_address),
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn address_family() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new()
+        .address_family(AddressFamily::Ipv4Only)
+        .standalone()
+        .unwrap();
+    let handle = transport.handle(&server.uri()).unwrap();
+    let response = handle.send(b"request".to_vec()).wait().unwrap();
+    assert_eq!(String::from_utf8(response).unwrap(), "127.0.0.1");
+
+    let transport = HttpTransport::new()
+        .address_family(AddressFamily::Ipv6Only)
+        .standalone()
+        .unwrap();
+    let handle = transport.handle(&server.uri()).unwrap();
+    let error = handle.send(b"request".to_vec()).wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::Hyper(hyper::Error::Io(ref error)) => {
+            assert_eq!(error.kind(), io::ErrorKind::AddrNotAvailable)
+        }
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn resolve() {
+    let server = Server::spawn();
+    // The .invalid top level domain never resolves.
+    let handle = HttpTransport::new()
+        .resolve("rpc.invalid", "127.0.0.2".parse().unwrap())
+        .resolve("rpc.invalid", "127.0.0.1".parse().unwrap())
+        .standalone()
+        .unwrap()
+        .handle(&format!("http://rpc.invalid:{}/", server.port))
+        .unwrap();
+
+    let response = handle.send(b"request".to_vec()).wait().unwrap();
+    assert_eq!(String::from_utf8(response).unwrap(), "127.0.0.1");
+}
+
+#[test]
+fn connect_to() {
+    let server = Server::spawn();
+    let address: SocketAddr = format!("127.0.0.1:{}", server.port).parse().unwrap();
+    // Nothing listens on port 1, the port in the URI is not connected to.
+    let handle = HttpTransport::new()
+        .connect_to("rpc.invalid", address)
+        .standalone()
+        .unwrap()
+        .handle("http://rpc.invalid:1/")
+        .unwrap();
+
+    let response = handle.send(b"request".to_vec()).wait().unwrap();
+    assert_eq!(String::from_utf8(response).unwrap(), "127.0.0.1");
+}
+
+/// Service responding with the IP address the request came from.
+#[derive(Clone)]
+struct PeerAddressService;
+
+impl Service for PeerAddressService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    // The server sets the remote address, which is all that is needed here.
+    #[allow(deprecated)]
+    fn call(&self, request: Request) -> Self::Future {
+        let peer = request.remote_addr().unwrap().ip();
+        Box::new(request.body().concat2().map(move |_| {
+            Response::new()
+                .with_header(ContentType::json())
+                .with_body(peer.to_string())
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let (port, shutdown_tx) = common::spawn_server(PeerAddressService);
+
+        Server {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+}
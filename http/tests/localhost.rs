@@ -22,6 +22,7 @@ mod common;
 
 use futures::future::Either;
 use futures::Future;
+use jsonrpc_client_core::CounterIdGenerator;
 use jsonrpc_client_http::HttpTransport;
 use std::time::Duration;
 use tokio_core::reactor::{Core, Timeout};
@@ -88,3 +89,26 @@ fn dropped_rpc_request_should_not_crash_transport() {
         _ => panic!("Sleep did not return as it should"),
     }
 }
+
+#[test]
+fn string_request_ids() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+    let transport = HttpTransport::new().standalone().unwrap();
+    let generator = CounterIdGenerator::with_prefix("upper-");
+    let handle = transport.handle_with_id_generator(&uri, generator).unwrap();
+    let mut client = MockRpcClient::new(handle);
+
+    assert_eq!("FIRST", client.to_upper("first").wait().unwrap());
+    assert_eq!("SECOND", client.to_upper("second").wait().unwrap());
+}
+
+#[test]
+fn connect_to_single_endpoint() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+    let mut client = MockRpcClient::new(HttpTransport::connect(&uri).unwrap());
+    assert_eq!("CONNECTED", client.to_upper("connected").wait().unwrap());
+
+    assert!(HttpTransport::connect("http://[::1").is_err());
+}
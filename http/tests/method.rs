@@ -0,0 +1,131 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::header::ContentType;
+use hyper::{Method, Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::HttpTransport;
+
+#[test]
+fn post_by_default() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let handle = transport.handle(&server.uri("/rpc")).unwrap();
+
+    let response = handle.send(b"body".to_vec()).wait().unwrap();
+    assert_eq!(String::from_utf8(response).unwrap(), "POST /rpc json body");
+}
+
+#[test]
+fn custom_method_and_query() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut handle = transport.handle(&server.uri("/v2/rpc?old=1")).unwrap();
+    handle
+        .set_method(Method::Put)
+        .unwrap()
+        .set_query(&[("api_key", "k3y"), ("trace", "on")])
+        .unwrap();
+
+    let response = handle.send(b"body".to_vec()).wait().unwrap();
+    assert_eq!(
+        String::from_utf8(response).unwrap(),
+        "PUT /v2/rpc?api_key=k3y&trace=on json body"
+    );
+}
+
+#[test]
+fn get_with_body_in_query() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut handle = transport.handle(&server.uri("/rpc?chain=main")).unwrap();
+    handle
+        .set_method(Method::Get)
+        .unwrap()
+        .set_get_param("json");
+
+    let response = handle.send(br#"{"id":1}"#.to_vec()).wait().unwrap();
+    assert_eq!(
+        String::from_utf8(response).unwrap(),
+        "GET /rpc?chain=main&json=%7B%22id%22%3A1%7D other "
+    );
+}
+
+#[test]
+fn send_to_path() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let handle = transport.handle(&server.uri("/rpc?key=1")).unwrap();
+
+    let response = handle.send_to("admin", b"body".to_vec()).wait().unwrap();
+    assert_eq!(
+        String::from_utf8(response).unwrap(),
+        "POST /rpc/admin?key=1 json body"
+    );
+    let response = handle.send_to("/v1", b"body".to_vec()).wait().unwrap();
+    assert_eq!(
+        String::from_utf8(response).unwrap(),
+        "POST /v1?key=1 json body"
+    );
+}
+
+/// Service responding with the method, path, query and content type of the request, followed by
+/// its body.
+#[derive(Clone)]
+struct EchoRequestService;
+
+impl Service for EchoRequestService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let content_type = match request.headers().get::<ContentType>() {
+            Some(content_type) if *content_type == ContentType::json() => "json",
+            _ => "other",
+        };
+        let prefix = format!("{} {} {} ", request.method(), request.uri(), content_type);
+        Box::new(request.body().concat2().map(move |body| {
+            let mut response = prefix.into_bytes();
+            response.extend_from_slice(&body);
+            Response::new()
+                .with_header(ContentType::json())
+                .with_body(response)
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let (port, shutdown_tx) = common::spawn_server(EchoRequestService);
+
+        Server {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+}
@@ -0,0 +1,142 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::header::ContentType;
+use hyper::{Request, Response, StatusCode, Uri};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{HttpTransport, InMemoryMetrics, RequestOutcome, TransportMetrics};
+
+/// Keeps the method hint and outcome of every recorded request.
+#[derive(Default)]
+struct RecordingMetrics {
+    records: Mutex<Vec<(Option<String>, RequestOutcome)>>,
+}
+
+impl TransportMetrics for RecordingMetrics {
+    fn record_request(&self, _: &Uri, method: Option<&str>, _: Duration, outcome: RequestOutcome) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((method.map(str::to_owned), outcome));
+    }
+}
+
+#[test]
+fn records_outcomes_and_methods() {
+    let server = Server::spawn();
+    let metrics = Arc::new(RecordingMetrics::default());
+    let handle = HttpTransport::new()
+        .metrics(metrics.clone())
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    handle
+        .send(br#"{"method": "echo", "id": 1}"#.to_vec())
+        .wait()
+        .unwrap();
+    assert!(handle
+        .send(br#"{"method": "fail", "id": 2}"#.to_vec())
+        .wait()
+        .is_err());
+    assert_eq!(
+        *metrics.records.lock().unwrap(),
+        vec![
+            (Some("echo".to_owned()), RequestOutcome::Success),
+            (
+                Some("fail".to_owned()),
+                RequestOutcome::HttpError(StatusCode::InternalServerError)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn in_memory_metrics() {
+    let server = Server::spawn();
+    // Accepts connections, but never responds.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let metrics = Arc::new(InMemoryMetrics::new());
+    let transport = HttpTransport::new()
+        .timeout(Duration::from_millis(100))
+        .metrics(metrics.clone())
+        .standalone()
+        .unwrap();
+    let handle = transport.handle(&server.uri()).unwrap();
+    let unresponsive_handle = transport
+        .handle(&format!("http://{}/", listener.local_addr().unwrap()))
+        .unwrap();
+
+    handle.send(b"body".to_vec()).wait().unwrap();
+    handle.send(b"body".to_vec()).wait().unwrap();
+    assert!(unresponsive_handle.send(b"body".to_vec()).wait().is_err());
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.requests, 3);
+    assert_eq!(snapshot.successes, 2);
+    assert_eq!(snapshot.timeouts, 1);
+    let bucketed: u64 = snapshot.latency.iter().map(|&(_, count)| count).sum();
+    assert_eq!(bucketed, 3);
+}
+
+/// Service echoing back the request body, or responding with 500 if the body contains "fail".
+#[derive(Clone)]
+struct EchoService;
+
+impl Service for EchoService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        Box::new(request.body().concat2().map(|body| {
+            if body.windows(4).any(|window| window == b"fail") {
+                Response::new().with_status(StatusCode::InternalServerError)
+            } else {
+                Response::new()
+                    .with_header(ContentType::json())
+                    .with_body(body.to_vec())
+            }
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let (port, shutdown_tx) = common::spawn_server(EchoService);
+
+        Server {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+}
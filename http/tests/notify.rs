@@ -0,0 +1,133 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use futures::future::{Future, FutureResult, IntoFuture};
+use futures::sync::{mpsc as futures_mpsc, oneshot};
+use hyper::{Body, Chunk, Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::{send_notification, Transport};
+use jsonrpc_client_http::{ErrorKind, HttpTransport, StatusCode};
+
+#[test]
+fn notify_does_not_wait_for_body() {
+    let server = Server::spawn(StatusCode::Ok);
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let (result_tx, result_rx) = mpsc::channel();
+    thread::spawn(move || {
+        result_tx
+            .send(handle.notify(b"{}".to_vec()).wait())
+            .unwrap();
+    });
+    result_rx
+        .recv_timeout(Duration::from_secs(5))
+        .expect("notification waited for the body")
+        .unwrap();
+}
+
+#[test]
+fn notify_does_not_use_an_id() {
+    let server = Server::spawn(StatusCode::NoContent);
+    let mut handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let id = handle.get_next_id();
+    send_notification(&mut handle, "log".to_owned(), ("message",))
+        .call()
+        .unwrap();
+    handle.notify(b"{}".to_vec()).wait().unwrap();
+    assert_eq!(handle.get_next_id(), id + 1);
+}
+
+#[test]
+fn notify_with_error_status_fails() {
+    let server = Server::spawn(StatusCode::InternalServerError);
+    let handle = HttpTransport::new()
+        .accept_non_200_bodies(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    match handle.notify(b"{}".to_vec()).wait() {
+        Err(error) => match error.kind() {
+            &ErrorKind::HttpError(StatusCode::InternalServerError, _) => (),
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("notification did not fail"),
+    }
+}
+
+type BodySender = futures_mpsc::Sender<Result<Chunk, hyper::Error>>;
+
+/// Service responding to all requests with the given status code and a JSON body that never
+/// ends.
+#[derive(Clone)]
+pub struct EndlessBodyService {
+    status: StatusCode,
+    body_txs: Arc<Mutex<Vec<BodySender>>>,
+}
+
+impl Service for EndlessBodyService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = FutureResult<Self::Response, Self::Error>;
+
+    fn call(&self, _: Request) -> Self::Future {
+        let (body_tx, body) = Body::pair();
+        self.body_txs.lock().unwrap().push(body_tx);
+        Ok(Response::new()
+            .with_header(hyper::header::ContentType::json())
+            .with_status(self.status)
+            .with_body(body))
+        .into_future()
+    }
+}
+
+pub struct Server {
+    pub port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(status: StatusCode) -> Self {
+        let service = EndlessBodyService {
+            status,
+            body_txs: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Self {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+}
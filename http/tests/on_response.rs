@@ -0,0 +1,150 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::{Future, FutureResult, IntoFuture};
+use futures::sync::oneshot;
+use hyper::header::RetryAfter;
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{HttpTransport, StatusCode};
+
+#[test]
+fn inspects_successful_response() {
+    let server = Server::spawn(StatusCode::Ok);
+    let (tx, rx) = mpsc::channel();
+
+    let mut handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+    handle.on_response(move |status, headers| {
+        tx.send((*status, headers.get::<RetryAfter>().cloned()))
+            .unwrap();
+    });
+
+    handle.send(Vec::new()).wait().unwrap();
+    assert_eq!(rx.try_recv().unwrap(), (StatusCode::Ok, None));
+}
+
+#[test]
+fn inspects_error_response() {
+    let server = Server::spawn(StatusCode::TooManyRequests);
+    let (tx, rx) = mpsc::channel();
+
+    let mut handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+    handle.on_response(move |status, headers| {
+        tx.send((*status, headers.get::<RetryAfter>().cloned()))
+            .unwrap();
+    });
+
+    assert!(handle.send(Vec::new()).wait().is_err());
+    assert_eq!(
+        rx.try_recv().unwrap(),
+        (
+            StatusCode::TooManyRequests,
+            Some(RetryAfter::Delay(Duration::from_secs(120)))
+        )
+    );
+}
+
+#[test]
+fn inspector_is_shared_with_clones() {
+    let server = Server::spawn(StatusCode::Ok);
+    let count = Arc::new(Mutex::new(0));
+
+    let mut handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+    let callback_count = count.clone();
+    handle.on_response(move |_, _| *callback_count.lock().unwrap() += 1);
+    let cloned_handle = handle.clone();
+
+    handle.send(Vec::new()).wait().unwrap();
+    cloned_handle.send(Vec::new()).wait().unwrap();
+    assert_eq!(*count.lock().unwrap(), 2);
+}
+
+#[test]
+fn send_with_response_returns_status_and_headers() {
+    let server = Server::spawn(StatusCode::TooManyRequests);
+    let handle = HttpTransport::new()
+        .accept_status(|status| status == StatusCode::TooManyRequests)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let response = handle.send_with_response(Vec::new()).wait().unwrap();
+    assert_eq!(response.status, StatusCode::TooManyRequests);
+    assert_eq!(
+        response.headers.get::<RetryAfter>(),
+        Some(&RetryAfter::Delay(Duration::from_secs(120)))
+    );
+    assert!(response.body.is_empty());
+}
+
+/// Service responding to all requests with the given status code. Error responses get a
+/// `Retry-After` header.
+#[derive(Clone)]
+pub struct StatusService(StatusCode);
+
+impl Service for StatusService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = FutureResult<Self::Response, Self::Error>;
+
+    fn call(&self, _: Request) -> Self::Future {
+        let mut response = Response::new().with_status(self.0);
+        if self.0 != StatusCode::Ok {
+            response
+                .headers_mut()
+                .set(RetryAfter::Delay(Duration::from_secs(120)));
+        }
+        Ok(response).into_future()
+    }
+}
+
+pub struct Server {
+    pub port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(status: StatusCode) -> Self {
+        let service = StatusService(status);
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Self {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+}
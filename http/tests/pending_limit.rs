@@ -0,0 +1,75 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate futures;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+
+mod common;
+
+use futures::future::{self, Future};
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{ErrorKind, HttpTransport, OverloadPolicy};
+
+use common::{MockRpcClient, MockRpcServer};
+
+#[test]
+fn requests_over_limit_fail() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+
+    let transport = HttpTransport::new()
+        .max_pending_requests(2)
+        .overload_policy(OverloadPolicy::Fail)
+        .standalone()
+        .unwrap();
+    let handle = transport.handle(&uri).unwrap();
+    let mut client = MockRpcClient::new(transport.handle(&uri).unwrap());
+
+    let slow1 = client.slow_to_upper("slow1", 200);
+    let slow2 = client.slow_to_upper("slow2", 200);
+    match handle.send(Vec::new()).wait() {
+        Err(ref error) => match error.kind() {
+            &ErrorKind::TransportOverloaded => (),
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("request did not fail"),
+    }
+
+    assert_eq!("SLOW1", slow1.wait().unwrap());
+    assert_eq!("SLOW2", slow2.wait().unwrap());
+    assert_eq!("FAST", client.to_upper("fast").wait().unwrap());
+}
+
+#[test]
+fn requests_over_limit_wait() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+
+    let transport = HttpTransport::new()
+        .max_pending_requests(1)
+        .standalone()
+        .unwrap();
+    let mut client = MockRpcClient::new(transport.handle(&uri).unwrap());
+
+    let slow = client.slow_to_upper("slow", 200);
+    let rpcs: Vec<_> = (0..10)
+        .map(|i| client.to_upper(&format!("request {}", i)))
+        .collect();
+
+    assert_eq!("SLOW", slow.wait().unwrap());
+    let results = future::join_all(rpcs).wait().unwrap();
+    let expected: Vec<_> = (0..10).map(|i| format!("REQUEST {}", i)).collect();
+    assert_eq!(results, expected);
+}
@@ -0,0 +1,168 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::{Method, Request, Response, StatusCode};
+use tokio_service::Service;
+
+use jsonrpc_client_http::{ErrorKind, HttpTransport, InMemoryMetrics, PingMethod};
+
+#[test]
+fn ping_returns_round_trip_time() {
+    let (server, requests) = Server::spawn(StatusCode::Ok);
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let round_trip = handle.ping().wait().unwrap();
+    assert!(round_trip < Duration::from_secs(5));
+    let (method, body) = requests.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(method, Method::Post);
+    assert!(body.is_empty());
+}
+
+#[test]
+fn ping_methods() {
+    let (server, requests) = Server::spawn(StatusCode::Ok);
+    let mut handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    for &(ping_method, ref method) in &[
+        (PingMethod::Head, Method::Head),
+        (PingMethod::Options, Method::Options),
+    ] {
+        handle.set_ping_method(ping_method);
+        handle.ping().wait().unwrap();
+        let (sent, _) = requests.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(sent, *method);
+    }
+}
+
+#[test]
+fn pings_are_not_hedged_or_recorded() {
+    let (server, requests) = Server::spawn(StatusCode::Ok);
+    let metrics = Arc::new(InMemoryMetrics::new());
+    let inspected = Arc::new(AtomicUsize::new(0));
+    let inspected_count = inspected.clone();
+    let handle = HttpTransport::new()
+        .hedge(Duration::from_millis(0), 2)
+        .metrics(metrics.clone())
+        .inspector(move |_| {
+            inspected_count.fetch_add(1, Ordering::SeqCst);
+        })
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    handle.ping().wait().unwrap();
+    requests.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert!(requests.recv_timeout(Duration::from_millis(200)).is_err());
+    assert_eq!(metrics.snapshot().requests, 0);
+    assert_eq!(inspected.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn ping_fails_on_error_status() {
+    let (server, _requests) = Server::spawn(StatusCode::ServiceUnavailable);
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    match *handle.ping().wait().unwrap_err().kind() {
+        ErrorKind::HttpError(status, _) => assert_eq!(status, StatusCode::ServiceUnavailable),
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn ping_fails_when_unreachable() {
+    // Nothing listens on the port once the listener is dropped.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&format!("http://127.0.0.1:{}", port))
+        .unwrap();
+
+    assert!(handle.ping().wait().is_err());
+}
+
+/// Service responding to all requests with the same status code, and sending the method and
+/// body of the requests it gets.
+#[derive(Clone)]
+struct StatusService {
+    status: StatusCode,
+    requests_tx: mpsc::Sender<(Method, Vec<u8>)>,
+}
+
+impl Service for StatusService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let method = request.method().clone();
+        let status = self.status;
+        let requests_tx = self.requests_tx.clone();
+        Box::new(request.body().concat2().map(move |body| {
+            let _ = requests_tx.send((method, body.to_vec()));
+            Response::new().with_status(status)
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(status: StatusCode) -> (Self, mpsc::Receiver<(Method, Vec<u8>)>) {
+        let (requests_tx, requests_rx) = mpsc::channel();
+        let service = StatusService {
+            status,
+            requests_tx,
+        };
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        let server = Self {
+            port,
+            _shutdown_tx: shutdown_tx,
+        };
+        (server, requests_rx)
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+}
@@ -0,0 +1,53 @@
+extern crate futures;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+
+mod common;
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use futures::future::{join_all, Future};
+use jsonrpc_client_http::HttpTransport;
+
+use common::{MockRpcClient, MockRpcServer};
+
+#[test]
+fn requests_are_spread_over_the_pool() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+    let transport = HttpTransport::new()
+        .thread_name("pool")
+        .standalone_pool(3)
+        .unwrap();
+    let threads = Arc::new(Mutex::new(HashSet::new()));
+    let mut handle = transport.handle(&uri).unwrap();
+    let inspector_threads = threads.clone();
+    handle.on_response(move |_, _| {
+        let name = thread::current().name().map(str::to_owned);
+        inspector_threads.lock().unwrap().insert(name.unwrap());
+    });
+    let mut client = MockRpcClient::new(handle);
+
+    let words: Vec<String> = (0..9).map(|i| format!("word{}", i)).collect();
+    let calls = words
+        .iter()
+        .map(|word| client.to_upper(word))
+        .collect::<Vec<_>>();
+    let results = join_all(calls).wait().unwrap();
+    let expected: Vec<String> = words.iter().map(|word| word.to_uppercase()).collect();
+    assert_eq!(results, expected);
+
+    let threads = threads.lock().unwrap().clone();
+    let expected: HashSet<String> = (0..3).map(|i| format!("pool-{}", i)).collect();
+    assert_eq!(threads, expected);
+    transport.shutdown(Duration::from_secs(5)).unwrap();
+}
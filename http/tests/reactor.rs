@@ -0,0 +1,38 @@
+extern crate futures;
+extern crate jsonrpc_client_http;
+extern crate tokio_core;
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use futures::{future, Future};
+use jsonrpc_client_http::HttpTransport;
+use tokio_core::reactor::{Core, Timeout};
+
+#[test]
+fn standalone_reactor() {
+    let transport = HttpTransport::new().standalone().unwrap();
+    assert!(transport.handle_reactor().is_none());
+
+    let (result_tx, result_rx) = mpsc::channel();
+    let loop_transport = transport.clone();
+    transport.reactor_remote().spawn(move |_| {
+        let handle = loop_transport.handle_reactor().unwrap();
+        Timeout::new(Duration::from_millis(10), &handle)
+            .unwrap()
+            .map(move |()| result_tx.send(()).unwrap())
+            .map_err(|e| panic!("timer failed: {}", e))
+    });
+    result_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+}
+
+#[test]
+fn shared_reactor() {
+    let mut core = Core::new().unwrap();
+    let transport = HttpTransport::new().shared(&core.handle()).unwrap();
+    assert_eq!(transport.reactor_remote().id(), core.id());
+    assert!(transport.handle_reactor().is_none());
+
+    let handle = core.run(future::lazy(|| Ok::<_, ()>(transport.handle_reactor())));
+    assert_eq!(handle.unwrap().unwrap().id(), core.id());
+}
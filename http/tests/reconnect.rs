@@ -0,0 +1,205 @@
+extern crate futures;
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use futures::Future;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{ErrorKind, HttpTransport, RetryPolicy};
+
+#[test]
+fn no_reconnect_by_default() {
+    let server = Server::spawn(vec![Reply::Close]);
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri)
+        .unwrap();
+
+    assert!(handle.send(b"body".to_vec()).wait().is_err());
+    assert_eq!(server.requests(), 1);
+}
+
+#[test]
+fn refused_connection_error() {
+    // Nothing listens on the port after the listener is dropped.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&format!("http://127.0.0.1:{}/", port))
+        .unwrap();
+
+    let error = handle.send(b"body".to_vec()).wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::ConnectionFailed(kind, _) => assert_eq!(kind, io::ErrorKind::ConnectionRefused),
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+    assert!(
+        error.iter().nth(1).is_some(),
+        "the IO error is not the cause"
+    );
+}
+
+#[test]
+fn reconnects_after_connection_error() {
+    let server = Server::spawn(vec![Reply::Close]);
+    let handle = HttpTransport::new()
+        .reconnect_on_connection_error(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri)
+        .unwrap();
+
+    assert_eq!(handle.send(b"body".to_vec()).wait().unwrap(), b"body");
+    assert_eq!(server.requests(), 2);
+}
+
+#[test]
+fn reconnects_only_once() {
+    let server = Server::spawn(vec![Reply::Close, Reply::Close]);
+    let handle = HttpTransport::new()
+        .reconnect_on_connection_error(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri)
+        .unwrap();
+
+    assert!(handle.send(b"body".to_vec()).wait().is_err());
+    assert_eq!(server.requests(), 2);
+}
+
+#[test]
+fn reconnect_comes_on_top_of_retries() {
+    let server = Server::spawn(vec![Reply::Close, Reply::Close]);
+    let policy = RetryPolicy::new(2, Duration::from_millis(10)).retry_if(|_| true);
+    let handle = HttpTransport::new()
+        .retry(policy)
+        .reconnect_on_connection_error(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri)
+        .unwrap();
+
+    assert_eq!(handle.send(b"body".to_vec()).wait().unwrap(), b"body");
+    assert_eq!(server.requests(), 3);
+}
+
+#[test]
+fn no_reconnect_after_response_started() {
+    let server = Server::spawn(vec![Reply::CloseDuringBody]);
+    let handle = HttpTransport::new()
+        .reconnect_on_connection_error(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri)
+        .unwrap();
+
+    assert!(handle.send(b"body".to_vec()).wait().is_err());
+    assert_eq!(server.requests(), 1);
+}
+
+/// How the server handles a request.
+#[derive(Debug, Clone, Copy)]
+enum Reply {
+    /// Closes the connection without responding.
+    Close,
+    /// Sends the response head and only part of the body, then closes the connection.
+    CloseDuringBody,
+    /// Echoes back the request body with 200 OK as JSON, keeping the connection open.
+    Echo,
+}
+
+/// HTTP server written directly on top of a `TcpListener`, to be able to drop connections at
+/// any point. Handles one connection at a time.
+struct Server {
+    uri: String,
+    requests: Arc<AtomicUsize>,
+}
+
+impl Server {
+    /// Spawns a server handling the first requests as given by `replies`, and echoing back all
+    /// requests after that.
+    fn spawn(replies: Vec<Reply>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let thread_requests = requests.clone();
+
+        thread::spawn(move || {
+            let mut replies = replies.into_iter();
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                while let Some(body) = read_request(&mut stream) {
+                    thread_requests.fetch_add(1, Ordering::SeqCst);
+                    match replies.next().unwrap_or(Reply::Echo) {
+                        Reply::Close => break,
+                        Reply::CloseDuringBody => {
+                            write_head(&mut stream, body.len() + 10);
+                            stream.write_all(&body).unwrap();
+                            break;
+                        }
+                        Reply::Echo => {
+                            write_head(&mut stream, body.len());
+                            stream.write_all(&body).unwrap();
+                        }
+                    }
+                }
+            }
+        });
+
+        Server { uri, requests }
+    }
+
+    fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+/// Reads a request and returns its body, or `None` if the client closed the connection.
+fn read_request(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        match stream.read(&mut byte) {
+            Ok(1) => head.push(byte[0]),
+            _ => return None,
+        }
+    }
+    let content_length = String::from_utf8(head)
+        .unwrap()
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) if name.eq_ignore_ascii_case("content-length") => {
+                    Some(value.trim().parse().unwrap())
+                }
+                _ => None,
+            }
+        })
+        .next()
+        .unwrap_or(0);
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).ok().map(|()| body)
+}
+
+fn write_head(stream: &mut TcpStream, content_length: usize) {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+        content_length
+    )
+    .unwrap();
+}
@@ -0,0 +1,212 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::header::{Authorization, ContentType, Host, Location};
+use hyper::{Request, Response, StatusCode};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{ErrorKind, HttpTransport, RedirectPolicy};
+
+#[test]
+fn redirects_not_followed_by_default() {
+    let server = Server::spawn(None);
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri("moved"))
+        .unwrap();
+
+    let error = handle.send(b"body".to_vec()).wait().unwrap_err();
+    assert_eq!(error.http_status(), Some(StatusCode::MovedPermanently));
+}
+
+#[test]
+fn follows_redirects_with_same_request() {
+    let server = Server::spawn(None);
+    let transport = HttpTransport::new().max_redirects(2).standalone().unwrap();
+    let mut handle = transport.handle(&server.uri("moved")).unwrap();
+    handle.set_header(Authorization("secret".to_owned()));
+
+    let response = handle.send(b"body".to_vec()).wait().unwrap();
+    let expected = format!("POST /target 127.0.0.1:{} secret body", server.port);
+    assert_eq!(String::from_utf8(response).unwrap(), expected);
+}
+
+#[test]
+fn see_other_is_followed_with_get() {
+    let server = Server::spawn(None);
+    let handle = HttpTransport::new()
+        .max_redirects(1)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri("see-other"))
+        .unwrap();
+
+    let response = handle.send(b"body".to_vec()).wait().unwrap();
+    let expected = format!("GET /target 127.0.0.1:{} - ", server.port);
+    assert_eq!(String::from_utf8(response).unwrap(), expected);
+}
+
+#[test]
+fn host_is_recomputed_for_other_server() {
+    let target_server = Server::spawn(None);
+    let server = Server::spawn(Some(target_server.port));
+    let transport = HttpTransport::new().max_redirects(1).standalone().unwrap();
+    let mut handle = transport.handle(&server.uri("elsewhere")).unwrap();
+    handle.set_header(Host::new("original.example.com", None));
+
+    let response = handle.send(b"body".to_vec()).wait().unwrap();
+    let expected = format!("POST /target 127.0.0.1:{} - body", target_server.port);
+    assert_eq!(String::from_utf8(response).unwrap(), expected);
+}
+
+#[test]
+fn too_many_redirects() {
+    let server = Server::spawn(None);
+    let handle = HttpTransport::new()
+        .max_redirects(3)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri("loop"))
+        .unwrap();
+
+    let error = handle.send(b"body".to_vec()).wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::TooManyRedirects(3, ref location) => {
+            assert_eq!(*location, server.uri("loop"))
+        }
+        _ => panic!("unexpected error: {}", error),
+    }
+}
+
+#[test]
+fn same_host_policy() {
+    let target_server = Server::spawn(None);
+    let server = Server::spawn(Some(target_server.port));
+    let transport = HttpTransport::new()
+        .max_redirects(1)
+        .redirect_policy(RedirectPolicy::SameHost)
+        .standalone()
+        .unwrap();
+
+    let handle = transport.handle(&server.uri("elsewhere")).unwrap();
+    let response = handle.send(b"body".to_vec()).wait().unwrap();
+    let expected = format!("POST /target 127.0.0.1:{} - body", target_server.port);
+    assert_eq!(String::from_utf8(response).unwrap(), expected);
+
+    let handle = transport.handle(&server.uri("other-host")).unwrap();
+    let error = handle.send(b"body".to_vec()).wait().unwrap_err();
+    assert_eq!(error.http_status(), Some(StatusCode::PermanentRedirect));
+}
+
+#[test]
+fn https_redirect_not_followed_without_tls() {
+    let server = Server::spawn(None);
+    let handle = HttpTransport::new()
+        .max_redirects(1)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri("secure"))
+        .unwrap();
+
+    let error = handle.send(b"body".to_vec()).wait().unwrap_err();
+    assert_eq!(error.http_status(), Some(StatusCode::Found));
+}
+
+/// Service redirecting requests to `/moved` and `/loop` within the same server, `/elsewhere`
+/// to `/target` on the other server, if any, `/other-host` to another host and `/secure` to https.
+/// Requests to `/target` get their method, path, `Host`
+/// and `Authorization` headers and body echoed back.
+#[derive(Clone)]
+struct RedirectService {
+    other_port: Option<u16>,
+}
+
+impl Service for RedirectService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let other_port = self.other_port;
+        let method = request.method().clone();
+        let path = request.path().to_owned();
+        let host = request.headers().get::<Host>().expect("No Host").clone();
+        let auth = request
+            .headers()
+            .get::<Authorization<String>>()
+            .map_or("-".to_owned(), |auth| auth.0.clone());
+        Box::new(request.body().concat2().map(move |body| {
+            let redirect = |status, location: String| {
+                Response::new()
+                    .with_status(status)
+                    .with_header(Location::new(location))
+            };
+            match &path[..] {
+                "/moved" => redirect(StatusCode::MovedPermanently, "/middle".to_owned()),
+                "/middle" => redirect(StatusCode::TemporaryRedirect, "target".to_owned()),
+                "/see-other" => redirect(StatusCode::SeeOther, "/target".to_owned()),
+                "/loop" => redirect(StatusCode::Found, "/loop".to_owned()),
+                "/elsewhere" => {
+                    let port = other_port.expect("no other server");
+                    let location = format!("http://127.0.0.1:{}/target", port);
+                    redirect(StatusCode::PermanentRedirect, location)
+                }
+                "/other-host" => {
+                    let location = "http://other.example.com/target".to_owned();
+                    redirect(StatusCode::PermanentRedirect, location)
+                }
+                "/secure" => {
+                    let port = host.port().unwrap_or(80);
+                    let location = format!("https://127.0.0.1:{}/target", port);
+                    redirect(StatusCode::Found, location)
+                }
+                "/target" => {
+                    let port = host.port().unwrap_or(80);
+                    let prefix =
+                        format!("{} {} {}:{} {} ", method, path, host.hostname(), port, auth);
+                    let mut response = prefix.into_bytes();
+                    response.extend_from_slice(&body);
+                    Response::new()
+                        .with_header(ContentType::json())
+                        .with_body(response)
+                }
+                _ => Response::new().with_status(StatusCode::NotFound),
+            }
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(other_port: Option<u16>) -> Self {
+        let (port, shutdown_tx) = common::spawn_server(RedirectService { other_port });
+
+        Server {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}/{}", self.port, path)
+    }
+}
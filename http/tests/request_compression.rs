@@ -0,0 +1,139 @@
+#![cfg(feature = "compression")]
+
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate miniz_oxide;
+extern crate tokio_service;
+
+mod common;
+
+use futures::sync::oneshot;
+use futures::{Future, Stream};
+use hyper::header::{ContentEncoding, ContentLength, ContentType, Encoding};
+use hyper::{Request, Response, StatusCode};
+use miniz_oxide::inflate;
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{CompressionKind, HttpTransport};
+
+#[test]
+fn gzip_compressed_above_threshold() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut handle = transport.handle(&server.uri()).unwrap();
+    handle.compress_request(CompressionKind::Gzip, 100);
+
+    let body = vec![b'a'; 1000];
+    let response = handle.send(body.clone()).wait().unwrap();
+    assert_eq!(response, [&b"gzip "[..], &body].concat());
+}
+
+#[test]
+fn deflate_compressed_above_threshold() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut handle = transport.handle(&server.uri()).unwrap();
+    handle.compress_request(CompressionKind::Deflate, 100);
+
+    let body = vec![b'a'; 100];
+    let response = handle.send(body.clone()).wait().unwrap();
+    assert_eq!(response, [&b"deflate "[..], &body].concat());
+}
+
+#[test]
+fn small_bodies_not_compressed() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut handle = transport.handle(&server.uri()).unwrap();
+    handle.compress_request(CompressionKind::Gzip, 100);
+
+    let response = handle.send(b"small".to_vec()).wait().unwrap();
+    assert_eq!(response, b"identity small");
+}
+
+#[test]
+fn compression_can_be_disabled() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut handle = transport.handle(&server.uri()).unwrap();
+    handle
+        .compress_request(CompressionKind::Gzip, 0)
+        .disable_request_compression();
+
+    let response = handle.send(b"body".to_vec()).wait().unwrap();
+    assert_eq!(response, b"identity body");
+}
+
+/// Service decoding the request body according to its `Content-Encoding` header, and responding
+/// with the name of the content coding followed by the decoded body. Responds with 400 Bad
+/// Request if the `Content-Length` header does not match the received body.
+#[derive(Clone)]
+struct DecompressingService;
+
+impl Service for DecompressingService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let encoding = request
+            .headers()
+            .get::<ContentEncoding>()
+            .map_or(Encoding::Identity, |encoding| encoding[0].clone());
+        let length = request
+            .headers()
+            .get::<ContentLength>()
+            .map(|length| length.0);
+        Box::new(request.body().concat2().map(move |body| {
+            if length != Some(body.len() as u64) {
+                return Response::new().with_status(StatusCode::BadRequest);
+            }
+            let decoded = match encoding {
+                Encoding::Identity => Ok(body.to_vec()),
+                // Skips the header without any optional fields, and the trailer.
+                Encoding::Gzip => inflate::decompress_to_vec(&body[10..body.len() - 8]),
+                Encoding::Deflate => inflate::decompress_to_vec_zlib(&body),
+                _ => return Response::new().with_status(StatusCode::BadRequest),
+            };
+            match decoded {
+                Ok(decoded) => {
+                    let mut response = format!("{} ", encoding).into_bytes();
+                    response.extend_from_slice(&decoded);
+                    Response::new()
+                        .with_header(ContentType::json())
+                        .with_body(response)
+                }
+                Err(_) => Response::new().with_status(StatusCode::BadRequest),
+            }
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let (port, shutdown_tx) = common::spawn_server(DecompressingService);
+
+        Server {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+}
@@ -0,0 +1,123 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use futures::future::{FutureResult, IntoFuture};
+use futures::sync::oneshot;
+use hyper::header::ContentType;
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::{ErrorKind, Id};
+use jsonrpc_client_http::HttpTransport;
+
+jsonrpc_client!(pub struct AnswerClient {
+    pub fn answer(&mut self) -> RpcRequest<u64>;
+});
+
+const WRONG_ID: &[u8] = br#"{"jsonrpc":"2.0","result":42,"id":1000}"#;
+const NULL_ID: &[u8] = br#"{"jsonrpc":"2.0","result":42,"id":null}"#;
+
+#[test]
+fn wrong_id_is_rejected() {
+    let server = Server::spawn(WRONG_ID);
+    let handle = HttpTransport::new()
+        .accept_null_response_id(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+    let mut client = AnswerClient::new(handle);
+
+    match *client.answer().call().unwrap_err().kind() {
+        ErrorKind::ResponseIdMismatch(ref expected, ref got, ref body) => {
+            assert_eq!(*expected, Id::Num(1));
+            assert_eq!(*got, Id::Num(1000));
+            assert_eq!(&body[..], WRONG_ID);
+        }
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn null_id_is_rejected_by_default() {
+    let server = Server::spawn(NULL_ID);
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+    let mut client = AnswerClient::new(handle);
+
+    match *client.answer().call().unwrap_err().kind() {
+        ErrorKind::ResponseIdMismatch(_, Id::Null, ref body) => assert_eq!(&body[..], NULL_ID),
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn null_id_can_be_accepted() {
+    let server = Server::spawn(NULL_ID);
+    let handle = HttpTransport::new()
+        .accept_null_response_id(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+    let mut client = AnswerClient::new(handle);
+
+    assert_eq!(client.answer().call().unwrap(), 42);
+}
+
+/// Service responding to all requests with the same JSON body.
+#[derive(Clone)]
+struct BodyService {
+    body: Vec<u8>,
+}
+
+impl Service for BodyService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = FutureResult<Self::Response, Self::Error>;
+
+    fn call(&self, _: Request) -> Self::Future {
+        let response = Response::new()
+            .with_header(ContentType::json())
+            .with_body(self.body.clone());
+        Ok(response).into_future()
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(body: &[u8]) -> Self {
+        let service = BodyService {
+            body: body.to_vec(),
+        };
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Self {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+}
@@ -0,0 +1,179 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::mem;
+use std::thread;
+
+use futures::sync::oneshot;
+use futures::{Future, Sink, Stream};
+use hyper::header::{ContentLength, ContentType};
+use hyper::{Body, Chunk, Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{ErrorKind, HttpTransport};
+
+#[test]
+fn limited_by_default() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let body = vec![b'x'; 10 * 1024 * 1024];
+    assert_eq!(handle.send(body.clone()).wait().unwrap(), body);
+    assert_too_large(handle.send(b"endless".to_vec()).wait(), 10 * 1024 * 1024);
+}
+
+#[test]
+fn unlimited_response_size() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .max_response_size(1000)
+        .unlimited_response_size()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let body = vec![b'x'; 11 * 1024 * 1024];
+    assert_eq!(handle.send(body.clone()).wait().unwrap(), body);
+}
+
+#[test]
+fn body_at_limit_is_accepted() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .max_response_size(1000)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let body = vec![b'x'; 1000];
+    assert_eq!(handle.send(body.clone()).wait().unwrap(), body);
+}
+
+#[test]
+fn body_over_limit_fails() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .max_response_size(1000)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_too_large(handle.send(vec![b'x'; 1001]).wait(), 1000);
+}
+
+#[test]
+fn endless_body_fails() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .max_response_size(1024 * 1024)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_too_large(handle.send(b"endless".to_vec()).wait(), 1024 * 1024);
+}
+
+#[test]
+fn content_length_over_limit_fails_before_reading_body() {
+    let server = Server::spawn();
+    let handle = HttpTransport::new()
+        .max_response_size(1000)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    // The server never sends the body.
+    assert_too_large(handle.send(b"huge".to_vec()).wait(), 1000);
+}
+
+fn assert_too_large<T>(result: jsonrpc_client_http::Result<T>, expected_limit: usize) {
+    match result {
+        Err(error) => match error.kind() {
+            &ErrorKind::ResponseTooLarge(limit, _) if limit == expected_limit => (),
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("request did not fail"),
+    }
+}
+
+/// Service echoing back the request body, except for the body "endless" which gets a response
+/// body that never ends, and the body "huge" which gets a `Content-Length` of 1 GiB but no body.
+#[derive(Clone)]
+struct LargeBodyService;
+
+impl Service for LargeBodyService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        Box::new(request.body().concat2().map(|body| {
+            if &body[..] == b"huge" {
+                let (sender, body) = Body::pair();
+                // Keep the body open without sending anything.
+                mem::forget(sender);
+                return Response::new()
+                    .with_header(ContentType::json())
+                    .with_header(ContentLength(1024 * 1024 * 1024))
+                    .with_body(body);
+            }
+            if &body[..] != b"endless" {
+                return Response::new()
+                    .with_header(ContentType::json())
+                    .with_body(body.to_vec());
+            }
+            let (mut sender, body) = Body::pair();
+            thread::spawn(move || loop {
+                let chunk = Chunk::from(vec![b'x'; 64 * 1024]);
+                sender = match sender.send(Ok(chunk)).wait() {
+                    Ok(sender) => sender,
+                    Err(_) => break,
+                };
+            });
+            Response::new()
+                .with_header(ContentType::json())
+                .with_body(body)
+        }))
+    }
+}
+
+struct Server {
+    port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let (port, shutdown_tx) = common::spawn_server(LargeBodyService);
+
+        Server {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+}
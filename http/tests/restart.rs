@@ -0,0 +1,75 @@
+extern crate futures;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use futures::Future;
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{ErrorKind, HttpHandle, HttpTransport};
+
+use common::{MockRpcClient, MockRpcServer};
+
+/// Makes the event loop panic while processing the first response to a request through
+/// `handle`.
+fn panic_once(handle: &mut HttpHandle) {
+    let panicked = Arc::new(AtomicBool::new(false));
+    handle.on_response(move |_, _| {
+        if !panicked.swap(true, Ordering::SeqCst) {
+            panic!("inspector panicking on purpose");
+        }
+    });
+}
+
+#[test]
+fn restarts_after_panic() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+    let transport = HttpTransport::new()
+        .restart_on_failure(true)
+        .standalone()
+        .unwrap();
+    let (death_tx, death_rx) = mpsc::channel();
+    transport.on_death(move || death_tx.send(()).unwrap());
+    let mut handle = transport.handle(&uri).unwrap();
+    panic_once(&mut handle);
+
+    let body = br#"{"jsonrpc":"2.0","id":1,"method":"to_upper","params":["first"]}"#;
+    match handle.send(body.to_vec()).wait() {
+        Err(ref error) => match error.kind() {
+            &ErrorKind::TransportRestarted => (),
+            kind => panic!("unexpected error: {}", kind),
+        },
+        Ok(_) => panic!("request succeeded despite the panic"),
+    }
+    let mut client = MockRpcClient::new(handle);
+    assert_eq!("SECOND", client.to_upper("second").wait().unwrap());
+    assert!(transport.is_alive());
+    assert!(death_rx.try_recv().is_err());
+    transport.shutdown(Duration::from_secs(5)).unwrap();
+    death_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+}
+
+#[test]
+fn dies_after_panic_by_default() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut handle = transport.handle(&uri).unwrap();
+    panic_once(&mut handle);
+    let mut client = MockRpcClient::new(handle);
+
+    assert!(client.to_upper("first").wait().is_err());
+    assert!(client.to_upper("second").wait().is_err());
+    assert!(!transport.is_alive());
+}
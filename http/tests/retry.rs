@@ -0,0 +1,323 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Future, FutureResult};
+use futures::sync::oneshot;
+use futures::Stream;
+use hyper::header::{ContentLength, ContentType};
+use hyper::server::Http;
+use hyper::{Body, Chunk, Request, Response, StatusCode};
+use tokio_service::Service;
+
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{HttpTransport, RetryPolicy};
+
+#[test]
+fn no_retries_by_default() {
+    let server = Server::spawn(1, StatusCode::ServiceUnavailable);
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_http_error(
+        handle.send(b"body".to_vec()).wait(),
+        StatusCode::ServiceUnavailable,
+    );
+    assert_eq!(server.received_bodies().len(), 1);
+}
+
+#[test]
+fn retries_transient_error_until_success() {
+    let server = Server::spawn(2, StatusCode::ServiceUnavailable);
+    let handle = HttpTransport::new()
+        .retry(RetryPolicy::new(3, Duration::from_millis(10)))
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let response = handle.send(b"same body".to_vec()).wait().unwrap();
+    assert_eq!(response, b"same body");
+    assert_eq!(server.received_bodies(), vec![b"same body".to_vec(); 3]);
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    let server = Server::spawn(10, StatusCode::BadGateway);
+    let handle = HttpTransport::new()
+        .retry(RetryPolicy::new(2, Duration::from_millis(10)))
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_http_error(handle.send(b"body".to_vec()).wait(), StatusCode::BadGateway);
+    assert_eq!(server.received_bodies().len(), 2);
+}
+
+#[test]
+fn does_not_retry_non_transient_error() {
+    let server = Server::spawn(1, StatusCode::InternalServerError);
+    let handle = HttpTransport::new()
+        .retry(RetryPolicy::new(3, Duration::from_millis(10)))
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_http_error(
+        handle.send(b"body".to_vec()).wait(),
+        StatusCode::InternalServerError,
+    );
+    assert_eq!(server.received_bodies().len(), 1);
+}
+
+#[test]
+fn custom_retry_predicate() {
+    let server = Server::spawn(1, StatusCode::InternalServerError);
+    let policy = RetryPolicy::new(3, Duration::from_millis(10))
+        .retry_if(|error| error.http_status() == Some(StatusCode::InternalServerError));
+    let handle = HttpTransport::new()
+        .retry(policy)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(handle.send(b"body".to_vec()).wait().unwrap(), b"body");
+    assert_eq!(server.received_bodies().len(), 2);
+}
+
+#[test]
+fn exponential_backoff_until_success() {
+    let server = Server::spawn(2, StatusCode::ServiceUnavailable);
+    let policy = RetryPolicy::exponential(Duration::from_millis(50), 2.0, Duration::from_secs(1));
+    let handle = HttpTransport::new()
+        .retry(policy)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let start = Instant::now();
+    assert_eq!(handle.send(b"body".to_vec()).wait().unwrap(), b"body");
+    // 50 ms before the first retry and 100 ms before the second.
+    assert!(start.elapsed() >= Duration::from_millis(150));
+    assert_eq!(server.received_bodies().len(), 3);
+}
+
+#[test]
+fn exponential_backoff_with_jitter_until_success() {
+    let server = Server::spawn(2, StatusCode::ServiceUnavailable);
+    let policy = RetryPolicy::exponential(Duration::from_millis(50), 2.0, Duration::from_secs(1))
+        .jitter(true);
+    let handle = HttpTransport::new()
+        .retry(policy)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(handle.send(b"body".to_vec()).wait().unwrap(), b"body");
+    assert_eq!(server.received_bodies().len(), 3);
+}
+
+#[test]
+fn deadline_stops_retries() {
+    let server = Server::spawn(10, StatusCode::ServiceUnavailable);
+    let policy = RetryPolicy::exponential(Duration::from_millis(200), 2.0, Duration::from_secs(1))
+        .max_attempts(10)
+        .deadline(Duration::from_millis(300));
+    let handle = HttpTransport::new()
+        .retry(policy)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let start = Instant::now();
+    assert_http_error(
+        handle.send(b"body".to_vec()).wait(),
+        StatusCode::ServiceUnavailable,
+    );
+    // The second retry would have to wait 400 ms, which is past the deadline.
+    assert!(start.elapsed() < Duration::from_millis(400));
+    assert_eq!(server.received_bodies().len(), 2);
+}
+
+#[test]
+fn timeout_stops_retries() {
+    let server = Server::spawn(10, StatusCode::ServiceUnavailable);
+    let handle = HttpTransport::new()
+        .timeout(Duration::from_millis(250))
+        .retry(RetryPolicy::new(10, Duration::from_millis(100)))
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let start = Instant::now();
+    assert_http_error(
+        handle.send(b"body".to_vec()).wait(),
+        StatusCode::ServiceUnavailable,
+    );
+    assert!(start.elapsed() < Duration::from_millis(400));
+    assert!(server.received_bodies().len() <= 3);
+}
+
+#[test]
+fn no_retry_after_body_started() {
+    let (port, requests) = spawn_truncating_server();
+    let handle = HttpTransport::new()
+        .retry(RetryPolicy::new(3, Duration::from_millis(10)).retry_if(|_| true))
+        .standalone()
+        .unwrap()
+        .handle(&format!("http://127.0.0.1:{}", port))
+        .unwrap();
+
+    assert!(handle.send(b"body".to_vec()).wait().is_err());
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+fn assert_http_error<T>(result: jsonrpc_client_http::Result<T>, expected_status: StatusCode) {
+    match result {
+        Err(error) => match error.http_status() {
+            Some(status) if status == expected_status => (),
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("request did not fail"),
+    }
+}
+
+/// Service responding with the given status code to the first `failures` requests, then echoing
+/// back the request body with 200 OK. All received bodies are forwarded to a channel.
+#[derive(Clone)]
+pub struct FlakyService {
+    failures: usize,
+    failure_status: StatusCode,
+    requests: Arc<AtomicUsize>,
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl Service for FlakyService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        let request_number = self.requests.fetch_add(1, Ordering::SeqCst);
+        let status = if request_number < self.failures {
+            self.failure_status
+        } else {
+            StatusCode::Ok
+        };
+        let sender = self.sender.clone();
+
+        Box::new(request.body().concat2().map(move |body| {
+            let body = body.to_vec();
+            let _ = sender.send(body.clone());
+            Response::new()
+                .with_status(status)
+                .with_header(ContentType::json())
+                .with_body(body)
+        }))
+    }
+}
+
+pub struct Server {
+    pub port: u16,
+    pub bodies: mpsc::Receiver<Vec<u8>>,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(failures: usize, failure_status: StatusCode) -> Self {
+        let (sender, bodies) = mpsc::channel();
+        let service = FlakyService {
+            failures,
+            failure_status,
+            requests: Arc::new(AtomicUsize::new(0)),
+            sender,
+        };
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Self {
+            port,
+            bodies,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    fn received_bodies(&self) -> Vec<Vec<u8>> {
+        self.bodies.try_iter().collect()
+    }
+}
+
+/// Service responding with a `Content-Length` larger than the body it sends, making the client
+/// fail while reading the body.
+#[derive(Clone)]
+pub struct TruncatingService {
+    requests: Arc<AtomicUsize>,
+}
+
+impl Service for TruncatingService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = FutureResult<Self::Response, Self::Error>;
+
+    fn call(&self, _: Request) -> Self::Future {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        let (mut body_tx, body) = Body::pair();
+        let _ = body_tx.try_send(Ok(Chunk::from("partial")));
+        future::ok(
+            Response::new()
+                .with_header(ContentLength(1000))
+                .with_body(body),
+        )
+    }
+}
+
+/// Spawns a server running `TruncatingService`. Returns the port and the number of requests
+/// received.
+fn spawn_truncating_server() -> (u16, Arc<AtomicUsize>) {
+    let service = TruncatingService {
+        requests: Arc::new(AtomicUsize::new(0)),
+    };
+    let requests = service.requests.clone();
+    let (port_tx, port_rx) = oneshot::channel();
+
+    thread::spawn(move || {
+        let address = "127.0.0.1:0".parse().unwrap();
+        let server = Http::new()
+            .bind(&address, move || Ok(service.clone()))
+            .unwrap();
+        port_tx.send(server.local_addr().unwrap().port()).unwrap();
+        server.run().unwrap();
+    });
+
+    (port_rx.wait().unwrap(), requests)
+}
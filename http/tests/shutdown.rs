@@ -0,0 +1,90 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate futures;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+
+extern crate tokio_core;
+
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+
+mod common;
+
+use std::time::Duration;
+
+use futures::Future;
+use jsonrpc_client_http::{ErrorKind, HttpTransport};
+use tokio_core::reactor::Core;
+
+use common::{MockRpcClient, MockRpcServer};
+
+#[test]
+fn shutdown_waits_for_queued_requests() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut client = MockRpcClient::new(transport.handle(&uri).unwrap());
+
+    let rpc_future1 = client.slow_to_upper("first", 100);
+    let rpc_future2 = client.to_upper("second");
+    transport.shutdown(Duration::from_secs(5)).unwrap();
+
+    assert_eq!("FIRST", rpc_future1.wait().unwrap());
+    assert_eq!("SECOND", rpc_future2.wait().unwrap());
+}
+
+#[test]
+fn requests_after_shutdown_fail() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut client = MockRpcClient::new(transport.handle(&uri).unwrap());
+    transport.clone().shutdown(Duration::from_secs(5)).unwrap();
+
+    assert!(client.to_upper("too late").wait().is_err());
+    assert!(transport.handle(&uri).is_ok());
+    transport.shutdown(Duration::from_secs(5)).unwrap();
+}
+
+#[test]
+fn shutdown_times_out() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut client = MockRpcClient::new(transport.handle(&uri).unwrap());
+
+    let rpc_future = client.slow_to_upper("slow", 500);
+    let error = transport.shutdown(Duration::from_millis(50)).unwrap_err();
+    match error.kind() {
+        &ErrorKind::TokioCoreError(_) => (),
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+    assert_eq!("SLOW", rpc_future.wait().unwrap());
+}
+
+#[test]
+fn shared_shutdown_returns_right_away() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+
+    let mut core = Core::new().unwrap();
+    let transport = HttpTransport::new().shared(&core.handle()).unwrap();
+    let mut client = MockRpcClient::new(transport.handle(&uri).unwrap());
+    transport.shutdown(Duration::from_secs(5)).unwrap();
+    core.turn(Some(Duration::from_millis(10)));
+
+    assert!(core.run(client.to_upper("too late")).is_err());
+}
@@ -0,0 +1,373 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::io;
+use std::net::TcpListener;
+
+use futures::future::{Future, FutureResult, IntoFuture};
+use futures::sync::oneshot;
+use hyper::header::ContentType;
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::{call_method, send_notification, Transport};
+use jsonrpc_client_http::{ErrorKind, HttpTransport, StatusCode};
+
+const JSON_RPC_ERROR: &[u8] =
+    br#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"Rate limited"},"id":1}"#;
+
+#[test]
+fn error_body_is_kept_in_the_error() {
+    let server = Server::spawn(
+        StatusCode::TooManyRequests,
+        Some(ContentType::json()),
+        JSON_RPC_ERROR,
+    );
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_http_error_with_body(
+        handle.send(Vec::new()).wait(),
+        StatusCode::TooManyRequests,
+        JSON_RPC_ERROR,
+    );
+}
+
+#[test]
+fn json_error_body_is_passed_on() {
+    let server = Server::spawn(
+        StatusCode::TooManyRequests,
+        Some(ContentType::json()),
+        JSON_RPC_ERROR,
+    );
+    let mut handle = HttpTransport::new()
+        .accept_non_200_bodies(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let error = call_method::<_, _, String>(&mut handle, "ping".to_owned(), ())
+        .call()
+        .unwrap_err();
+    match *error.kind() {
+        jsonrpc_client_core::ErrorKind::JsonRpcError(ref error) => {
+            assert_eq!(error.message, "Rate limited")
+        }
+        ref kind => panic!("unexpected error: {:?}", kind),
+    }
+}
+
+#[test]
+fn empty_error_body_is_an_http_error() {
+    let server = Server::spawn(StatusCode::BadRequest, Some(ContentType::json()), b"");
+    let handle = HttpTransport::new()
+        .accept_non_200_bodies(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_http_error(handle.send(Vec::new()).wait(), StatusCode::BadRequest);
+}
+
+#[test]
+fn non_json_error_body_is_kept_in_the_error() {
+    let server = Server::spawn(
+        StatusCode::BadGateway,
+        Some(ContentType::html()),
+        b"<h1>Bad Gateway</h1>",
+    );
+    let handle = HttpTransport::new()
+        .accept_non_200_bodies(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_http_error_with_body(
+        handle.send(Vec::new()).wait(),
+        StatusCode::BadGateway,
+        b"<h1>Bad Gateway</h1>",
+    );
+}
+
+#[test]
+fn huge_error_body_is_an_http_error() {
+    let server = Server::spawn(
+        StatusCode::InternalServerError,
+        Some(ContentType::json()),
+        &vec![b' '; 2 * 1024 * 1024],
+    );
+    let handle = HttpTransport::new()
+        .accept_non_200_bodies(true)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_http_error(
+        handle.send(Vec::new()).wait(),
+        StatusCode::InternalServerError,
+    );
+}
+
+#[test]
+fn any_2xx_status_is_accepted_by_default() {
+    let accepted = Server::spawn(StatusCode::Accepted, Some(ContentType::json()), b"[]");
+    let no_content = Server::spawn(StatusCode::NoContent, None, b"");
+    let transport = HttpTransport::new().standalone().unwrap();
+
+    let handle = transport.handle(&accepted.uri()).unwrap();
+    assert_eq!(handle.send(Vec::new()).wait().unwrap(), b"[]");
+    let handle = transport.handle(&no_content.uri()).unwrap();
+    assert_eq!(handle.send(Vec::new()).wait().unwrap(), b"");
+}
+
+#[test]
+fn empty_body_is_accepted_for_notifications() {
+    let no_content = Server::spawn(StatusCode::NoContent, None, b"");
+    let ok = Server::spawn(StatusCode::Ok, Some(ContentType::json()), b"");
+    let transport = HttpTransport::new().standalone().unwrap();
+
+    for server in &[no_content, ok] {
+        let mut handle = transport.handle(&server.uri()).unwrap();
+        send_notification(&mut handle, "log".to_owned(), ("message",))
+            .call()
+            .unwrap();
+    }
+}
+
+#[test]
+fn custom_accept_status() {
+    let server = Server::spawn(StatusCode::Accepted, Some(ContentType::json()), b"[]");
+    let handle = HttpTransport::new()
+        .accept_status(|status| status == StatusCode::Ok)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_http_error_with_body(handle.send(Vec::new()).wait(), StatusCode::Accepted, b"[]");
+}
+
+#[test]
+fn empty_success_with_custom_accept_status() {
+    let accepted = Server::spawn(StatusCode::Accepted, None, b"");
+    let no_content = Server::spawn(StatusCode::NoContent, None, b"");
+    let ok = Server::spawn(StatusCode::Ok, Some(ContentType::json()), b"");
+    let transport = HttpTransport::new()
+        .accept_status(|status| status == StatusCode::Ok)
+        .allow_empty_success(true)
+        .standalone()
+        .unwrap();
+
+    for server in &[accepted, no_content, ok] {
+        let mut handle = transport.handle(&server.uri()).unwrap();
+        call_method::<_, _, ()>(&mut handle, "store".to_owned(), ("value",))
+            .call()
+            .unwrap();
+        let missing = call_method::<_, _, Option<String>>(&mut handle, "load".to_owned(), ())
+            .call()
+            .unwrap();
+        assert_eq!(missing, None);
+        let error = call_method::<_, _, String>(&mut handle, "load".to_owned(), ())
+            .call()
+            .unwrap_err();
+        match *error.kind() {
+            jsonrpc_client_core::ErrorKind::ResponseError(_) => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+}
+
+#[test]
+fn http_error_carries_request_id() {
+    let server = Server::spawn(StatusCode::BadRequest, Some(ContentType::json()), b"");
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    let request = br#"{"jsonrpc": "2.0", "method": "ping", "id": 42}"#.to_vec();
+    let error = handle.send(request).wait().unwrap_err();
+    assert_eq!(error.request_id(), Some("42"));
+    assert!(error.to_string().ends_with("(request id 42)"), "{}", error);
+
+    // A batch has no single id.
+    let batch = br#"[{"jsonrpc": "2.0", "method": "ping", "id": 1}]"#.to_vec();
+    assert_eq!(handle.send(batch).wait().unwrap_err().request_id(), None);
+}
+
+#[test]
+fn http_error_names_method_and_uri() {
+    let server = Server::spawn(StatusCode::BadGateway, None, b"");
+    let handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&format!("{}/rpc?key=secret", server.uri()))
+        .unwrap();
+
+    let request = br#"{"jsonrpc": "2.0", "method": "eth_getLogs", "id": 1}"#.to_vec();
+    let error = handle.send(request).wait().unwrap_err();
+    let context = error.context().unwrap();
+    assert_eq!(context.method(), Some("eth_getLogs"));
+    assert_eq!(context.uri().unwrap().query(), Some("key=secret"));
+    // The query is left out of the message.
+    let expected = format!(
+        "Http error. Status code 502 Bad Gateway calling \"eth_getLogs\" on {}/rpc (request id 1)",
+        server.uri()
+    );
+    assert_eq!(error.to_string(), expected);
+
+    let server = Server::spawn(StatusCode::BadGateway, None, b"upstream down");
+    let handle = handle.with_uri(&format!("{}/rpc", server.uri())).unwrap();
+    let request = br#"{"jsonrpc": "2.0", "method": "eth_getLogs", "id": 2}"#.to_vec();
+    let error = handle.send(request).wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::HttpErrorWithBody(_, ref body, _) => assert_eq!(&body[..], b"upstream down"),
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+    let expected = format!(
+        "Http error. Status code 502 Bad Gateway calling \"eth_getLogs\" on {}/rpc (request id 2)",
+        server.uri()
+    );
+    assert_eq!(error.to_string(), expected);
+
+    // Nothing listens on the port once the listener is dropped.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let uri = format!("http://127.0.0.1:{}/rpc", port);
+    let handle = handle.with_uri(&uri).unwrap();
+    let request = br#"{"jsonrpc": "2.0", "method": "eth_getLogs", "id": 3}"#.to_vec();
+    let error = handle.send(request).wait().unwrap_err();
+    match *error.kind() {
+        ErrorKind::ConnectionFailed(kind, _) => assert_eq!(kind, io::ErrorKind::ConnectionRefused),
+        ref kind => panic!("unexpected error: {}", kind),
+    }
+    assert_eq!(error.context().unwrap().method(), Some("eth_getLogs"));
+    let message = error.to_string();
+    assert!(
+        message.starts_with("The connection to the server failed"),
+        "{}",
+        message
+    );
+    assert!(
+        message.ends_with(&format!(
+            " calling \"eth_getLogs\" on {} (request id 3)",
+            uri
+        )),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn accept_status_can_accept_error_status() {
+    let server = Server::spawn(StatusCode::NotFound, Some(ContentType::json()), b"[]");
+    let handle = HttpTransport::new()
+        .accept_status(|status| status.is_success() || status == StatusCode::NotFound)
+        .standalone()
+        .unwrap()
+        .handle(&server.uri())
+        .unwrap();
+
+    assert_eq!(handle.send(Vec::new()).wait().unwrap(), b"[]");
+}
+
+fn assert_http_error<T>(result: jsonrpc_client_http::Result<T>, expected_status: StatusCode) {
+    match result {
+        Err(error) => match error.kind() {
+            &ErrorKind::HttpError(status, _) if status == expected_status => (),
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("request did not fail"),
+    }
+}
+
+fn assert_http_error_with_body<T>(
+    result: jsonrpc_client_http::Result<T>,
+    expected_status: StatusCode,
+    expected_body: &[u8],
+) {
+    match result {
+        Err(error) => {
+            assert_eq!(error.http_status(), Some(expected_status));
+            match *error.kind() {
+                ErrorKind::HttpErrorWithBody(_, ref body, _) => {
+                    assert_eq!(&body[..], expected_body)
+                }
+                _ => panic!("unexpected error: {}", error),
+            }
+        }
+        Ok(_) => panic!("request did not fail"),
+    }
+}
+
+/// Service responding to all requests with the same status code, content type and body.
+#[derive(Clone)]
+pub struct BodyService {
+    status: StatusCode,
+    content_type: Option<ContentType>,
+    body: Vec<u8>,
+}
+
+impl Service for BodyService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = FutureResult<Self::Response, Self::Error>;
+
+    fn call(&self, _: Request) -> Self::Future {
+        let mut response = Response::new()
+            .with_status(self.status)
+            .with_body(self.body.clone());
+        if let Some(ref content_type) = self.content_type {
+            response.headers_mut().set(content_type.clone());
+        }
+        Ok(response).into_future()
+    }
+}
+
+pub struct Server {
+    pub port: u16,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn(status: StatusCode, content_type: Option<ContentType>, body: &[u8]) -> Self {
+        let service = BodyService {
+            status,
+            content_type,
+            body: body.to_vec(),
+        };
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Self {
+            port,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+}
@@ -0,0 +1,126 @@
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate tokio_service;
+
+mod common;
+
+use std::cmp;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::sync::oneshot;
+use futures::{stream, Future, Stream};
+use hyper::header::TransferEncoding;
+use hyper::{Request, Response};
+use tokio_service::Service;
+
+use jsonrpc_client_core::{call_method_streaming, BodyStream, StreamingTransport};
+use jsonrpc_client_http::HttpTransport;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[test]
+fn large_body_is_streamed() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let mut handle = transport.handle(&server.uri()).unwrap();
+
+    // 100 MB of parameters, produced only as fast as the server reads them.
+    let chunks = 100 * 1024 * 1024 / CHUNK_SIZE;
+    let max_lead = Arc::new(AtomicUsize::new(0));
+    let (received, stream_max_lead) = (server.received.clone(), max_lead.clone());
+    let string = stream::iter_ok(0..chunks).map(move |index| {
+        let lead = (index * CHUNK_SIZE).saturating_sub(received.load(Ordering::SeqCst));
+        let max_lead = cmp::max(stream_max_lead.load(Ordering::SeqCst), lead);
+        stream_max_lead.store(max_lead, Ordering::SeqCst);
+        vec![b'a'; CHUNK_SIZE]
+    });
+    let params = stream::once(Ok(b"[\"".to_vec()))
+        .chain(string)
+        .chain(stream::once(Ok(b"\"]".to_vec())));
+
+    let length: usize = call_method_streaming(&mut handle, "import", Box::new(params))
+        .wait()
+        .unwrap();
+    assert!(length > chunks * CHUNK_SIZE);
+    let max_lead = max_lead.load(Ordering::SeqCst);
+    assert!(max_lead < 16 * 1024 * 1024, "{} bytes buffered", max_lead);
+}
+
+#[test]
+fn stream_error_fails_request() {
+    let server = Server::spawn();
+    let transport = HttpTransport::new().standalone().unwrap();
+    let handle = transport.handle(&server.uri()).unwrap();
+
+    let error = io::Error::new(io::ErrorKind::UnexpectedEof, "file truncated");
+    let chunks = stream::once(Ok(b"{".to_vec())).chain(stream::once(Err(error)));
+    let body: BodyStream = Box::new(chunks);
+    assert!(handle.send_stream(body).wait().is_err());
+}
+
+/// Service reading chunked request bodies without keeping them, and responding with their length
+/// as the result of a JSON-RPC response with id 1.
+#[derive(Clone)]
+struct SinkService {
+    received: Arc<AtomicUsize>,
+}
+
+impl Service for SinkService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn call(&self, request: Request) -> Self::Future {
+        assert!(request.headers().has::<TransferEncoding>());
+        let received = self.received.clone();
+        Box::new(
+            request
+                .body()
+                .fold(0, move |length, chunk| {
+                    received.fetch_add(chunk.len(), Ordering::SeqCst);
+                    Ok::<_, hyper::Error>(length + chunk.len())
+                })
+                .map(|length| {
+                    let response = format!(r#"{{"jsonrpc":"2.0","id":1,"result":{}}}"#, length);
+                    Response::new().with_body(response)
+                }),
+        )
+    }
+}
+
+struct Server {
+    port: u16,
+    received: Arc<AtomicUsize>,
+    _shutdown_tx: oneshot::Sender<()>,
+}
+
+impl Server {
+    fn spawn() -> Self {
+        let received = Arc::new(AtomicUsize::new(0));
+        let service = SinkService {
+            received: received.clone(),
+        };
+
+        let (port, shutdown_tx) = common::spawn_server(service);
+
+        Server {
+            port,
+            received,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    fn uri(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.port)
+    }
+}
@@ -21,13 +21,22 @@ extern crate jsonrpc_macros;
 #[macro_use]
 mod common;
 
+use std::io;
+use std::mem;
+use std::net::SocketAddr;
 use std::sync::mpsc;
 use std::time::Duration;
 
-use futures::future::{Either, Future};
+use futures::future::{self, Either, Future};
 use jsonrpc_client_core::Transport;
-use jsonrpc_client_http::{ErrorKind, HttpTransport};
-use jsonrpc_http_server::hyper::server::Http;
+use jsonrpc_client_http::{
+    ErrorKind, Handle, HttpTransport, HttpTransportBuilder, TimeoutConnector,
+};
+use jsonrpc_http_server::hyper::client::{Client, Service};
+use jsonrpc_http_server::hyper::header::ContentType;
+use jsonrpc_http_server::hyper::server::{Http, Request, Response};
+use jsonrpc_http_server::hyper::{self, Body, Chunk, Uri};
+use tokio_core::net::TcpStream;
 use tokio_core::reactor::{Core, Timeout};
 
 // Use a simple RPC API for testing purposes.
@@ -112,8 +121,273 @@ fn timeout_error() {
         Ok(Either::B(_)) => panic!("request didn't time out as expected"),
         Err(Either::A((error, _))) => panic!("test timeout error: {}", error),
         Err(Either::B((error, _))) => match error.kind() {
-            &ErrorKind::RequestTimeout => (),
+            &ErrorKind::RequestTimeout(..) => (),
             _ => panic!("failed to send request: {}", error),
         },
     }
 }
+
+#[test]
+fn request_timeout_carries_timeout() {
+    let address = spawn_server(|| UnresponsiveService);
+    let handle = HttpTransport::new()
+        .total_timeout(Duration::from_millis(100))
+        .standalone()
+        .unwrap()
+        .handle(&format!("http://{}", address))
+        .unwrap();
+
+    let request = br#"{"jsonrpc": "2.0", "method": "ping", "id": "abc"}"#.to_vec();
+    match handle.send(request).wait() {
+        Err(ref error) => match error.kind() {
+            &ErrorKind::RequestTimeout(timeout, elapsed, ref context) => {
+                assert_eq!(timeout, Duration::from_millis(100));
+                assert!(elapsed >= timeout);
+                assert_eq!(context.request_id(), Some("\"abc\""));
+                assert_eq!(context.method(), Some("ping"));
+            }
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("request didn't time out as expected"),
+    }
+}
+
+#[test]
+fn response_header_timeout_error() {
+    let address = spawn_server(|| UnresponsiveService);
+    let handle = HttpTransport::new()
+        .response_header_timeout(Duration::from_millis(100))
+        .timeout(Duration::from_secs(10))
+        .standalone()
+        .unwrap()
+        .handle(&format!("http://{}", address))
+        .unwrap();
+
+    match handle.send(Vec::new()).wait() {
+        Err(ref error) => match error.kind() {
+            &ErrorKind::ResponseTimeout(timeout, _, _) => {
+                assert_eq!(timeout, Duration::from_millis(100))
+            }
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("request didn't time out as expected"),
+    }
+}
+
+#[test]
+fn response_header_timeout_does_not_limit_slow_bodies() {
+    let address = spawn_server(|| StallingBodyService);
+    let handle = HttpTransport::new()
+        .response_header_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_millis(100))
+        .standalone()
+        .unwrap()
+        .handle(&format!("http://{}", address))
+        .unwrap();
+
+    // The headers arrive in time, so it is the total timeout that ends the request.
+    match handle.send(Vec::new()).wait() {
+        Err(ref error) => match error.kind() {
+            &ErrorKind::RequestTimeout(..) => (),
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("request didn't time out as expected"),
+    }
+}
+
+#[test]
+fn handle_timeout_overrides_transport_timeout() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+
+    let transport = HttpTransport::new()
+        .timeout(Duration::from_millis(50))
+        .standalone()
+        .unwrap();
+
+    let mut slow_handle = transport.handle(&uri).unwrap();
+    slow_handle.set_timeout(Some(Duration::from_secs(10)));
+    let mut slow_client = MockRpcClient::new(slow_handle);
+    let mut fast_client = MockRpcClient::new(transport.handle(&uri).unwrap());
+
+    let result = slow_client.slow_to_upper("sLoW", 200).wait().unwrap();
+    assert_eq!("SLOW", result);
+    assert!(fast_client.slow_to_upper("fast", 200).wait().is_err());
+}
+
+#[test]
+fn cleared_handle_timeout_falls_back_to_transport_timeout() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+
+    let mut handle = HttpTransport::new()
+        .timeout(Duration::from_millis(50))
+        .standalone()
+        .unwrap()
+        .handle(&uri)
+        .unwrap();
+    handle
+        .set_timeout(Some(Duration::from_secs(10)))
+        .set_timeout(None);
+    let mut client = MockRpcClient::new(handle);
+
+    assert!(client.slow_to_upper("fast", 200).wait().is_err());
+}
+
+#[test]
+fn zero_handle_timeout_fails_immediately() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+
+    let mut handle = HttpTransport::new()
+        .standalone()
+        .unwrap()
+        .handle(&uri)
+        .unwrap();
+    handle.set_timeout(Some(Duration::from_secs(0)));
+
+    match handle.send(Vec::new()).wait() {
+        Err(ref error) => match error.kind() {
+            &ErrorKind::RequestTimeout(..) => (),
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("request didn't time out as expected"),
+    }
+}
+
+#[test]
+fn time_waiting_in_queue_counts_against_timeout() {
+    let (address_tx, address_rx) = mpsc::channel();
+    ::std::thread::spawn(move || {
+        let address = "127.0.0.1:0".parse().unwrap();
+        let server = Http::new()
+            .bind(&address, || Ok(UnresponsiveService))
+            .unwrap();
+        address_tx.send(server.local_addr().unwrap()).unwrap();
+        server.run().unwrap();
+    });
+    let address = address_rx.recv().unwrap();
+
+    // Requests are sent one at a time, so the second one waits for the first to time out.
+    let handle = HttpTransport::new()
+        .timeout(Duration::from_millis(100))
+        .standalone()
+        .unwrap()
+        .handle(&format!("http://{}", address))
+        .unwrap();
+    let first = handle.send(Vec::new());
+    let second = handle.send(Vec::new());
+
+    match first.wait() {
+        Err(ref error) => match error.kind() {
+            &ErrorKind::RequestTimeout(..) => (),
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("request didn't time out as expected"),
+    }
+    match second.wait() {
+        Err(ref error) => match error.kind() {
+            &ErrorKind::RequestTimeout(..) => {
+                assert!(error
+                    .iter()
+                    .any(|cause| cause.to_string().contains("queue")))
+            }
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("request didn't time out as expected"),
+    }
+}
+
+#[test]
+fn connect_timeout_error() {
+    let handle = HttpTransportBuilder::with_client(|handle: &Handle| {
+        let connector =
+            TimeoutConnector::new(NeverConnect, Some(Duration::from_millis(50)), handle);
+        Ok(Client::configure().connector(connector).build(handle)) as Result<_, io::Error>
+    })
+    .timeout(Duration::from_secs(10))
+    .standalone()
+    .unwrap()
+    .handle("http://127.0.0.1:1/")
+    .unwrap();
+
+    match handle.send(Vec::new()).wait() {
+        Err(ref error) => match error.kind() {
+            &ErrorKind::ConnectTimeout(..) => (),
+            _ => panic!("unexpected error: {}", error),
+        },
+        Ok(_) => panic!("connect didn't time out as expected"),
+    }
+}
+
+#[test]
+fn connect_timeout_does_not_limit_slow_responses() {
+    let server = MockRpcServer::spawn();
+    let uri = format!("http://{}", server.address());
+
+    let transport = HttpTransport::new()
+        .connect_timeout(Duration::from_millis(50))
+        .timeout(Duration::from_secs(10))
+        .standalone()
+        .unwrap()
+        .handle(&uri)
+        .unwrap();
+    let mut client = MockRpcClient::new(transport);
+
+    let result = client.slow_to_upper("sLoW", 200).wait().unwrap();
+    assert_eq!("SLOW", result);
+}
+
+/// Spawns a server on its own thread, running the services created by `new_service`.
+fn spawn_server<S, F>(new_service: F) -> SocketAddr
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error> + 'static,
+    F: Fn() -> S + Send + 'static,
+{
+    let (address_tx, address_rx) = mpsc::channel();
+    ::std::thread::spawn(move || {
+        let address = "127.0.0.1:0".parse().unwrap();
+        let server = Http::new()
+            .bind(&address, move || Ok(new_service()))
+            .unwrap();
+        address_tx.send(server.local_addr().unwrap()).unwrap();
+        server.run().unwrap();
+    });
+    address_rx.recv().unwrap()
+}
+
+/// Service responding with headers and the start of a body, but never finishing the body.
+struct StallingBodyService;
+
+impl Service for StallingBodyService {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = future::FutureResult<Response, hyper::Error>;
+
+    fn call(&self, _: Request) -> Self::Future {
+        let (mut body_tx, body) = Body::pair();
+        let _ = body_tx.try_send(Ok(Chunk::from("{\"jsonrpc\"")));
+        // Leaking the sender keeps the body open forever.
+        mem::forget(body_tx);
+        future::ok(
+            Response::new()
+                .with_header(ContentType::json())
+                .with_body(body),
+        )
+    }
+}
+
+/// Connector whose connection attempts never finish, like when dialing an unreachable host.
+struct NeverConnect;
+
+impl Service for NeverConnect {
+    type Request = Uri;
+    type Response = TcpStream;
+    type Error = io::Error;
+    type Future = future::Empty<TcpStream, io::Error>;
+
+    fn call(&self, _: Uri) -> Self::Future {
+        future::empty()
+    }
+}
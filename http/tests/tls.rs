@@ -0,0 +1,69 @@
+#![cfg(feature = "tls")]
+
+extern crate jsonrpc_client_http;
+
+use jsonrpc_client_http::{ErrorKind, HttpTransport, TlsConfig};
+
+#[test]
+fn default_config() {
+    HttpTransport::with_tls_config(TlsConfig::new())
+        .standalone()
+        .unwrap();
+}
+
+#[test]
+fn invalid_root_certificate_fails_on_build() {
+    let config = TlsConfig::new().add_root_certificate_pem(b"not a certificate");
+    let result = HttpTransport::with_tls_config(config).standalone();
+    match result.map(|_| ()).unwrap_err().kind() {
+        &ErrorKind::ClientCreatorError => (),
+        kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn invalid_builder_root_certificate_fails_on_build() {
+    let pem = b"-----BEGIN CERTIFICATE-----\nnot a certificate\n-----END CERTIFICATE-----\n";
+    let result = HttpTransport::with_tls()
+        .add_root_certificate(pem)
+        .add_root_certificate(b"not a certificate either")
+        .standalone();
+    match result.map(|_| ()).unwrap_err().kind() {
+        &ErrorKind::ClientCreatorError => (),
+        kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn invalid_identity_fails_on_build() {
+    let config = TlsConfig::new().identity_pkcs12(b"not an identity", "password");
+    let result = HttpTransport::with_tls_config(config).standalone();
+    match result.map(|_| ()).unwrap_err().kind() {
+        &ErrorKind::ClientCreatorError => (),
+        kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn invalid_builder_identity_fails_on_build() {
+    let result = HttpTransport::with_tls()
+        .identity(b"not an identity", "password")
+        .standalone();
+    match result.map(|_| ()).unwrap_err().kind() {
+        &ErrorKind::ClientCreatorError => (),
+        kind => panic!("unexpected error: {}", kind),
+    }
+}
+
+#[test]
+fn accept_invalid_certs() {
+    HttpTransport::with_tls()
+        .danger_accept_invalid_certs(true)
+        .standalone()
+        .unwrap();
+    let config = TlsConfig::new().danger_accept_invalid_certs(true);
+    HttpTransport::with_tls_config(config)
+        .danger_accept_invalid_certs(false)
+        .standalone()
+        .unwrap();
+}
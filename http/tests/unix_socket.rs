@@ -0,0 +1,118 @@
+#![cfg(feature = "unix-socket")]
+
+extern crate futures;
+extern crate jsonrpc_client_core;
+extern crate jsonrpc_client_http;
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use futures::Future;
+use jsonrpc_client_core::Transport;
+use jsonrpc_client_http::{Error, ErrorKind, HttpTransport};
+
+#[test]
+fn requests_are_sent_over_unix_socket() {
+    let server = Server::spawn("requests_are_sent");
+    let transport = HttpTransport::with_unix_socket().standalone().unwrap();
+    let handle = transport.unix_handle(&server.path, "/rpc").unwrap();
+
+    let body = handle.send(b"request".to_vec()).wait().unwrap();
+    assert_eq!(body, b"response");
+    let (request_line, host, body) = server
+        .requests
+        .recv_timeout(Duration::from_secs(1))
+        .unwrap();
+    assert_eq!(request_line, "POST /rpc HTTP/1.1");
+    assert_eq!(host, "localhost");
+    assert_eq!(body, b"request");
+}
+
+#[test]
+fn missing_socket_error() {
+    let path = socket_path("missing_socket");
+    let transport = HttpTransport::with_unix_socket().standalone().unwrap();
+    let handle = transport.unix_handle(&path, "/").unwrap();
+
+    match handle.send(Vec::new()).wait() {
+        Err(Error(ErrorKind::SocketNotFound(ref error_path), _)) if *error_path == path => (),
+        result => panic!("Expected SocketNotFound, got {:?}", result),
+    }
+}
+
+fn socket_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!(
+        "jsonrpc-client-http-{}-{}.sock",
+        process::id(),
+        name
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// Minimal HTTP server on a unix socket, answering every request with the body "response"
+/// labeled as JSON. Sends the request line, the Host header and the body of every request on a
+/// channel.
+pub struct Server {
+    pub path: PathBuf,
+    pub requests: mpsc::Receiver<(String, String, Vec<u8>)>,
+}
+
+impl Server {
+    fn spawn(name: &str) -> Self {
+        let path = socket_path(name);
+        let listener = UnixListener::bind(&path).unwrap();
+        let (requests_tx, requests) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = BufReader::new(stream.unwrap());
+                let mut request_line = String::new();
+                stream.read_line(&mut request_line).unwrap();
+                let mut host = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).unwrap();
+                    let line = line.trim().to_owned();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let mut parts = line.splitn(2, ": ");
+                    let name = parts.next().unwrap().to_lowercase();
+                    let value = parts.next().unwrap_or("").to_owned();
+                    if name == "host" {
+                        host = value;
+                    } else if name == "content-length" {
+                        content_length = value.parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                stream.read_exact(&mut body).unwrap();
+                stream
+                    .get_mut()
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                          Content-Length: 8\r\nConnection: close\r\n\r\nresponse",
+                    )
+                    .unwrap();
+                let _ = requests_tx.send((request_line.trim().to_owned(), host, body));
+            }
+        });
+
+        Server { path, requests }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
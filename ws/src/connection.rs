@@ -0,0 +1,245 @@
+use error_chain::ChainedError;
+use frame::{Message, MessageCodec};
+use futures::{Async, AsyncSink, Future, Poll, Sink, Stream};
+use serde_json::{self, Value as JsonValue};
+use std::collections::VecDeque;
+use tokio_codec::Framed;
+use tokio_io::{AsyncRead, AsyncWrite};
+use {CoreReceiver, ErrorKind, QueuedRequest, ResponseSender, Result};
+
+/// The status code sent in the close frame when all handles are dropped, meaning a normal
+/// closure.
+const CLOSE_NORMAL: [u8; 2] = [0x03, 0xE8];
+
+/// Returns the ids in a JSON-RPC 2.0 request or response, or in all the entries of a batch.
+/// Entries without an id are skipped, so notifications have no ids, but responses with a null id
+/// do.
+pub(crate) fn message_ids(json: &JsonValue) -> Vec<JsonValue> {
+    match *json {
+        JsonValue::Object(ref object) => object.get("id").cloned().into_iter().collect(),
+        JsonValue::Array(ref entries) => entries
+            .iter()
+            .filter_map(|entry| entry.get("id").cloned())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// A request written to the server and waiting for its response.
+struct PendingRequest {
+    ids: Vec<JsonValue>,
+    response_tx: ResponseSender,
+}
+
+/// Future sending the requests from the handles over a WebSocket connection, and handing the
+/// responses back to the pending requests they belong to.
+///
+/// Completes when the connection is closed or fails, after failing all pending requests with a
+/// `ConnectionLost` error. Then completes with the receiver for new requests, unless all
+/// handles were already dropped, so the caller can fail those as well.
+pub(crate) struct Connection<S> {
+    framed: Framed<S, MessageCodec>,
+    /// Receives requests from the handles. `None` once all handles are dropped.
+    request_rx: Option<CoreReceiver>,
+    /// Messages waiting to be written, since the connection is not ready for them. Notifications
+    /// have the channel to tell they are sent with them.
+    outgoing: VecDeque<(Message, Option<ResponseSender>)>,
+    /// Notifications written, but not yet flushed.
+    flushing: Vec<ResponseSender>,
+    pending: Vec<PendingRequest>,
+    close_sent: bool,
+    close_received: bool,
+}
+
+impl<S: AsyncRead + AsyncWrite> Connection<S> {
+    pub(crate) fn new(framed: Framed<S, MessageCodec>, request_rx: CoreReceiver) -> Self {
+        Connection {
+            framed,
+            request_rx: Some(request_rx),
+            outgoing: VecDeque::new(),
+            flushing: Vec::new(),
+            pending: Vec::new(),
+            close_sent: false,
+            close_received: false,
+        }
+    }
+
+    /// Drives the connection. Completes when the connection is closed.
+    fn poll_connection(&mut self) -> Poll<(), ::Error> {
+        loop {
+            match self.framed.poll()? {
+                Async::Ready(Some(message)) => self.handle_message(message),
+                Async::Ready(None) => return Ok(Async::Ready(())),
+                Async::NotReady => break,
+            }
+        }
+        self.receive_requests();
+        self.forget_canceled();
+        if self.request_rx.is_none()
+            && self.pending.is_empty()
+            && self.outgoing.is_empty()
+            && !self.close_sent
+        {
+            debug!("All handles dropped, closing the WebSocket connection");
+            self.send_close(CLOSE_NORMAL.to_vec());
+        }
+        self.write_outgoing()?;
+
+        if self.framed.poll_complete()?.is_ready() {
+            for written_tx in self.flushing.drain(..) {
+                let _ = written_tx.send(Ok(Vec::new()));
+            }
+            if self.close_received && self.outgoing.is_empty() {
+                return Ok(Async::Ready(()));
+            }
+        }
+        Ok(Async::NotReady)
+    }
+
+    fn handle_message(&mut self, message: Message) {
+        match message {
+            Message::Text(data) | Message::Binary(data) => self.respond(data),
+            Message::Ping(data) => {
+                if !self.close_sent {
+                    trace!("Answering ping from the server");
+                    self.outgoing.push_back((Message::Pong(data), None));
+                }
+            }
+            Message::Pong(_) => (),
+            Message::Close(payload) => {
+                debug!("The server closed the WebSocket connection");
+                self.close_received = true;
+                if !self.close_sent {
+                    // Echo the status code back, as specified by RFC 6455.
+                    self.send_close(payload.into_iter().take(2).collect());
+                }
+            }
+        }
+    }
+
+    /// Hands a message from the server to the pending request with a matching id. A response
+    /// with a null id, sent when the server can not parse the request, goes to the only pending
+    /// request if there is just one.
+    fn respond(&mut self, data: Vec<u8>) {
+        let ids = match serde_json::from_slice(&data) {
+            Ok(json) => message_ids(&json),
+            Err(_) => {
+                warn!("Ignoring message from the server that is not valid JSON");
+                return;
+            }
+        };
+        let index = self.pending.iter().position(|pending| {
+            ids.iter()
+                .any(|id| !id.is_null() && pending.ids.contains(id))
+        });
+        let index = match index {
+            Some(index) => index,
+            None if self.pending.len() == 1
+                && !ids.is_empty()
+                && ids.iter().all(JsonValue::is_null) =>
+            {
+                0
+            }
+            None => {
+                debug!("Ignoring message from the server not belonging to any request");
+                return;
+            }
+        };
+        let pending = self.pending.remove(index);
+        let _ = pending.response_tx.send(Ok(data));
+    }
+
+    fn receive_requests(&mut self) {
+        loop {
+            let request = match self.request_rx.as_mut().map(|request_rx| request_rx.poll()) {
+                Some(Ok(Async::Ready(Some(request)))) => request,
+                Some(Ok(Async::Ready(None))) | Some(Err(())) => {
+                    self.request_rx = None;
+                    break;
+                }
+                Some(Ok(Async::NotReady)) | None => break,
+            };
+            self.queue(request);
+        }
+    }
+
+    fn queue(&mut self, request: QueuedRequest) {
+        if self.close_sent {
+            let _ = request
+                .response_tx
+                .send(Err(ErrorKind::ConnectionLost.into()));
+            return;
+        }
+        let message = Message::Text(request.body);
+        if request.ids.is_empty() {
+            self.outgoing
+                .push_back((message, Some(request.response_tx)));
+        } else {
+            self.pending.push(PendingRequest {
+                ids: request.ids,
+                response_tx: request.response_tx,
+            });
+            self.outgoing.push_back((message, None));
+        }
+    }
+
+    /// Forgets the pending requests whose futures were dropped, ignoring their responses, so the
+    /// connection can close once all handles are dropped. Polling the senders for cancellation
+    /// wakes the connection up when a future is dropped later.
+    fn forget_canceled(&mut self) {
+        let mut index = 0;
+        while index < self.pending.len() {
+            match self.pending[index].response_tx.poll_cancel() {
+                Ok(Async::NotReady) => index += 1,
+                _ => {
+                    let canceled = self.pending.remove(index);
+                    trace!("Forgetting canceled request with ids {:?}", canceled.ids);
+                }
+            }
+        }
+    }
+
+    fn send_close(&mut self, payload: Vec<u8>) {
+        self.outgoing.push_back((Message::Close(payload), None));
+        self.close_sent = true;
+    }
+
+    fn write_outgoing(&mut self) -> Result<()> {
+        while let Some((message, written_tx)) = self.outgoing.pop_front() {
+            match self.framed.start_send(message)? {
+                AsyncSink::Ready => self.flushing.extend(written_tx),
+                AsyncSink::NotReady(message) => {
+                    self.outgoing.push_front((message, written_tx));
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn fail_requests(&mut self) {
+        let pending = self.pending.drain(..).map(|pending| pending.response_tx);
+        let outgoing = self
+            .outgoing
+            .drain(..)
+            .filter_map(|(_, written_tx)| written_tx);
+        for response_tx in pending.chain(outgoing).chain(self.flushing.drain(..)) {
+            let _ = response_tx.send(Err(ErrorKind::ConnectionLost.into()));
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> Future for Connection<S> {
+    type Item = Option<CoreReceiver>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, ()> {
+        match self.poll_connection() {
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Ok(Async::Ready(())) => debug!("WebSocket connection closed"),
+            Err(error) => error!("WebSocket connection failed: {}", error.display_chain()),
+        }
+        self.fail_requests();
+        Ok(Async::Ready(self.request_rx.take()))
+    }
+}
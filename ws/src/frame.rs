@@ -0,0 +1,288 @@
+use bytes::{BufMut, BytesMut};
+use rand;
+use tokio_codec::{Decoder, Encoder};
+use {Error, ErrorKind, Result};
+
+/// The largest message accepted from the server, after joining its fragments. Larger messages
+/// fail the connection with a `ProtocolError`.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// A complete WebSocket message, or control frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Message {
+    Text(Vec<u8>),
+    Binary(Vec<u8>),
+    /// A close frame, with the status code and reason as the payload.
+    Close(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+}
+
+/// Codec for reading and writing WebSocket messages, as specified by RFC 6455. Fragmented
+/// messages are joined before they are returned. Written messages are never fragmented.
+#[derive(Debug)]
+pub(crate) struct MessageCodec {
+    /// Set for the client side of a connection. Clients mask the frames they send, servers must
+    /// not.
+    client: bool,
+    /// The opcode and payload so far of a fragmented message being received.
+    fragments: Option<(u8, Vec<u8>)>,
+}
+
+impl MessageCodec {
+    pub(crate) fn client() -> Self {
+        MessageCodec {
+            client: true,
+            fragments: None,
+        }
+    }
+
+    /// Returns a codec for the server side of a connection. Only used by the tests.
+    #[cfg(test)]
+    pub(crate) fn server() -> Self {
+        MessageCodec {
+            client: false,
+            fragments: None,
+        }
+    }
+
+    /// Adds a frame to the message being received. Returns the message if it is complete.
+    fn assemble(&mut self, frame: Frame) -> Result<Option<Message>> {
+        match frame.opcode {
+            OPCODE_CONTINUATION => match self.fragments.take() {
+                Some((opcode, mut payload)) => {
+                    if payload.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+                        bail!(ErrorKind::ProtocolError("Message too large"));
+                    }
+                    payload.extend_from_slice(&frame.payload);
+                    if frame.fin {
+                        Ok(Some(data_message(opcode, payload)))
+                    } else {
+                        self.fragments = Some((opcode, payload));
+                        Ok(None)
+                    }
+                }
+                None => bail!(ErrorKind::ProtocolError("Unexpected continuation frame")),
+            },
+            OPCODE_TEXT | OPCODE_BINARY => {
+                if self.fragments.is_some() {
+                    bail!(ErrorKind::ProtocolError("Expected a continuation frame"));
+                }
+                if frame.fin {
+                    Ok(Some(data_message(frame.opcode, frame.payload)))
+                } else {
+                    self.fragments = Some((frame.opcode, frame.payload));
+                    Ok(None)
+                }
+            }
+            OPCODE_CLOSE | OPCODE_PING | OPCODE_PONG => {
+                if !frame.fin || frame.payload.len() > 125 {
+                    bail!(ErrorKind::ProtocolError("Invalid control frame"));
+                }
+                Ok(Some(match frame.opcode {
+                    OPCODE_CLOSE => Message::Close(frame.payload),
+                    OPCODE_PING => Message::Ping(frame.payload),
+                    _ => Message::Pong(frame.payload),
+                }))
+            }
+            _ => bail!(ErrorKind::ProtocolError("Unknown opcode")),
+        }
+    }
+}
+
+impl Decoder for MessageCodec {
+    type Item = Message;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Message>> {
+        while let Some(frame) = parse_frame(src, !self.client)? {
+            if let Some(message) = self.assemble(frame)? {
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Encoder for MessageCodec {
+    type Item = Message;
+    type Error = Error;
+
+    fn encode(&mut self, message: Message, dst: &mut BytesMut) -> Result<()> {
+        let (opcode, payload) = match message {
+            Message::Text(payload) => (OPCODE_TEXT, payload),
+            Message::Binary(payload) => (OPCODE_BINARY, payload),
+            Message::Close(payload) => (OPCODE_CLOSE, payload),
+            Message::Ping(payload) => (OPCODE_PING, payload),
+            Message::Pong(payload) => (OPCODE_PONG, payload),
+        };
+        let mask_bit = if self.client { 0x80 } else { 0 };
+        dst.reserve(14 + payload.len());
+        dst.put_u8(0x80 | opcode);
+        if payload.len() < 126 {
+            dst.put_u8(mask_bit | payload.len() as u8);
+        } else if payload.len() <= 0xFFFF {
+            dst.put_u8(mask_bit | 126);
+            dst.put_u16_be(payload.len() as u16);
+        } else {
+            dst.put_u8(mask_bit | 127);
+            dst.put_u64_be(payload.len() as u64);
+        }
+        if self.client {
+            let mask: [u8; 4] = rand::random();
+            dst.put_slice(&mask);
+            for (i, byte) in payload.iter().enumerate() {
+                dst.put_u8(byte ^ mask[i % 4]);
+            }
+        } else {
+            dst.put_slice(&payload);
+        }
+        Ok(())
+    }
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Removes one frame from the start of `src`, unmasking its payload. Returns `None` if `src`
+/// does not contain a complete frame yet. Frames must be masked if `masked` is set, and not
+/// masked otherwise.
+fn parse_frame(src: &mut BytesMut, masked: bool) -> Result<Option<Frame>> {
+    if src.len() < 2 {
+        return Ok(None);
+    }
+    if src[0] & 0x70 != 0 {
+        bail!(ErrorKind::ProtocolError("Reserved bits set"));
+    }
+    if (src[1] & 0x80 != 0) != masked {
+        bail!(ErrorKind::ProtocolError("Invalid masking"));
+    }
+    let fin = src[0] & 0x80 != 0;
+    let opcode = src[0] & 0x0F;
+    let (len, mut header_len) = match src[1] & 0x7F {
+        126 => {
+            if src.len() < 4 {
+                return Ok(None);
+            }
+            ((u64::from(src[2]) << 8) | u64::from(src[3]), 4)
+        }
+        127 => {
+            if src.len() < 10 {
+                return Ok(None);
+            }
+            let len = src[2..10]
+                .iter()
+                .fold(0, |len, &byte| (len << 8) | u64::from(byte));
+            (len, 10)
+        }
+        len => (u64::from(len), 2),
+    };
+    if len > MAX_MESSAGE_SIZE as u64 {
+        bail!(ErrorKind::ProtocolError("Message too large"));
+    }
+    let len = len as usize;
+    let mask_len = if masked { 4 } else { 0 };
+    if src.len() < header_len + mask_len + len {
+        return Ok(None);
+    }
+
+    let mut mask = [0; 4];
+    if masked {
+        mask.copy_from_slice(&src[header_len..header_len + 4]);
+        header_len += 4;
+    }
+    src.split_to(header_len);
+    let mut payload = src.split_to(len).to_vec();
+    if masked {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+    Ok(Some(Frame {
+        fin,
+        opcode,
+        payload,
+    }))
+}
+
+fn data_message(opcode: u8, payload: Vec<u8>) -> Message {
+    if opcode == OPCODE_TEXT {
+        Message::Text(payload)
+    } else {
+        Message::Binary(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let messages = vec![
+            Message::Text(b"{}".to_vec()),
+            Message::Binary(vec![7; 300]),
+            Message::Text(vec![b' '; 70_000]),
+            Message::Ping(b"ping".to_vec()),
+            Message::Close(vec![0x03, 0xE8]),
+        ];
+        let mut buffer = BytesMut::new();
+        for message in &messages {
+            MessageCodec::client()
+                .encode(message.clone(), &mut buffer)
+                .unwrap();
+        }
+        // Only half of the last message is available at first.
+        let mut tail = buffer.split_off(buffer.len() - 2);
+
+        let mut server = MessageCodec::server();
+        for message in &messages[..messages.len() - 1] {
+            assert_eq!(server.decode(&mut buffer).unwrap().as_ref(), Some(message));
+        }
+        assert_eq!(server.decode(&mut buffer).unwrap(), None);
+        buffer.extend_from_slice(&tail.split_off(0));
+        assert_eq!(
+            server.decode(&mut buffer).unwrap().as_ref(),
+            messages.last()
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn fragmented_message() {
+        // "Hel" and "lo" as a fragmented text message, with a ping in between.
+        let mut buffer = BytesMut::from(
+            &[
+                0x01, 0x03, b'H', b'e', b'l', 0x89, 0x00, 0x80, 0x02, b'l', b'o',
+            ][..],
+        );
+        let mut client = MessageCodec::client();
+        assert_eq!(
+            client.decode(&mut buffer).unwrap(),
+            Some(Message::Ping(Vec::new()))
+        );
+        assert_eq!(
+            client.decode(&mut buffer).unwrap(),
+            Some(Message::Text(b"Hello".to_vec()))
+        );
+    }
+
+    #[test]
+    fn masked_frame_from_server() {
+        let mut buffer = BytesMut::new();
+        MessageCodec::client()
+            .encode(Message::Text(b"{}".to_vec()), &mut buffer)
+            .unwrap();
+        assert!(MessageCodec::client().decode(&mut buffer).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use base64;
+use bytes::BytesMut;
+use frame::MessageCodec;
+use futures::{Async, Future, Poll};
+use httparse;
+use rand;
+use sha1;
+use tokio_codec::{Framed, FramedParts};
+use tokio_io::{io, AsyncRead, AsyncWrite};
+use url::WsUrl;
+use {Error, ErrorKind, Result};
+
+/// Appended to the key of the handshake request before hashing it, as specified by RFC 6455.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The largest handshake response head accepted from the server.
+const MAX_RESPONSE_HEAD_SIZE: usize = 8 * 1024;
+
+/// Performs the opening handshake over `stream`, returning it framed for sending and receiving
+/// WebSocket messages.
+pub(crate) fn handshake<S>(
+    stream: S,
+    url: &WsUrl,
+) -> Box<Future<Item = Framed<S, MessageCodec>, Error = Error>>
+where
+    S: AsyncRead + AsyncWrite + 'static,
+{
+    let key = base64::encode(&rand::random::<[u8; 16]>());
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        url.resource,
+        url.host_header(),
+        key
+    );
+    let expected_accept = accept_key(&key);
+    trace!("Sending WebSocket handshake to {}", url);
+    let future = io::write_all(stream, request.into_bytes())
+        .from_err()
+        .and_then(move |(stream, _)| ReadResponse {
+            stream: Some(stream),
+            buffer: BytesMut::new(),
+            expected_accept,
+        })
+        .map(|(stream, buffer)| {
+            // The server may already have sent messages after the response.
+            let mut parts = FramedParts::new(stream, MessageCodec::client());
+            parts.read_buf = buffer;
+            Framed::from_parts(parts)
+        });
+    Box::new(future)
+}
+
+/// Returns the `Sec-WebSocket-Accept` value the server must respond with to a handshake request
+/// with the given `Sec-WebSocket-Key`.
+pub(crate) fn accept_key(key: &str) -> String {
+    let digest = sha1::Sha1::from(format!("{}{}", key, WEBSOCKET_GUID)).digest();
+    base64::encode(&digest.bytes())
+}
+
+/// Reads and checks the response to the handshake request. Completes with the stream and
+/// anything read after the response head.
+struct ReadResponse<S> {
+    stream: Option<S>,
+    buffer: BytesMut,
+    expected_accept: String,
+}
+
+impl<S: AsyncRead> Future for ReadResponse<S> {
+    type Item = (S, BytesMut);
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Error> {
+        loop {
+            if let Some(head_len) = check_response(&self.buffer, &self.expected_accept)? {
+                self.buffer.split_to(head_len);
+                let stream = self
+                    .stream
+                    .take()
+                    .expect("ReadResponse polled after completion");
+                return Ok(Async::Ready((stream, self.buffer.take())));
+            }
+            if self.buffer.len() >= MAX_RESPONSE_HEAD_SIZE {
+                bail!(ErrorKind::HandshakeError("Response head too large"));
+            }
+            self.buffer.reserve(1024);
+            let stream = self
+                .stream
+                .as_mut()
+                .expect("ReadResponse polled after completion");
+            if try_ready!(AsyncRead::read_buf(stream, &mut self.buffer)) == 0 {
+                bail!(ErrorKind::HandshakeError(
+                    "Connection closed during the handshake"
+                ));
+            }
+        }
+    }
+}
+
+/// Checks the handshake response in `buffer`. Returns the length of the response head, or
+/// `None` if the whole head has not been received yet.
+fn check_response(buffer: &[u8], expected_accept: &str) -> Result<Option<usize>> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut response = httparse::Response::new(&mut headers);
+    let head_len = match response.parse(buffer) {
+        Ok(httparse::Status::Complete(head_len)) => head_len,
+        Ok(httparse::Status::Partial) => return Ok(None),
+        Err(_) => bail!(ErrorKind::HandshakeError("Invalid HTTP response")),
+    };
+    if response.code != Some(101) {
+        bail!(ErrorKind::HandshakeError(
+            "The server did not switch protocols"
+        ));
+    }
+
+    let header = |name: &str| {
+        response
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| String::from_utf8_lossy(header.value).into_owned())
+            .unwrap_or_default()
+    };
+    if !header("Upgrade").eq_ignore_ascii_case("websocket")
+        || !header("Connection")
+            .split(',')
+            .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+    {
+        bail!(ErrorKind::HandshakeError(
+            "The server did not upgrade to WebSocket"
+        ));
+    }
+    if header("Sec-WebSocket-Accept") != expected_accept {
+        bail!(ErrorKind::HandshakeError(
+            "Invalid Sec-WebSocket-Accept header"
+        ));
+    }
+    Ok(Some(head_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_key_from_rfc() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn check_responses() {
+        let accept = "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=";
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: WebSocket\r\n\
+             Connection: keep-alive, Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept
+        );
+        assert_eq!(
+            check_response(response.as_bytes(), accept).unwrap(),
+            Some(response.len())
+        );
+        assert_eq!(
+            check_response(&response.as_bytes()[..20], accept).unwrap(),
+            None
+        );
+        assert!(check_response(response.as_bytes(), "invalid").is_err());
+
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+        assert!(check_response(response.as_bytes(), accept).is_err());
+    }
+}
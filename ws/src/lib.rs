@@ -0,0 +1,632 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! WebSocket transport implementation for the JSON-RPC 2.0 clients generated by
+//! [`jsonrpc-client-core`](../jsonrpc_client_core/index.html).
+//!
+//! Sends all requests over a single WebSocket connection, running on a Tokio event loop. Any
+//! number of requests can be waiting for their responses at the same time. The responses are
+//! matched back to their requests by id, so the server can respond in any order. Pings from the
+//! server are answered automatically.
+//!
+//! # Connection loss
+//!
+//! The connection is opened when the [`WsTransport`](struct.WsTransport.html) is created and is
+//! never reopened. If it is closed or fails, all requests waiting for a response, and all
+//! requests sent after that, fail with a `ConnectionLost` error. A new transport has to be
+//! created to connect again.
+//!
+//! # TLS
+//!
+//! Support for `wss://` URLs is compiled if the "tls" feature is enabled. The TLS session is
+//! then set up with `native_tls`.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! #[macro_use] extern crate jsonrpc_client_core;
+//! extern crate jsonrpc_client_ws;
+//!
+//! use jsonrpc_client_ws::WsTransport;
+//!
+//! jsonrpc_client!(pub struct FizzBuzzClient {
+//!     /// Returns the fizz-buzz string for the given number.
+//!     pub fn fizz_buzz(&mut self, number: u64) -> RpcRequest<String>;
+//! });
+//!
+//! fn main() {
+//!     let transport = WsTransport::new("ws://api.fizzbuzzexample.org/rpc/")
+//!         .standalone()
+//!         .unwrap();
+//!     let mut client = FizzBuzzClient::new(transport.handle());
+//!     let result1 = client.fizz_buzz(3).call().unwrap();
+//!     let result2 = client.fizz_buzz(4).call().unwrap();
+//!     let result3 = client.fizz_buzz(5).call().unwrap();
+//!
+//!     // Should print "fizz 4 buzz" if the server implemented the service correctly
+//!     println!("{} {} {}", result1, result2, result3);
+//! }
+//! ```
+
+#![deny(missing_docs)]
+
+extern crate base64;
+extern crate bytes;
+#[macro_use]
+pub extern crate error_chain;
+#[macro_use]
+extern crate futures;
+extern crate httparse;
+extern crate jsonrpc_client_core;
+#[macro_use]
+extern crate log;
+extern crate rand;
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
+extern crate sha1;
+extern crate tokio_codec;
+extern crate tokio_core;
+extern crate tokio_io;
+
+#[cfg(feature = "tls")]
+extern crate native_tls;
+#[cfg(feature = "tls")]
+extern crate tokio_tls;
+
+use futures::future::{self, Either};
+use futures::sync::{mpsc, oneshot};
+use futures::{Future, Stream};
+use jsonrpc_client_core::Transport;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tokio_core::reactor::Core;
+pub use tokio_core::reactor::Handle;
+
+mod connection;
+use connection::Connection;
+
+mod frame;
+mod handshake;
+mod stream;
+
+mod url;
+use url::WsUrl;
+
+error_chain! {
+    errors {
+        /// When the URL is not a valid WebSocket URL.
+        InvalidUrl(msg: &'static str) {
+            description("Invalid WebSocket URL")
+            display("Invalid WebSocket URL: {}", msg)
+        }
+        /// When connecting to the server fails.
+        ConnectError {
+            description("Unable to connect to the WebSocket server")
+        }
+        /// When the server does not accept the WebSocket handshake.
+        HandshakeError(msg: &'static str) {
+            description("The WebSocket handshake failed")
+            display("The WebSocket handshake failed: {}", msg)
+        }
+        /// When the server breaks the WebSocket protocol. Fails the connection.
+        ProtocolError(msg: &'static str) {
+            description("WebSocket protocol error")
+            display("WebSocket protocol error: {}", msg)
+        }
+        /// When the connection to the server is closed or fails. All requests waiting for a
+        /// response, and all requests sent after that, fail with this error.
+        ConnectionLost {
+            description("The connection to the WebSocket server was lost")
+        }
+        /// When there was an error in the Tokio Core.
+        TokioCoreError(msg: &'static str) {
+            description("Error with the Tokio Core")
+            display("Error with the Tokio Core: {}", msg)
+        }
+    }
+    foreign_links {
+        Io(::std::io::Error) #[doc = "An I/O error on the connection."];
+        Tls(native_tls::Error) #[cfg(feature = "tls")] #[doc = "An error from the TLS library."];
+    }
+}
+
+type CoreSender = mpsc::UnboundedSender<QueuedRequest>;
+type CoreReceiver = mpsc::UnboundedReceiver<QueuedRequest>;
+type ResponseSender = oneshot::Sender<Result<Vec<u8>>>;
+type WsFramed = tokio_codec::Framed<stream::MaybeTlsStream, frame::MessageCodec>;
+
+/// A request on its way from a `WsHandle` to the event loop, together with the channel to send
+/// the response back on.
+#[derive(Debug)]
+struct QueuedRequest {
+    body: Vec<u8>,
+    /// The ids to match the response with. Empty for notifications, which are answered with an
+    /// empty body as soon as they are sent.
+    ids: Vec<serde_json::Value>,
+    response_tx: ResponseSender,
+}
+
+/// The name of the thread created by `WsTransportBuilder::standalone`, unless configured with
+/// `WsTransportBuilder::thread_name`.
+const DEFAULT_THREAD_NAME: &str = "jsonrpc-ws-transport";
+
+/// The main struct of the WebSocket transport implementation for
+/// [`jsonrpc_client_core`](../jsonrpc_client_core).
+///
+/// Acts as a handle to the connection running on the Tokio `Core` event loop thread. To get a
+/// handle implementing [`Transport`](../jsonrpc_client_core/trait.Transport.html) to use with an
+/// RPC client you call the [`handle`](#method.handle) method.
+///
+/// The connection is closed when this transport, all its clones and all handles created from
+/// them are dropped. A standalone transport then also stops its thread.
+#[derive(Debug, Clone)]
+pub struct WsTransport {
+    request_tx: CoreSender,
+    id: Arc<AtomicUsize>,
+}
+
+impl WsTransport {
+    /// Returns a builder to create a `WsTransport` connected to the given `ws://` or `wss://`
+    /// URL. The URL is not parsed until the transport is created.
+    pub fn new(url: &str) -> WsTransportBuilder {
+        WsTransportBuilder {
+            url: url.to_owned(),
+            thread_name: DEFAULT_THREAD_NAME.to_owned(),
+        }
+    }
+
+    /// Returns a handle to this `WsTransport`, sending requests over its connection.
+    ///
+    /// Used to create instances implementing `jsonrpc_client_core::Transport` for use with RPC
+    /// clients. All handles share the same request ids.
+    pub fn handle(&self) -> WsHandle {
+        WsHandle {
+            request_tx: self.request_tx.clone(),
+            id: self.id.clone(),
+        }
+    }
+
+    fn build(request_tx: CoreSender) -> WsTransport {
+        WsTransport {
+            request_tx,
+            id: Arc::new(AtomicUsize::new(1)),
+        }
+    }
+}
+
+/// Builder type for `WsTransport`.
+///
+/// Can be finished by the [`standalone()`](struct.WsTransportBuilder.html#method.standalone)
+/// method, where it is backed by its own Tokio `Core` running in a separate thread, or by the
+/// [`shared(handle)`](struct.WsTransportBuilder.html#method.shared) method, where it is backed by
+/// the Tokio `Handle` given to it.
+#[derive(Debug, Clone)]
+pub struct WsTransportBuilder {
+    url: String,
+    thread_name: String,
+}
+
+impl WsTransportBuilder {
+    /// Configure the name of the thread created by [`standalone`](#method.standalone). Defaults
+    /// to "jsonrpc-ws-transport".
+    pub fn thread_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.thread_name = name.into();
+        self
+    }
+
+    /// Creates the final `WsTransport` backed by its own Tokio `Core` running in a separate
+    /// thread that is exclusive to this transport instance. To make the transport run on an
+    /// existing event loop, use the [`shared`](#method.shared) method instead.
+    ///
+    /// Blocks until the connection is established. Fails with a `ConnectError` if connecting or
+    /// the WebSocket handshake fails.
+    pub fn standalone(self) -> Result<WsTransport> {
+        let url = WsUrl::parse(&self.url)?;
+        let addrs = url.socket_addrs()?;
+        let (tx, rx) = ::std::sync::mpsc::channel();
+        let thread_builder = thread::Builder::new().name(self.thread_name);
+        thread_builder
+            .spawn(move || {
+                let mut core = match Core::new() {
+                    Ok(core) => core,
+                    Err(e) => {
+                        let error =
+                            Error::with_chain(e, ErrorKind::TokioCoreError("Unable to create"));
+                        tx.send(Err(error)).unwrap();
+                        return;
+                    }
+                };
+                let handle = core.handle();
+                match core.run(connect(&url, addrs, &handle)) {
+                    Err(e) => {
+                        tx.send(Err(e)).unwrap();
+                    }
+                    Ok(framed) => {
+                        let (request_tx, request_rx) = mpsc::unbounded();
+                        tx.send(Ok(request_tx)).unwrap();
+                        if core.run(run_connection(framed, request_rx)).is_err() {
+                            error!("JSON-RPC processing thread had an error");
+                        }
+                        debug!("Standalone WsTransport thread exiting");
+                    }
+                }
+            })
+            .chain_err(|| ErrorKind::TokioCoreError("Unable to spawn the event loop thread"))?;
+
+        let request_tx = rx.recv().unwrap()?;
+        Ok(WsTransport::build(request_tx))
+    }
+
+    /// Creates the final `WsTransport` backed by the Tokio `Handle` given to it. Use the
+    /// [`standalone`](#method.standalone) method to make it create its own internal event loop.
+    ///
+    /// Connects in the background, so this returns right away. If connecting or the WebSocket
+    /// handshake fails, all requests sent through the transport fail with a `ConnectError`.
+    pub fn shared(self, handle: &Handle) -> Result<WsTransport> {
+        let url = WsUrl::parse(&self.url)?;
+        let addrs = url.socket_addrs()?;
+        let (request_tx, request_rx) = mpsc::unbounded();
+        let future = connect(&url, addrs, handle).then(move |result| match result {
+            Ok(framed) => Either::A(run_connection(framed, request_rx)),
+            Err(error) => {
+                error!("Unable to connect to {}: {}", url, error);
+                let cause = error.to_string();
+                Either::B(fail_requests(request_rx, move || {
+                    Error::with_chain(Error::from(cause.clone()), ErrorKind::ConnectError)
+                }))
+            }
+        });
+        handle.spawn(future);
+        Ok(WsTransport::build(request_tx))
+    }
+}
+
+/// Connects to the server and performs the WebSocket handshake.
+fn connect(
+    url: &WsUrl,
+    addrs: Vec<std::net::SocketAddr>,
+    handle: &Handle,
+) -> Box<Future<Item = WsFramed, Error = Error>> {
+    let handshake_url = url.clone();
+    let future = stream::connect(url, addrs, handle)
+        .and_then(move |stream| handshake::handshake(stream, &handshake_url))
+        .map_err(|e| Error::with_chain(e, ErrorKind::ConnectError));
+    Box::new(future)
+}
+
+/// Processes the requests from `request_rx` over the connection until it is lost, and then fails
+/// the requests still coming in with a `ConnectionLost` error.
+fn run_connection(
+    framed: WsFramed,
+    request_rx: CoreReceiver,
+) -> Box<Future<Item = (), Error = ()>> {
+    let future = Connection::new(framed, request_rx).and_then(|request_rx| match request_rx {
+        Some(request_rx) => Either::A(fail_requests(request_rx, || {
+            ErrorKind::ConnectionLost.into()
+        })),
+        None => Either::B(future::ok(())),
+    });
+    Box::new(future)
+}
+
+/// Fails all requests from `request_rx` with the error returned from `error`, until all handles
+/// are dropped.
+fn fail_requests<F>(request_rx: CoreReceiver, error: F) -> Box<Future<Item = (), Error = ()>>
+where
+    F: Fn() -> Error + 'static,
+{
+    Box::new(request_rx.for_each(move |request| {
+        let _ = request.response_tx.send(Err(error()));
+        Ok(())
+    }))
+}
+
+/// A handle to a [`WsTransport`](struct.WsTransport.html). This implements
+/// `jsonrpc_client_core::Transport` and can be used as the transport for a RPC client generated
+/// by the `jsonrpc_client!` macro.
+///
+/// Data sent with `Transport::send` that has no id, because it is not valid JSON or is a
+/// notification, is treated like a notification. Its future completes with an empty body as
+/// soon as it is sent.
+#[derive(Debug, Clone)]
+pub struct WsHandle {
+    request_tx: CoreSender,
+    id: Arc<AtomicUsize>,
+}
+
+impl WsHandle {
+    fn queue(
+        &self,
+        body: Vec<u8>,
+        ids: Vec<serde_json::Value>,
+    ) -> Box<Future<Item = Vec<u8>, Error = Error> + Send> {
+        let (response_tx, response_rx) = oneshot::channel();
+        let request = QueuedRequest {
+            body,
+            ids,
+            response_tx,
+        };
+        if let Err(e) = self.request_tx.unbounded_send(request) {
+            return Box::new(future::err(Error::with_chain(
+                e,
+                ErrorKind::TokioCoreError("Not listening for requests"),
+            )));
+        }
+        let future = response_rx
+            .map_err(|e| {
+                Error::with_chain(
+                    e,
+                    ErrorKind::TokioCoreError("Died without returning response"),
+                )
+            })
+            .and_then(future::result);
+        Box::new(future)
+    }
+}
+
+impl Transport for WsHandle {
+    type Future = Box<Future<Item = Vec<u8>, Error = Self::Error> + Send>;
+    type Error = Error;
+
+    fn get_next_id(&mut self) -> u64 {
+        self.id.fetch_add(1, Ordering::SeqCst) as u64
+    }
+
+    fn send(&self, json_data: Vec<u8>) -> Self::Future {
+        let ids = match serde_json::from_slice(&json_data) {
+            Ok(json) => connection::message_ids(&json),
+            Err(_) => Vec::new(),
+        };
+        self.queue(json_data, ids)
+    }
+
+    fn send_notification(&self, json_data: Vec<u8>) -> Self::Future {
+        self.queue(json_data, Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use frame::{Message, MessageCodec};
+    use jsonrpc_client_core::{call_method, send_notification};
+    use serde_json::Value as JsonValue;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc as std_mpsc;
+    use tokio_codec::{Decoder, Encoder};
+
+    /// The server side of a WebSocket connection, using blocking I/O.
+    struct ServerConnection {
+        stream: TcpStream,
+        buffer: BytesMut,
+        codec: MessageCodec,
+    }
+
+    impl ServerConnection {
+        /// Accepts a connection on `listener` and completes the handshake.
+        fn accept(listener: &TcpListener) -> Self {
+            let (mut stream, _) = listener.accept().unwrap();
+            let head = read_head(&mut stream);
+            let key = head
+                .lines()
+                .filter_map(|line| {
+                    let mut parts = line.splitn(2, ':');
+                    match (parts.next(), parts.next()) {
+                        (Some(name), Some(value))
+                            if name.eq_ignore_ascii_case("sec-websocket-key") =>
+                        {
+                            Some(value.trim().to_owned())
+                        }
+                        _ => None,
+                    }
+                })
+                .next()
+                .expect("No Sec-WebSocket-Key");
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                handshake::accept_key(&key)
+            )
+            .unwrap();
+            ServerConnection {
+                stream,
+                buffer: BytesMut::new(),
+                codec: MessageCodec::server(),
+            }
+        }
+
+        fn receive(&mut self) -> Message {
+            loop {
+                if let Some(message) = self.codec.decode(&mut self.buffer).unwrap() {
+                    return message;
+                }
+                let mut chunk = [0; 1024];
+                let len = self.stream.read(&mut chunk).unwrap();
+                assert!(len > 0, "Connection closed by the client");
+                self.buffer.extend_from_slice(&chunk[..len]);
+            }
+        }
+
+        fn receive_json(&mut self) -> JsonValue {
+            match self.receive() {
+                Message::Text(data) => serde_json::from_slice(&data).unwrap(),
+                message => panic!("Unexpected message: {:?}", message),
+            }
+        }
+
+        fn send(&mut self, message: Message) {
+            let mut buffer = BytesMut::new();
+            self.codec.encode(message, &mut buffer).unwrap();
+            self.stream.write_all(&buffer).unwrap();
+        }
+
+        fn send_json(&mut self, json: JsonValue) {
+            self.send(Message::Text(serde_json::to_vec(&json).unwrap()));
+        }
+    }
+
+    fn read_head(stream: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            stream.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    /// Starts a server handling a single connection with `f` on a separate thread. Returns the
+    /// URL to connect to it.
+    fn spawn_server<F: FnOnce(ServerConnection) + Send + 'static>(f: F) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}/", listener.local_addr().unwrap());
+        thread::spawn(move || f(ServerConnection::accept(&listener)));
+        url
+    }
+
+    fn assert_error_kind<T>(result: Result<T>, expected: &ErrorKind) {
+        match result {
+            Err(error) => assert_eq!(error.kind().to_string(), expected.to_string()),
+            Ok(_) => panic!("Request did not fail"),
+        }
+    }
+
+    #[test]
+    fn responses_are_matched_by_id() {
+        let url = spawn_server(|mut server| {
+            let first = server.receive_json();
+            let second = server.receive_json();
+            for request in &[second, first] {
+                server.send_json(json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": request["params"][0],
+                }));
+            }
+        });
+        let mut handle = WsTransport::new(&url).standalone().unwrap().handle();
+
+        let first = call_method::<_, _, String>(&mut handle, "echo".to_owned(), ("first",));
+        let second = call_method::<_, _, String>(&mut handle, "echo".to_owned(), ("second",));
+        let (first, second) = first.join(second).wait().unwrap();
+        assert_eq!(first, "first");
+        assert_eq!(second, "second");
+    }
+
+    #[test]
+    fn server_pings_are_answered() {
+        let url = spawn_server(|mut server| {
+            let request = server.receive_json();
+            server.send(Message::Ping(b"still there?".to_vec()));
+            assert_eq!(server.receive(), Message::Pong(b"still there?".to_vec()));
+            server.send_json(json!({"jsonrpc": "2.0", "id": request["id"], "result": "yes"}));
+        });
+        let mut handle = WsTransport::new(&url).standalone().unwrap().handle();
+
+        let result = call_method::<_, _, String>(&mut handle, "ask".to_owned(), ())
+            .call()
+            .unwrap();
+        assert_eq!(result, "yes");
+    }
+
+    #[test]
+    fn notifications_and_close() {
+        let (message_tx, message_rx) = std_mpsc::channel();
+        let url = spawn_server(move |mut server| {
+            let notification = serde_json::to_vec(&server.receive_json()).unwrap();
+            message_tx.send(Message::Text(notification)).unwrap();
+            let close = server.receive();
+            message_tx.send(close.clone()).unwrap();
+            server.send(close);
+        });
+        let mut handle = WsTransport::new(&url).standalone().unwrap().handle();
+
+        send_notification(&mut handle, "log".to_owned(), ("message",))
+            .call()
+            .unwrap();
+        let notification = json!({"jsonrpc": "2.0", "method": "log", "params": ["message"]});
+        assert_eq!(
+            message_rx.recv().unwrap(),
+            Message::Text(serde_json::to_vec(&notification).unwrap())
+        );
+        drop(handle);
+        assert_eq!(message_rx.recv().unwrap(), Message::Close(vec![0x03, 0xE8]));
+    }
+
+    #[test]
+    fn canceled_requests_do_not_keep_connection_open() {
+        let (message_tx, message_rx) = std_mpsc::channel();
+        let url = spawn_server(move |mut server| {
+            let request = serde_json::to_vec(&server.receive_json()).unwrap();
+            message_tx.send(Message::Text(request)).unwrap();
+            let close = server.receive();
+            message_tx.send(close.clone()).unwrap();
+            server.send(close);
+        });
+        let mut handle = WsTransport::new(&url).standalone().unwrap().handle();
+
+        let request = call_method::<_, _, String>(&mut handle, "hang".to_owned(), ());
+        message_rx.recv().unwrap();
+        drop(request);
+        drop(handle);
+        assert_eq!(message_rx.recv().unwrap(), Message::Close(vec![0x03, 0xE8]));
+    }
+
+    #[test]
+    fn connection_loss_fails_requests() {
+        let url = spawn_server(|mut server| {
+            server.receive_json();
+        });
+        let mut handle = WsTransport::new(&url).standalone().unwrap().handle();
+
+        let id = handle.get_next_id();
+        let request = format!(r#"{{"jsonrpc": "2.0", "id": {}, "method": "hang"}}"#, id);
+        let pending = handle.send(request.clone().into_bytes()).wait();
+        assert_error_kind(pending, &ErrorKind::ConnectionLost);
+        let later = handle.send(request.into_bytes()).wait();
+        assert_error_kind(later, &ErrorKind::ConnectionLost);
+    }
+
+    #[test]
+    fn rejected_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}/", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            read_head(&mut stream);
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        });
+
+        let error = WsTransport::new(&url).standalone().unwrap_err();
+        assert_error_kind::<()>(Err(error), &ErrorKind::ConnectError);
+    }
+
+    #[test]
+    fn shared_connect_error() {
+        // Nothing is listening on the port once the listener is dropped.
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut core = Core::new().unwrap();
+        let handle = WsTransport::new(&format!("ws://{}/", address))
+            .shared(&core.handle())
+            .unwrap()
+            .handle();
+
+        let request = br#"{"jsonrpc": "2.0", "id": 1, "method": "hang"}"#.to_vec();
+        let result = core.run(handle.send(request));
+        assert_error_kind(result, &ErrorKind::ConnectError);
+    }
+}
@@ -0,0 +1,126 @@
+use futures::future::{self, Either, Loop};
+use futures::{Future, Poll};
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use tokio_core::net::TcpStream;
+use tokio_core::reactor::Handle;
+use tokio_io::{AsyncRead, AsyncWrite};
+use url::WsUrl;
+use Error;
+
+#[cfg(feature = "tls")]
+use native_tls::TlsConnector;
+#[cfg(feature = "tls")]
+use tokio_tls::{TlsConnectorExt, TlsStream};
+
+/// A connection to the server, encrypted for `wss://` URLs.
+pub(crate) enum MaybeTlsStream {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(TlsStream<TcpStream>),
+}
+
+impl Read for MaybeTlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            MaybeTlsStream::Plain(ref mut stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            MaybeTlsStream::Tls(ref mut stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for MaybeTlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            MaybeTlsStream::Plain(ref mut stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            MaybeTlsStream::Tls(ref mut stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            MaybeTlsStream::Plain(ref mut stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            MaybeTlsStream::Tls(ref mut stream) => stream.flush(),
+        }
+    }
+}
+
+impl AsyncRead for MaybeTlsStream {}
+
+impl AsyncWrite for MaybeTlsStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match *self {
+            MaybeTlsStream::Plain(ref mut stream) => AsyncWrite::shutdown(stream),
+            #[cfg(feature = "tls")]
+            MaybeTlsStream::Tls(ref mut stream) => stream.shutdown(),
+        }
+    }
+}
+
+/// Connects to the first of `addrs` accepting the connection, and starts a TLS session on it if
+/// `url` is a `wss://` URL.
+pub(crate) fn connect(
+    url: &WsUrl,
+    addrs: Vec<SocketAddr>,
+    handle: &Handle,
+) -> Box<Future<Item = MaybeTlsStream, Error = Error>> {
+    let handle = handle.clone();
+    let tcp_stream =
+        future::loop_fn(
+            (addrs.into_iter(), None),
+            move |(mut addrs, last_error)| match addrs.next() {
+                Some(addr) => {
+                    trace!("Connecting to {}", addr);
+                    Either::A(
+                        TcpStream::connect(&addr, &handle).then(move |result| match result {
+                            Ok(stream) => Ok(Loop::Break(stream)),
+                            Err(error) => {
+                                debug!("Unable to connect to {}: {}", addr, error);
+                                Ok(Loop::Continue((addrs, Some(error))))
+                            }
+                        }),
+                    )
+                }
+                None => Either::B(future::err(last_error.unwrap_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::AddrNotAvailable,
+                        "No addresses to connect to",
+                    )
+                }))),
+            },
+        )
+        .from_err();
+    if url.secure {
+        tls_connect(&url.host, tcp_stream)
+    } else {
+        Box::new(tcp_stream.map(MaybeTlsStream::Plain))
+    }
+}
+
+#[cfg(feature = "tls")]
+fn tls_connect<F>(host: &str, tcp_stream: F) -> Box<Future<Item = MaybeTlsStream, Error = Error>>
+where
+    F: Future<Item = TcpStream, Error = Error> + 'static,
+{
+    let host = host.to_owned();
+    let future = future::result(TlsConnector::builder().and_then(|builder| builder.build()))
+        .from_err()
+        .join(tcp_stream)
+        .and_then(move |(connector, tcp_stream)| {
+            connector.connect_async(&host, tcp_stream).from_err()
+        })
+        .map(MaybeTlsStream::Tls);
+    Box::new(future)
+}
+
+/// Never called, since `WsUrl::parse` rejects `wss://` URLs without the "tls" feature.
+#[cfg(not(feature = "tls"))]
+fn tls_connect<F>(_host: &str, _tcp_stream: F) -> Box<Future<Item = MaybeTlsStream, Error = Error>>
+where
+    F: Future<Item = TcpStream, Error = Error> + 'static,
+{
+    unreachable!("wss URL accepted without the \"tls\" feature")
+}
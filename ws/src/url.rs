@@ -0,0 +1,159 @@
+use std::fmt;
+use std::net::{SocketAddr, ToSocketAddrs};
+use {ErrorKind, Result, ResultExt};
+
+/// A parsed `ws://` or `wss://` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WsUrl {
+    /// Set for `wss://` URLs, connecting over TLS.
+    pub secure: bool,
+    /// The host, without the brackets around IPv6 addresses.
+    pub host: String,
+    pub port: u16,
+    /// The path and query sent in the handshake request.
+    pub resource: String,
+}
+
+impl WsUrl {
+    pub fn parse(url: &str) -> Result<WsUrl> {
+        let scheme_end = url
+            .find("://")
+            .ok_or(ErrorKind::InvalidUrl("Missing scheme"))?;
+        let secure = match url[..scheme_end].to_lowercase().as_str() {
+            "ws" => false,
+            "wss" if cfg!(feature = "tls") => true,
+            "wss" => bail!(ErrorKind::InvalidUrl(
+                "wss URLs require the \"tls\" feature"
+            )),
+            _ => bail!(ErrorKind::InvalidUrl("The scheme must be ws or wss")),
+        };
+        let rest = &url[scheme_end + 3..];
+        let rest = match rest.find('#') {
+            Some(fragment_start) => &rest[..fragment_start],
+            None => rest,
+        };
+        let authority_end = rest.find(&['/', '?'][..]).unwrap_or(rest.len());
+        let (authority, resource) = rest.split_at(authority_end);
+        if authority.contains('@') {
+            bail!(ErrorKind::InvalidUrl(
+                "Credentials in the URL are not supported"
+            ));
+        }
+
+        let (host, port) = if authority.starts_with('[') {
+            let host_end = authority
+                .find(']')
+                .ok_or(ErrorKind::InvalidUrl("Unterminated IPv6 address"))?;
+            (&authority[1..host_end], &authority[host_end + 1..])
+        } else {
+            match authority.find(':') {
+                Some(port_start) => authority.split_at(port_start),
+                None => (authority, ""),
+            }
+        };
+        if host.is_empty() {
+            bail!(ErrorKind::InvalidUrl("Missing host"));
+        }
+        let port = match port {
+            "" => {
+                if secure {
+                    443
+                } else {
+                    80
+                }
+            }
+            port if port.starts_with(':') => port[1..]
+                .parse()
+                .map_err(|_| ErrorKind::InvalidUrl("Invalid port"))?,
+            _ => bail!(ErrorKind::InvalidUrl("Invalid host")),
+        };
+        let resource = if resource.starts_with('/') {
+            resource.to_owned()
+        } else {
+            format!("/{}", resource)
+        };
+
+        Ok(WsUrl {
+            secure,
+            host: host.to_owned(),
+            port,
+            resource,
+        })
+    }
+
+    /// Returns the value of the `Host` header for the handshake request. The port is only
+    /// included if it is not the default one.
+    pub fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        let default_port = if self.secure { 443 } else { 80 };
+        if self.port == default_port {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+
+    /// Resolves the host into the addresses to try connecting to. Blocks while resolving.
+    pub fn socket_addrs(&self) -> Result<Vec<SocketAddr>> {
+        let addrs = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .chain_err(|| ErrorKind::ConnectError)?;
+        Ok(addrs.collect())
+    }
+}
+
+impl fmt::Display for WsUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scheme = if self.secure { "wss" } else { "ws" };
+        write!(f, "{}://{}{}", scheme, self.host_header(), self.resource)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_urls() {
+        let url = WsUrl::parse("ws://localhost:8546").unwrap();
+        assert_eq!(
+            url,
+            WsUrl {
+                secure: false,
+                host: "localhost".to_owned(),
+                port: 8546,
+                resource: "/".to_owned(),
+            }
+        );
+        assert_eq!(url.host_header(), "localhost:8546");
+
+        let url = WsUrl::parse("WS://[::1]/rpc?key=value#fragment").unwrap();
+        assert_eq!(url.host, "::1");
+        assert_eq!(url.port, 80);
+        assert_eq!(url.resource, "/rpc?key=value");
+        assert_eq!(url.to_string(), "ws://[::1]/rpc?key=value");
+
+        assert_eq!(WsUrl::parse("ws://example.com?a").unwrap().resource, "/?a");
+    }
+
+    #[test]
+    fn invalid_urls() {
+        for url in &[
+            "localhost:8546",
+            "http://localhost",
+            "ws://:8546",
+            "ws://localhost:port",
+            "ws://user:password@localhost",
+            "ws://[::1",
+        ] {
+            match *WsUrl::parse(url).unwrap_err().kind() {
+                ErrorKind::InvalidUrl(_) => (),
+                ref kind => panic!("Wrong error kind for {}: {:?}", url, kind),
+            }
+        }
+    }
+}